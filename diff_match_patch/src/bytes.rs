@@ -0,0 +1,185 @@
+/*Diff, delta and patch functions for byte strings.
+
+Bytes don't have to be valid UTF-8.  Diffs run on the generic token engine,
+while patches map every byte to the char with the same value (Latin-1) so
+that the char patch machinery can be reused unchanged; the mapping is one to
+one, so no data is lost on the way back.
+*/
+
+use super::dmp::{Diff, Dmp, Operation, Patch, delta_length, delta_tokens, percent_decode_bytes};
+use super::errors::DmpError;
+use super::tokens::DiffOp;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BytePatch {
+    // patch object over bytes, offsets and lengths are in bytes.
+    pub diffs: Vec<DiffOp<u8>>,
+    pub start1: i32,
+    pub start2: i32,
+    pub length1: i32,
+    pub length2: i32
+}
+
+fn bytes_to_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| *byte as char).collect()
+}
+
+fn latin1_to_bytes(text: &str) -> Vec<u8> {
+    // only ever called on text built by bytes_to_latin1, so every char fits a byte.
+    text.chars().map(|ch| ch as u8).collect()
+}
+
+impl From<&BytePatch> for Patch {
+    fn from(patch: &BytePatch) -> Patch {
+        let diffs = patch.diffs.iter().map(|diff| Diff::new(diff.operation, bytes_to_latin1(&diff.tokens))).collect();
+        Patch::new(diffs, patch.start1, patch.start2, patch.length1, patch.length2)
+    }
+}
+
+impl From<Patch> for BytePatch {
+    fn from(patch: Patch) -> BytePatch {
+        BytePatch {
+            diffs: patch.diffs.iter().map(|diff| DiffOp::new(diff.operation, latin1_to_bytes(&diff.text))).collect(),
+            start1: patch.start1,
+            start2: patch.start2,
+            length1: patch.length1,
+            length2: patch.length2
+        }
+    }
+}
+
+impl Dmp {
+    pub fn diff_bytes(&self, bytes1: &[u8], bytes2: &[u8]) -> Vec<DiffOp<u8>> {
+        /*
+        Find the differences between two byte strings.
+
+        Args:
+            bytes1: Old bytes to be diffed.
+            bytes2: New bytes to be diffed.
+
+        Returns:
+            Vector of byte diffs as changes.
+        */
+        self.diff_tokens(bytes1, bytes2)
+    }
+
+    pub fn diff_bytes_todelta(&self, diffs: &[DiffOp<u8>]) -> String {
+        /*
+        Crush a byte diff into an encoded string.  Same format as diff_todelta,
+        but lengths count bytes and every inserted byte outside the safe set
+        is escaped as %xx, so any byte string round-trips.
+
+        Args:
+            diffs: Vector of byte diff object.
+
+        Returns:
+            Delta text.
+        */
+        let mut text: Vec<String> = vec![];
+        for diff in diffs {
+            match diff.operation {
+                Operation::Insert => {
+                    let mut token = "+".to_string();
+                    for byte in &diff.tokens {
+                        if byte.is_ascii_alphanumeric() || b"-_.!~*()';/?:@&=+$,# ".contains(byte) {
+                            token.push(*byte as char);
+                        }
+                        else {
+                            token += format!("%{byte:02X}").as_str();
+                        }
+                    }
+                    text.push(token);
+                },
+                Operation::Delete => text.push(format!("-{}", diff.tokens.len())),
+                Operation::Equal => text.push(format!("={}", diff.tokens.len()))
+            }
+        }
+        text.join("\t")
+    }
+
+    pub fn diff_bytes_from_delta(&self, bytes1: &[u8], delta: &str) -> Result<Vec<DiffOp<u8>>, DmpError> {
+        /*
+        Given the original bytes1 and a delta from diff_bytes_todelta, compute
+        the full byte diff.
+
+        Args:
+            bytes1: Source bytes for the diff.
+            delta: Delta text.
+
+        Returns:
+            Vector of byte diff object.
+
+        Errors:
+            DmpError: If invalid input.
+        */
+        let mut diffs: Vec<DiffOp<u8>> = vec![];
+        let mut offset = 0;
+        for (column, operation, content) in delta_tokens(delta) {
+            if operation == '+' {
+                diffs.push(DiffOp::new(Operation::Insert, percent_decode_bytes(content, 1, column + 1)?));
+                continue;
+            }
+            let length = delta_length(operation, content, column)?;
+            if length > bytes1.len() - offset {
+                return Err(DmpError::LengthMismatch { line: 1, column, expected: bytes1.len(), actual: offset.saturating_add(length) });
+            }
+            let operation = if operation == '=' { Operation::Equal } else { Operation::Delete };
+            diffs.push(DiffOp::new(operation, bytes1[offset..(offset + length)].to_vec()));
+            offset += length;
+        }
+
+        // we should have consumed all bytes
+        if offset != bytes1.len() {
+            return Err(DmpError::LengthMismatch { line: 1, column: delta.len() + 1, expected: bytes1.len(), actual: offset });
+        }
+        Ok(diffs)
+    }
+
+    pub fn patch_make_bytes(&self, bytes1: &[u8], bytes2: &[u8]) -> Vec<BytePatch> {
+        /*
+        Compute a list of patches to turn bytes1 into bytes2.
+
+        Args:
+            bytes1: First bytes.
+            bytes2: Second bytes.
+
+        Returns:
+            Vector of BytePatch objects.
+        */
+        let patches = self.patch_make1(&bytes_to_latin1(bytes1), &bytes_to_latin1(bytes2));
+        patches.into_iter().map(BytePatch::from).collect()
+    }
+
+    pub fn patch_make_bytes_diffs(&self, bytes1: &[u8], diffs: &[DiffOp<u8>]) -> Vec<BytePatch> {
+        /*
+        Compute a list of patches to turn bytes1 into bytes2 from an existing
+        byte diff.
+
+        Args:
+            bytes1: First bytes.
+            diffs: Vector of byte diff object.
+
+        Returns:
+            Vector of BytePatch objects.
+        */
+        let diffs: Vec<Diff> = diffs.iter().map(|diff| Diff::new(diff.operation, bytes_to_latin1(&diff.tokens))).collect();
+        let patches = self.patch_make4(&bytes_to_latin1(bytes1), &diffs);
+        patches.into_iter().map(BytePatch::from).collect()
+    }
+
+    pub fn patch_apply_bytes(&self, patches: &[BytePatch], source: &[u8]) -> (Vec<u8>, Vec<bool>) {
+        /*
+        Merge a set of byte patches onto some bytes, allowing for errors.
+
+        Args:
+            patches: Vector of BytePatch objects.
+            source: Old bytes.
+
+        Returns:
+            The new bytes and one boolean per applied patch.
+        */
+        let patches: Vec<Patch> = patches.iter().map(Patch::from).collect();
+        let (text, results) = self.patch_apply(&patches, &bytes_to_latin1(source));
+        (latin1_to_bytes(&text), results)
+    }
+}
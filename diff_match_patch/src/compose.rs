@@ -0,0 +1,189 @@
+/*Patch composition.
+
+A patch set is read as a walk over its source text: the stretches between
+patches are kept without knowing what they hold, while the context and
+edits of every patch carry their text.  Two such walks compose like the
+operations of operational transform.  Every char a deletion removes is
+spelled out by one of the two patch sets, so the intermediate text is never
+needed; the result is cut back into patches wherever nothing is known about
+the text.
+*/
+
+use super::dmp::{Diff, Dmp, Operation, Patch};
+
+#[derive(Debug, Clone)]
+struct ComposeOp {
+    // Equal keeps length chars, Delete and Insert remove or add text.  text is
+    // None for stretches of the source no patch describes.
+    operation: Operation,
+    length: usize,
+    text: Option<Vec<char>>
+}
+
+impl ComposeOp {
+    fn new(operation: Operation, text: Vec<char>) -> ComposeOp {
+        ComposeOp { operation, length: text.len(), text: Some(text) }
+    }
+
+    fn unknown(length: usize) -> ComposeOp {
+        ComposeOp { operation: Operation::Equal, length, text: None }
+    }
+
+    fn split_off(&mut self, length: usize) -> ComposeOp {
+        // take the first length chars off the op.
+        let head = ComposeOp {
+            operation: self.operation,
+            length,
+            text: self.text.as_mut().map(|text| text.drain(..length).collect())
+        };
+        self.length -= length;
+        head
+    }
+}
+
+pub(crate) fn patch_gaps(patches: &[Patch]) -> Vec<(usize, &Patch)> {
+    // every patch with the length of the text1 no patch describes before it.
+    let mut gaps = vec![];
+    let mut position = 0;
+    // Patch lists have a rolling context: start1 counts the chars added by
+    // the patches before it.
+    let mut delta = 0;
+    for patch in patches {
+        let start = (patch.start1 - delta).max(0) as usize;
+        gaps.push((start.saturating_sub(position), patch));
+        position = position.max(start + patch.length1.max(0) as usize);
+        delta += patch.length2 - patch.length1;
+    }
+    gaps
+}
+
+fn compose_ops(patches: &[Patch]) -> Vec<ComposeOp> {
+    // the walk over text1 described by a patch set.
+    let mut ops: Vec<ComposeOp> = vec![];
+    for (gap, patch) in patch_gaps(patches) {
+        if gap > 0 {
+            ops.push(ComposeOp::unknown(gap));
+        }
+        for diff in &patch.diffs {
+            ops.push(ComposeOp::new(diff.operation, diff.text.chars().collect()));
+        }
+    }
+    ops
+}
+
+fn compose_push(ops: &mut Vec<ComposeOp>, op: ComposeOp) {
+    // append op, merging it into the last one when they are alike.
+    if op.length == 0 {
+        return;
+    }
+    if let Some(last) = ops.last_mut() {
+        if last.operation == op.operation && last.text.is_some() == op.text.is_some() {
+            last.length += op.length;
+            if let (Some(text), Some(more)) = (last.text.as_mut(), op.text) {
+                text.extend(more);
+            }
+            return;
+        }
+    }
+    ops.push(op);
+}
+
+impl Dmp {
+    pub fn patch_compose(&self, patches1: &[Patch], patches2: &[Patch]) -> Vec<Patch> {
+        /*
+        Squash two patch sets applied one after the other into one.  Given
+        patches1 taking text1 to text2 and patches2 taking text2 to text3,
+        return patches taking text1 to text3.
+
+        Args:
+            patches1: Vector of Patch objects for text1 to text2.
+            patches2: Vector of Patch objects for text2 to text3.
+
+        Returns:
+            Vector of Patch objects.
+        */
+        let mut ops1 = compose_ops(patches1).into_iter();
+        let mut ops2 = compose_ops(patches2).into_iter();
+        let mut op1 = ops1.next();
+        let mut op2 = ops2.next();
+        let mut ops: Vec<ComposeOp> = vec![];
+        loop {
+            // Text1 deleted by patches1 and text inserted by patches2 go straight through.
+            if let Some(op) = op1.as_ref().filter(|op| op.operation == Operation::Delete) {
+                compose_push(&mut ops, op.clone());
+                op1 = ops1.next();
+                continue;
+            }
+            if let Some(op) = op2.as_ref().filter(|op| op.operation == Operation::Insert) {
+                compose_push(&mut ops, op.clone());
+                op2 = ops2.next();
+                continue;
+            }
+            // What is left walks over text2: patches1 keeps or inserts it,
+            // patches2 keeps or deletes it.  Past the last patch text2 is kept.
+            let (first, second) = match (op1.as_mut(), op2.as_mut()) {
+                (None, None) => break,
+                (Some(first), Some(second)) => {
+                    let length = first.length.min(second.length);
+                    (first.split_off(length), second.split_off(length))
+                },
+                (Some(first), None) => {
+                    let length = first.length;
+                    (first.split_off(length), ComposeOp::unknown(length))
+                },
+                (None, Some(second)) => {
+                    let length = second.length;
+                    (ComposeOp::unknown(length), second.split_off(length))
+                }
+            };
+            match (first.operation, second.operation) {
+                (Operation::Equal, Operation::Equal) => compose_push(&mut ops, ComposeOp { text: first.text.or(second.text), ..first }),
+                (Operation::Equal, _) => compose_push(&mut ops, second),
+                (_, Operation::Equal) => compose_push(&mut ops, first),
+                // Inserted by patches1, deleted again by patches2.
+                _ => {}
+            }
+            if op1.as_ref().is_some_and(|op| op.length == 0) {
+                op1 = ops1.next();
+            }
+            if op2.as_ref().is_some_and(|op| op.length == 0) {
+                op2 = ops2.next();
+            }
+        }
+
+        // Cut the walk into patches at every stretch of unknown text.
+        let mut patches: Vec<Patch> = vec![];
+        let mut char_count = 0;
+        let mut ops = ops.into_iter().peekable();
+        while let Some(op) = ops.next() {
+            let text = match op.text {
+                Some(text) => text,
+                None => {
+                    char_count += op.length;
+                    continue;
+                }
+            };
+            let mut patch = Patch::new(vec![], char_count as i32, char_count as i32, 0, 0);
+            patch.diffs.push(Diff::new(op.operation, text.into_iter().collect()));
+            while let Some(op) = ops.next_if(|op| op.text.is_some()) {
+                patch.diffs.push(Diff::new(op.operation, op.text.unwrap().into_iter().collect()));
+            }
+            self.diff_cleanup_merge(&mut patch.diffs);
+            for diff in &patch.diffs {
+                let length = diff.text.chars().count();
+                if diff.operation != Operation::Insert {
+                    patch.length1 += length as i32;
+                }
+                if diff.operation != Operation::Delete {
+                    patch.length2 += length as i32;
+                    char_count += length;
+                }
+            }
+            // Context on its own changes nothing.
+            if patch.diffs.iter().any(|diff| diff.operation != Operation::Equal) {
+                patches.push(patch);
+            }
+        }
+        patches
+    }
+}
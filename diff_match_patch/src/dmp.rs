@@ -6,15 +6,23 @@ Applies the patch onto another text, allowing for errors.
 
 use std::fmt::{self, Display};
 use core::char;
-use std::iter::FromIterator;
+use std::convert::TryFrom;
 use std::collections::HashMap;
 use std::result::Result;
-use std::error::Error;
+use std::ops::Range;
+use std::sync::{Arc, OnceLock};
 use std::time::Instant;
 use regex::Regex;
 extern crate  url;
 
-use super::percent_encoding::percent_decode_u16;
+use super::errors::DmpError;
+use super::grapheme::grapheme_boundary;
+use super::merge::MergeResolution;
+use super::normalize::WhitespaceMode;
+use super::pretty::{DiffFormatter, HtmlFormatter};
+use super::tokenizer::{default_word_boundary, LineTokenizer, Tokenizer, WordBoundary};
+use super::tokens::DiffOp;
+use super::percent_encoding::{percent_decode_u16, DecodeError};
 
 use url::percent_encoding::{
     utf8_percent_encode,
@@ -22,6 +30,29 @@ use url::percent_encoding::{
     USERINFO_ENCODE_SET,
     };
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffAlgorithm {
+    // Myers' O(ND) bisection, as in the reference implementation.
+    Myers,
+    // Anchor on tokens unique to both texts, bisect what is left.
+    Patience,
+    // Anchor on the region with the least frequent tokens, like git diff --histogram.
+    Histogram
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffGranularity {
+    // Diff char by char.
+    Char,
+    // Diff whole words, split by Dmp::word_boundary.
+    Word,
+    // Diff whole lines.
+    Line,
+    // Diff the tokens of the tokenizer registered under this name, char by
+    // char if there is none.
+    Custom(&'static str)
+}
+
 pub enum LengthUnit {
     #[allow(dead_code)]
     UnicodeScalar,
@@ -52,12 +83,46 @@ pub struct Dmp {
     the contents have to be to match the expected contents. (0.0 = perfection,
     1.0 = very loose).  Note that Match_Threshold controls how closely the
     end points of a delete need to match.*/
-    pub patch_delete_threshold: f32
+    pub patch_delete_threshold: f32,
+    // Algorithm used once the diff speedups can't split the problem any further.
+    pub diff_algorithm: DiffAlgorithm,
+    // Lines of context around each hunk of a unified diff.
+    pub unified_context: i32,
+    // How merge3 settles conflicting edits.
+    pub merge_resolution: MergeResolution,
+    // How alike a deletion and an insertion must be to be a move (0.0 = anything, 1.0 = identical).
+    pub move_threshold: f32,
+    // Shortest text, in chars, that diff_moves considers.
+    pub move_min_length: i32,
+    // How diff_main and diff_linemode compare whitespace.
+    pub whitespace_mode: WhitespaceMode,
+    // Whether inserted or deleted lines of whitespace count as changes.
+    pub ignore_blank_lines: bool,
+    // Whether letters differing only in case are equal.
+    pub ignore_case: bool,
+    // Smallest unit diff_main reports.
+    pub diff_granularity: DiffGranularity,
+    // Separators between words, every match is a token of its own.
+    pub word_boundary: Regex,
+    // Tokenizers registered for DiffGranularity::Custom.
+    pub tokenizers: HashMap<&'static str, Arc<dyn Tokenizer>>,
+    // Whether diffs and patch context may never split a grapheme cluster.
+    pub grapheme_clusters: bool,
+    // How alike a deleted and an inserted line must be for diff_review to pair them (0.0 = anything, 1.0 = identical).
+    pub review_threshold: f32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    // kind of a diff, stored as -1, 0 and 1 by older versions of this crate.
+    Delete,
+    Equal,
+    Insert
 }
 
 pub struct Diff {
     // diff object
-    pub operation: i32,
+    pub operation: Operation,
     pub text: String,
 }
 pub struct Patch {
@@ -68,10 +133,29 @@ pub struct Patch {
     pub length1: i32,
     pub length2: i32
 }
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApplyReport {
+    // what happened to one patch in patch_apply_report.
+    pub applied: bool,
+    // char offset the patch, context included, was expected at and the one
+    // it was found at.
+    pub expected_loc: i32,
+    pub actual_loc: Option<i32>,
+    // actual_loc - expected_loc, carried over to the patches that follow.
+    pub delta: i32,
+    // The text matched the patch's context exactly.
+    pub exact: bool,
+    // Levenshtein distance between the expected and the matched text, over
+    // the expected length.  Checked against patch_delete_threshold.
+    pub levenshtein_ratio: f32,
+    // chars of the output text covered by the patch and its context.
+    pub range: Option<Range<usize>>
+}
+
 impl Diff {
     // A new diff diff object created.
     #[allow(dead_code)]
-    pub fn new(operation: i32, text: String) -> Diff {
+    pub fn new(operation: Operation, text: String) -> Diff {
         Diff {
             operation,
             text
@@ -79,6 +163,42 @@ impl Diff {
     }
 }
 
+impl Operation {
+    // Convert a legacy integer operation (-1 delete, 0 equal, 1 insert).
+    pub fn from_i32(operation: i32) -> Option<Operation> {
+        match operation {
+            -1 => Some(Operation::Delete),
+            0 => Some(Operation::Equal),
+            1 => Some(Operation::Insert),
+            _ => None
+        }
+    }
+
+    // Legacy integer value of the operation.
+    pub fn to_i32(self) -> i32 {
+        match self {
+            Operation::Delete => -1,
+            Operation::Equal => 0,
+            Operation::Insert => 1
+        }
+    }
+}
+
+impl From<Operation> for i32 {
+    fn from(operation: Operation) -> i32 {
+        operation.to_i32()
+    }
+}
+
+impl TryFrom<i32> for Operation {
+    type Error = i32;
+
+    // The rejected integer is returned as the error.
+    fn try_from(operation: i32) -> Result<Self, Self::Error> {
+        Operation::from_i32(operation).ok_or(operation)
+    }
+}
+
 impl PartialEq for Diff {
     // it will return if two diff objects are equal.
     fn eq(&self, other: &Self) -> bool {
@@ -147,9 +267,61 @@ fn find_char(cha: char, text: &[char], start: usize) -> i32 {
     }
     -1
 }
+pub(crate) fn delta_tokens(delta: &str) -> Vec<(usize, char, &str)> {
+    // split a delta into (column, operation, content) triples, skipping empty tokens.
+    let mut tokens = vec![];
+    let mut column = 1;
+    for token in delta.split('\t') {
+        let mut chars = token.chars();
+        if let Some(operation) = chars.next() {
+            tokens.push((column, operation, chars.as_str()));
+        }
+        column += token.len() + 1;
+    }
+    tokens
+}
+
+pub(crate) fn delta_length(operation: char, content: &str, column: usize) -> Result<usize, DmpError> {
+    // parse the length carried by a "=n" or "-n" delta token.
+    if operation != '=' && operation != '-' {
+        return Err(DmpError::BadLinePrefix { line: 1, column, prefix: operation });
+    }
+    content.parse::<usize>().map_err(|_| DmpError::BadNumber { line: 1, column: column + 1 })
+}
+
+pub(crate) fn percent_decode_bytes(text: &str, line: usize, column: usize) -> Result<Vec<u8>, DmpError> {
+    // percent-decode text, rejecting malformed escapes instead of passing them through.
+    let bytes = text.as_bytes();
+    for (i, byte) in bytes.iter().enumerate() {
+        if *byte == b'%' && !matches!(bytes.get(i + 1..i + 3), Some(hex) if hex.iter().all(u8::is_ascii_hexdigit)) {
+            return Err(DmpError::BadEscape { line, column: column + i });
+        }
+    }
+    Ok(percent_decode(bytes).collect())
+}
+
+pub(crate) fn delta_escape(text: &str) -> String {
+    // escape inserted text for a delta, leaving the chars JavaScript's encodeURI keeps.
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            // High ascii will raise UnicodeDecodeError.  Use Unicode instead.
+            '!' | '~' | '*' | '(' | ')' | ';' | '/' | '?' | ':' | '@' | '&' | '=' | '+' | '$' | ',' | '#' | ' ' | '\'' => escaped.push(ch),
+            '%' => escaped += "%25",
+            _ => escaped.extend(utf8_percent_encode(ch.encode_utf8(&mut [0; 4]), USERINFO_ENCODE_SET))
+        }
+    }
+    escaped
+}
+
+pub(crate) fn percent_decode_checked(text: &str, line: usize, column: usize) -> Result<String, DmpError> {
+    // percent-decode text into a string, rejecting malformed escapes and invalid UTF-8.
+    String::from_utf8(percent_decode_bytes(text, line, column)?).map_err(|_| DmpError::InvalidUtf8 { line, column })
+}
+
 impl fmt::Debug for Diff {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "\n  {{ {}: {} }}", self.operation, self.text)
+        write!(f, "\n  {{ {:?}: {} }}", self.operation, self.text)
     }
 }
 
@@ -220,7 +392,7 @@ impl Dmp {
     #[allow(dead_code)]
     pub fn new() -> Self {
         // it will give a new dmp object.
-        Dmp { diff_timeout: None, patch_delete_threshold: 0.5, edit_cost: 0, match_distance: 1000, patch_margin: 4, match_maxbits: 32, match_threshold: 0.5}
+        Dmp { diff_timeout: None, patch_delete_threshold: 0.5, edit_cost: 0, match_distance: 1000, patch_margin: 4, match_maxbits: 32, match_threshold: 0.5, diff_algorithm: DiffAlgorithm::Myers, unified_context: 3, merge_resolution: MergeResolution::Markers, move_threshold: 0.8, move_min_length: 16, whitespace_mode: WhitespaceMode::Exact, ignore_blank_lines: false, ignore_case: false, diff_granularity: DiffGranularity::Char, word_boundary: default_word_boundary().clone(), tokenizers: HashMap::new(), grapheme_clusters: false, review_threshold: 0.5}
     }
 
     #[allow(dead_code)]
@@ -236,157 +408,55 @@ impl Dmp {
                 a line-level diff first to identify the changed areas.
                 Defaults to true, which does a faster, slightly less optimal diff.
         Returns:
-            Vector of diffs as changes.
+            Vector of diffs as changes.  With whitespace_mode, ignore_blank_lines
+            or ignore_case set, diff_text2 gives text2 back only up to what was
+            ignored.
         */
 
-        self.diff_main_internal(text1, text2, checklines, Instant::now())
-    }
-
-    fn diff_main_internal(&self, text1: &str, text2: &str, checklines: bool, start_time: Instant) -> Vec<Diff> {
-        // check for empty text
-        if text1.is_empty() && text2.is_empty() {
-            return vec![];
-        }
-        else if text1.is_empty() {
-            return vec![Diff::new(1, text2.to_string())];
-        }
-        else if text2.is_empty() {
-            return vec![Diff::new(-1, text1.to_string())];
-        }
-        
-        // check for equality
-        if text1 == text2 {
-            return vec![Diff::new(0, text1.to_string())];
-        }
-        
-        let mut char1: Vec<char> = text1.chars().collect();
-        let mut char2: Vec<char> = text2.chars().collect();
-        // Trim off common prefix (speedup).
-        let mut commonlength = self.diff_common_prefix(&char1, &char2) as usize;
-        let commonprefix = Vec::from_iter(char1[0..commonlength].iter().cloned());
-        char1 = Vec::from_iter(char1[commonlength..].iter().cloned());
-        char2 = Vec::from_iter(char2[commonlength..].iter().cloned());
-        
-        // Trim off common suffix (speedup).
-        commonlength = self.diff_common_suffix(&char1, &char2) as usize;
-        let commonsuffix = Vec::from_iter(char1[(char1.len() - commonlength)..char1.len()].iter().cloned());
-        char1 = Vec::from_iter(char1[..(char1.len() - commonlength)].iter().cloned());
-        char2 = Vec::from_iter(char2[..(char2.len() - commonlength)].iter().cloned());
-        let mut diffs: Vec<Diff> = Vec::new();
-
-        //Restore the prefix
-        if !commonprefix.is_empty() {
-            diffs.push(Diff::new(0, commonprefix.iter().collect()));
+        if !self.diff_ignores_anything() {
+            return self.diff_exact(text1, text2, checklines);
         }
-
-        // Compute the diff on the middle block.
-        let temp = self.diff_compute(&char1, &char2, checklines, start_time);
-        for z in temp {
-            diffs.push(z);
+        let chars1: Vec<char> = text1.chars().collect();
+        let chars2: Vec<char> = text2.chars().collect();
+        let mut diffs = if self.diff_granularity == DiffGranularity::Char && checklines && chars1.len() > 100 && chars2.len() > 100 {
+            self.diff_normalized(&chars1, &chars2, DiffGranularity::Line, true, Instant::now())
         }
-
-        // Restore the suffix
-        if !commonsuffix.is_empty() {
-            diffs.push(Diff::new(0, commonsuffix.iter().collect()));
+        else {
+            self.diff_normalized(&chars1, &chars2, self.diff_granularity, false, Instant::now())
+        };
+        if self.grapheme_clusters {
+            self.diff_cleanup_graphemes(&mut diffs);
         }
-        self.diff_cleanup_merge(&mut diffs);
         diffs
     }
 
-    fn diff_compute(&self, text1: &Vec<char>, text2: &Vec<char>, checklines: bool, start_time: Instant) -> Vec<Diff> {
-        /*
-        Find the differences between two texts.  Assumes that the texts do not
-        have any common prefix or suffix.
-
-        Args:
-            text1: Old chars to be diffed.
-            text2: New chars to be diffed.
-            checklines: Speedup flag.  If false, then don't run a line-level diff
-            first to identify the changed areas.
-            If true, then run a faster, slightly less optimal diff.
-
-        Returns:
-            Vector of diffs as changes.
-        */
-        
-        let mut diffs: Vec<Diff> = Vec::new();
-        if text1.is_empty() {
-            // Just add some text (speedup).
-            diffs.push(Diff::new(1, text2.iter().collect()));
-            return diffs;
+    pub(crate) fn diff_exact(&self, text1: &str, text2: &str, checklines: bool) -> Vec<Diff> {
+        // diff_main without whitespace_mode, ignore_blank_lines and ignore_case,
+        // for patches, merges and deltas that must rebuild text2 exactly.
+        let chars1: Vec<char> = text1.chars().collect();
+        let chars2: Vec<char> = text2.chars().collect();
+        let mut diffs = if self.diff_granularity != DiffGranularity::Char {
+            self.diff_tokenized_internal(&chars1, &chars2, &*self.diff_tokenizer(self.diff_granularity), Instant::now())
         }
-        else if text2.is_empty() {
-            // Just delete some text (speedup).
-            diffs.push(Diff::new(-1, text1.iter().collect()));
-            return diffs;
+        else {
+            self.diff_main_internal(&chars1, &chars2, checklines, Instant::now())
+        };
+        if self.grapheme_clusters {
+            self.diff_cleanup_graphemes(&mut diffs);
         }
-        {
-            let len1 = text1.len();
-            let len2 = text2.len();
-            let (longtext, shorttext) = if len1 >= len2 {
-                (text1, text2)
-            }
-            else {
-                (text2, text1)
-            };
-            let i = self.kmp(longtext, shorttext, 0);
-            if i != -1 {
-                // Shorter text is inside the longer text (speedup).
-                if len1 > len2 {
-                    if i != 0 {
-                        diffs.push(Diff::new(-1, (text1[0..(i as usize)]).iter().collect()));
-                    }
-                    diffs.push(Diff::new(0, text2.iter().collect()));
-                    if i as usize + text2.len() != text1.len() {
-                        diffs.push(Diff::new(-1, text1[((i as usize) + text2.len())..].iter().collect()));
-                    }
-                }
-                else {
-                    if i != 0 {
-                        diffs.push(Diff::new(1, (text2[0..(i as usize)]).iter().collect()));
-                    }
-                    diffs.push(Diff::new(0, text1.iter().collect()));
-                    if (i as usize) + text1.len() != text2.len() {
-                        diffs.push(Diff::new(1, text2[((i as usize) + text1.len())..].iter().collect()));
-                    }
-                }
-                return diffs;
+        diffs
+    }
 
-            }
-            if shorttext.len() == 1 {
-                // Single character string.
-                // After the previous speedup, the character can't be an equality.
-                diffs.push(Diff::new(-1, text1.iter().collect()));
-                diffs.push(Diff::new(1, text2.iter().collect()));
-                return diffs;
-            }
-        }
-        // Check to see if the problem can be split in two.
-        let hm = self.diff_half_match(text1, text2);
-        if !hm.is_empty() {
-            // A half-match was found, sort out the return data.
-            let text1_a = hm[0].clone();
-            let text1_b = hm[1].clone();
-            let text2_a = hm[2].clone();
-            let text2_b = hm[3].clone();
-            let mid_common = hm[4].clone();
-            // Send both pairs off for separate processing.
-            let mut diffs_a = self.diff_main_internal(text1_a.as_str(), text2_a.as_str(), checklines, start_time);
-            let diffs_b = self.diff_main_internal(text1_b.as_str(), text2_b.as_str(), checklines, start_time);
-            diffs_a.push(Diff::new(0, mid_common));
-            // Merge the result.
-            for x in diffs_b {
-                diffs_a.push(x);
-            }
-            return diffs_a;
-        }
-        if checklines && text1.len() > 100 && text2.len() > 100 {
-            return self.diff_linemode_internal(text1, text2, start_time);
-        }
-        self.diff_bisect_internal(text1, text2, start_time)
+    fn diff_main_internal(&self, text1: &[char], text2: &[char], checklines: bool, start_time: Instant) -> Vec<Diff> {
+        // the token diff over chars, with blocks of over 100 chars on both
+        // sides diffed in line mode when checklines is set.
+        let linemode = |text1: &[char], text2: &[char], start_time: Instant| {
+            (checklines && text1.len() > 100 && text2.len() > 100).then(|| self.diff_linemode_internal(text1, text2, start_time).iter().map(DiffOp::from).collect())
+        };
+        self.diff_tokens_shortcut(text1, text2, start_time, &linemode).into_iter().map(Diff::from).collect()
     }
-    
-    fn kmp(&self, text1: &[char], text2: &[char], ind: usize) -> i32 {
+
+    pub(crate) fn kmp<T: PartialEq>(&self, text1: &[T], text2: &[T], ind: usize) -> i32 {
         /*
         Find the first index after a specific index in text1 where patern is present.
 
@@ -447,7 +517,7 @@ impl Dmp {
         -1
     }
     
-    fn rkmp(&mut self, text1: &[char], text2: &[char], ind: usize) -> i32 {
+    pub(crate) fn rkmp<T: PartialEq>(&self, text1: &[T], text2: &[T], ind: usize) -> i32 {
         /*
         Find the last index before a specific index in text1 where patern is present.
 
@@ -509,7 +579,7 @@ impl Dmp {
     }
 
     #[allow(dead_code)]
-    pub fn diff_linemode(&mut self, text1: &[char], text2: &[char]) -> Vec<Diff> {
+    pub fn diff_linemode(&self, text1: &[char], text2: &[char]) -> Vec<Diff> {
         /*
         Do a quick line-level diff on both chars, then rediff the parts for
         greater accuracy.
@@ -523,24 +593,31 @@ impl Dmp {
             Vector of diffs as changes.
         */
 
+        if self.diff_ignores_anything() {
+            return self.diff_normalized(text1, text2, DiffGranularity::Line, true, Instant::now());
+        }
         self.diff_linemode_internal(text1, text2, Instant::now())
     }
 
     fn diff_linemode_internal(&self, text1: &[char], text2: &[char], start_time: Instant) -> Vec<Diff> {
         // Scan the text on a line-by-line basis first.
-        let (text3, text4, linearray) = self.diff_lines_tochars(text1, text2);
-        
-        let dmp = Dmp::new();
-        let mut diffs: Vec<Diff> = dmp.diff_main_internal(text3.as_str(), text4.as_str(), false, start_time);
-        
+        let mut linearray: Vec<String> = vec![];
+        let mut linehash: HashMap<String, usize> = HashMap::new();
+        let lines1 = self.diff_lines_toids(text1, &mut linearray, &mut linehash);
+        let lines2 = self.diff_lines_toids(text2, &mut linearray, &mut linehash);
+
+        let line_diffs = self.diff_tokens_internal(&lines1, &lines2, start_time);
+
         // Convert the diff back to original text.
-        self.diff_chars_tolines(&mut diffs, &linearray);
+        let mut diffs: Vec<Diff> = line_diffs.into_iter().map(|diff| {
+            Diff::new(diff.operation, diff.tokens.iter().map(|id| linearray[*id].as_str()).collect())
+        }).collect();
         // Eliminate freak matches (e.g. blank lines)
         self.diff_cleanup_semantic(&mut diffs);
 
         // Rediff any replacement blocks, this time character-by-character.
         // Add a dummy entry at the end.
-        diffs.push(Diff::new(0,"".to_string()));
+        diffs.push(Diff::new(Operation::Equal,"".to_string()));
         let mut count_delete = 0;
         let mut count_insert = 0;
         let mut text_delete: String = "".to_string();
@@ -548,11 +625,11 @@ impl Dmp {
         let mut pointer = 0;
         let mut temp: Vec<Diff> = vec![];
         while pointer < diffs.len() {
-            if diffs[pointer].operation == 1 {
+            if diffs[pointer].operation == Operation::Insert {
                 count_insert += 1;
                 text_insert += diffs[pointer].text.as_str();
             }
-            else if diffs[pointer].operation == -1 {
+            else if diffs[pointer].operation == Operation::Delete {
                 count_delete += 1;
                 text_delete += diffs[pointer].text.as_str();
             }
@@ -560,7 +637,7 @@ impl Dmp {
                 // Upon reaching an equality, check for prior redundancies.
                 if count_delete >= 1 && count_insert >= 1 {
                     // Delete the offending records and add the merged ones.
-                    let sub_diff = self.diff_main_internal(text_delete.as_str(), text_insert.as_str(), false, start_time);
+                    let sub_diff = self.diff_main_internal(&text_delete.chars().collect::<Vec<char>>(), &text_insert.chars().collect::<Vec<char>>(), false, start_time);
                     for z in sub_diff {
                         temp.push(z);
                     }
@@ -568,10 +645,10 @@ impl Dmp {
                 }
                 else {
                     if !text_delete.is_empty() {
-                        temp.push(Diff::new(-1, text_delete));
+                        temp.push(Diff::new(Operation::Delete, text_delete));
                     }
                     if !text_insert.is_empty() {
-                        temp.push(Diff::new(1, text_insert));
+                        temp.push(Diff::new(Operation::Insert, text_insert));
                     }
                     temp.push(Diff::new(diffs[pointer].operation, diffs[pointer].text.clone()));
                 }
@@ -587,7 +664,7 @@ impl Dmp {
     }
 
     #[allow(dead_code)]
-    pub fn diff_bisect(&mut self, char1: &[char], char2: &[char]) -> Vec<Diff> {
+    pub fn diff_bisect(&self, char1: &[char], char2: &[char]) -> Vec<Diff> {
         /*
         Find the 'middle snake' of a diff, split the problem in two
         and return the recursively constructed diff.
@@ -604,177 +681,11 @@ impl Dmp {
         self.diff_bisect_internal(char1, char2, Instant::now())
     }
 
-    fn diff_bisect_internal(&self, char1: &[char], char2: &[char], start_time: Instant) -> Vec<Diff> {       
-        let text1_length = char1.len() as i32;
-        let text2_length = char2.len() as i32;
-        let max_d: i32 = (text1_length + text2_length + 1)/2;
-        let v_offset: i32 = max_d;
-        let v_length: i32 = 2 * max_d;
-        let mut v1: Vec<i32> = vec![-1; v_length as usize];
-        let mut v2: Vec<i32> = vec![-1; v_length as usize];
-        v1[v_offset as usize + 1] = 0;
-        v2[v_offset as usize + 1] = 0;
-        let delta: i32 = text1_length - text2_length;
-        // If the total number of characters is odd, then the front path will
-        // collide with the reverse path.
-        let front: i32 = (delta%2 != 0) as i32;
-        // Offsets for start and end of k loop.
-        // Prevents mapping of space beyond the grid.
-        let mut k1start: i32 = 0;
-        let mut k1end: i32 = 0;
-        let mut k2start: i32 = 0;
-        let mut k2end: i32 = 0;
-        for d in 0..max_d {
-            if self.diff_timeout.is_some() && start_time.elapsed().as_secs_f32() >= self.diff_timeout.unwrap() {
-                break;
-            }
-            
-            let d1 = d;
-            let mut k1 = -d1 + k1start;
-            let mut x1: i32;
-            let mut k1_offset: i32;
-            let mut k2_offset;
-            let mut x2;
-            let mut y1;
-            // Walk the front path one step.
-            while k1 < d1 + 1 - k1end {
-                k1_offset = v_offset + k1;
-                if k1 == -d1 || (k1 != d1 && v1[k1_offset as usize - 1] < v1[k1_offset as usize + 1]) {
-                    x1 = v1[k1_offset as usize + 1];
-                }
-                else {
-                    x1 = v1[k1_offset as usize - 1] + 1;
-                }
-                y1 = x1 - k1;
-                while x1 < text1_length && y1 < text2_length {
-                    let i1 = if x1 < 0 {
-                        text1_length + x1
-                    }
-                    else {
-                        x1
-                    };
-                    let i2 = if y1 < 0 {
-                        text2_length + y1
-                    }
-                    else {
-                        y1
-                    };
-                    if char1[i1 as usize] != char2[i2 as usize] {
-                        break;
-                    }
-                    x1 += 1;
-                    y1 += 1;
-                }
-                v1[k1_offset as usize] = x1;
-                if x1 > text1_length {
-                    // Ran off the right of the graph.
-                    k1end += 2;
-                }
-                else if y1 > text2_length {
-                    // Ran off the bottom of the graph.
-                    k1start += 2;
-                }
-                else if front != 0 {
-                    k2_offset = v_offset + delta - k1;
-                    if k2_offset >= 0 && k2_offset < v_length && v2[k2_offset as usize] != -1 {
-                        // Mirror x2 onto top-left coordinate system.
-                        x2 = text1_length - v2[k2_offset as usize];
-                        if x1 >= x2 {
-                            // Overlap detected.
-                            return self.diff_bisect_split(char1, char2, x1, y1, start_time);
-                        }
-                    }
-                }
-                k1 += 2;
-            }
-            let mut k2 = -d1 + k2start;
-            let mut y2;
-            // Walk the reverse path one step.
-            while k2 < d1 + 1 - k2end {
-                k2_offset = v_offset + k2;
-                if k2 == -d1 || (k2 != d1 && v2[k2_offset as usize - 1] < v2[k2_offset as usize + 1]) {
-                    x2 = v2[k2_offset as usize + 1];
-                }
-                else {
-                    x2 = v2[k2_offset as usize - 1] + 1;
-                }
-                y2 = x2 - k2;
-                while x2 < text1_length && y2 < text2_length {
-                    let i1 = if text1_length - x2  > 0 {
-                        text1_length - x2 - 1
-                    }
-                    else {
-                        x2 + 1
-                    };
-                    let i2 = if text2_length - y2  > 0 {
-                        text2_length -y2 - 1
-                    }
-                    else {
-                        y2 + 1
-                    };
-                    if char1[i1 as usize] != char2[i2 as usize] {
-                        break;
-                    }
-                    x2 += 1;
-                    y2 += 1;
-                }
-                v2[k2_offset as usize] = x2;
-                if x2 > text1_length {
-                    // Ran off the left of the graph.
-                    k2end += 2;
-                }
-                else if y2 > text2_length {
-                    // Ran off the top of the graph.
-                    k2start += 2;
-                }
-                else if front == 0 {
-                    k1_offset = v_offset + delta - k2;
-                    if k1_offset >= 0 && k1_offset < v_length && v1[k1_offset as usize] != -1 {
-                        x1 = v1[k1_offset as usize];
-                        y1 = v_offset + x1 - k1_offset;
-                        // Mirror x2 onto top-left coordinate system.
-                        x2 = text1_length - x2;
-                        if x1 >= x2 {
-                            // Overlap detected.
-                            return self.diff_bisect_split(char1, char2, x1, y1, start_time);
-                        }
-                    }
-                }
-                k2 += 2;
-            }
-        }
-        // number of diffs equals number of characters, no commonality at all.
-        vec![Diff::new(-1, char1.iter().collect()), Diff::new(1, char2.iter().collect())]
-    }
-
-    fn diff_bisect_split(&self, text1: &[char], text2: &[char], x: i32, y: i32, start_time: Instant) -> Vec<Diff> {
-        /*
-        Given the location of the 'middle snake', split the diff in two parts
-        and recurse.
-
-        Args:
-            text1: Old text1 to be diffed.
-            text2: New text1 to be diffed.
-            x: Index of split point in text1.
-            y: Index of split point in text2.
-
-        Returns:
-                Vector of diffs as changes.
-        */
-        
-        let text1a: String = text1[..(x as usize)].iter().collect();
-        let text2a: String = text2[..(y as usize)].iter().collect();
-        let text1b: String = text1[(x as usize)..].iter().collect();
-        let text2b: String = text2[(y as usize)..].iter().collect();
-        
-        // Compute both diffs serially.
-        let mut diffs = self.diff_main_internal(text1a.as_str(), text2a.as_str(), false, start_time);
-        let mut diffsb = self.diff_main_internal(text1b.as_str(), text2b.as_str(), false, start_time);
-        diffs.append(&mut diffsb);
-        diffs
+    fn diff_bisect_internal(&self, char1: &[char], char2: &[char], start_time: Instant) -> Vec<Diff> {
+        self.diff_bisect_tokens(char1, char2, start_time).into_iter().map(Diff::from).collect()
     }
 
-    pub fn diff_words_tochars(&mut self, text1: &str, text2: &str) -> (String, String, Vec<String>) {
+    pub fn diff_words_tochars(&self, text1: &str, text2: &str) -> (String, String, Vec<String>) {
         /*
         Split two texts into an array of strings.  Reduce the texts to a string
         of hashes where each Unicode character represents one word.
@@ -792,15 +703,15 @@ impl Dmp {
         let mut wordarray: Vec<String> = vec!["".to_string()];
         let mut wordhash: HashMap<String, u32> = HashMap::new();
         let chars1 = self.diff_words_tochars_munge(text1, &mut wordarray, &mut wordhash);
-        let mut dmp = Dmp::new();
-        let chars2 = dmp.diff_words_tochars_munge(text2, &mut wordarray, &mut wordhash);
+        let chars2 = self.diff_words_tochars_munge(text2, &mut wordarray, &mut wordhash);
         (chars1, chars2, wordarray)
     }
 
-    pub fn diff_words_tochars_munge(&mut self, text: &str, wordarray: &mut Vec<String>, wordhash: &mut HashMap<String, u32>) -> String {
+    pub fn diff_words_tochars_munge(&self, text: &str, wordarray: &mut Vec<String>, wordhash: &mut HashMap<String, u32>) -> String {
         /*
         Split a text into an array of strings.  Reduce the texts to a string
-        of hashes where each Unicode character represents one word.
+        of hashes where each Unicode character represents one word.  Every
+        match of word_boundary is a word of its own.
         Modifies wordarray and wordhash through being a closure.
 
         Args:
@@ -810,26 +721,15 @@ impl Dmp {
             Encoded string.
         */
         let mut chars = "".to_string();
-
-        let re = Regex::new(r"[\s\n\r]").unwrap();
-        let mut prev_end: usize = 0;
-        for part in re.find_iter(text){
-            if prev_end < part.start() {
-                let word = &text[prev_end .. part.start()];
-                chars += &self.make_token_dict(word, wordarray, wordhash);
-            }
-            let word = &text[part.start() .. part.end()];
-            chars += &self.make_token_dict(word, wordarray, wordhash);
-            prev_end = part.end();
-        }
-        if prev_end < text.len(){
-            let word = &text[prev_end .. text.len()];
-            chars += &self.make_token_dict(word, wordarray, wordhash);
+        let text: Vec<char> = text.chars().collect();
+        for span in WordBoundary(&self.word_boundary).tokenize(&text) {
+            let word: String = text[span].iter().collect();
+            chars += &self.make_token_dict(&word, wordarray, wordhash);
         }
         chars
     }
 
-    fn make_token_dict(&mut self, word: &str , wordarray: &mut Vec<String>, wordhash: &mut HashMap<String, u32>) -> String {
+    fn make_token_dict(&self, word: &str , wordarray: &mut Vec<String>, wordhash: &mut HashMap<String, u32>) -> String {
         if !wordhash.contains_key(word){
             wordarray.push(word.to_string());
             wordhash.insert(word.to_string(), wordarray.len() as u32 - 1);
@@ -855,8 +755,7 @@ impl Dmp {
         let mut linearray: Vec<String> = vec!["".to_string()];
         let mut linehash: HashMap<String, i32> = HashMap::new();
         let chars1 = self.diff_lines_tochars_munge(text1, &mut linearray, &mut linehash);
-        let dmp = Dmp::new();
-        let chars2 = dmp.diff_lines_tochars_munge(text2, &mut linearray, &mut linehash);
+        let chars2 = self.diff_lines_tochars_munge(text2, &mut linearray, &mut linehash);
         (chars1, chars2, linearray)
     }
 
@@ -873,49 +772,64 @@ impl Dmp {
             Encoded string.
         */
         let mut chars = "".to_string();
-        // Walk the text, pulling out a substring for each line.
-        // text.split('\n') would would temporarily double our memory footprint.
-        // Modifying text would create many large strings to garbage collect.
-        let mut line_start = 0;
-        let mut line_end = -1;
-        let mut line: String;
-        while line_end < (text.len() as i32 - 1) {
-            line_end = find_char('\n', text, line_start as usize);
-            if line_end == -1 {
-                line_end = text.len() as i32 - 1;
-            }
-            line = text[line_start as usize..=line_end as usize].iter().collect();
-            if linehash.contains_key(&line) {
-                if let Some(char1) = char::from_u32(linehash[&line] as u32) {
-                    chars.push(char1);
-                    line_start = line_end + 1;
-                }
+        for span in LineTokenizer.tokenize(text) {
+            let mut line: String = text[span.clone()].iter().collect();
+            if let Some(u32char) = linehash.get(&line) {
+                chars.push(char::from_u32(*u32char as u32).unwrap());
+                continue;
             }
-            else {
-                let mut u32char = linearray.len() as i32;
+            let mut u32char = linearray.len() as i32;
 
-                // skip reserved range - U+D800 to U+DFFF
-                // unicode code points in this range can't be converted to unicode scalars
-                if u32char >= 55296 {
-                    u32char += 2048;
-                }
+            // skip reserved range - U+D800 to U+DFFF
+            // unicode code points in this range can't be converted to unicode scalars
+            if u32char >= 55296 {
+                u32char += 2048;
+            }
 
-                // 1114111 is the biggest unicode scalar, so stop here
-                if u32char == 1114111 {
-                    line = text[(line_start as usize)..].iter().collect();
-                    line_end = text.len() as i32 - 1;
-                }
+            // 1114111 is the biggest unicode scalar, so stop here
+            if u32char == 1114111 {
+                line = text[span.start..].iter().collect();
+            }
 
-                linearray.push(line.clone());
-                linehash.insert(line.clone(), u32char);
+            linearray.push(line.clone());
+            linehash.insert(line.clone(), u32char);
 
-                chars.push(char::from_u32(u32char as u32).unwrap());
-                line_start = line_end + 1;
+            chars.push(char::from_u32(u32char as u32).unwrap());
+            if u32char == 1114111 {
+                break;
             }
         }
         chars
     }
 
+    fn diff_lines_toids(&self, text: &[char], linearray: &mut Vec<String>, linehash: &mut HashMap<String, usize>) -> Vec<usize> {
+        /*
+        Split a text into lines and replace each line by its index in
+        linearray.  Unlike diff_lines_tochars there is no limit on the
+        number of unique lines.
+
+        Args:
+            text: chars to encode.
+
+        Returns:
+            Vector of line ids.
+        */
+        let mut ids: Vec<usize> = vec![];
+        for span in LineTokenizer.tokenize(text) {
+            let line: String = text[span].iter().collect();
+            let id = match linehash.get(&line) {
+                Some(id) => *id,
+                None => {
+                    linearray.push(line.clone());
+                    linehash.insert(line, linearray.len() - 1);
+                    linearray.len() - 1
+                }
+            };
+            ids.push(id);
+        }
+        ids
+    }
+
     pub fn diff_chars_tolines(&self, diffs: &mut [Diff], line_array: &[String] ) {
         /*
         Rehydrate the text in a diff from a string of line hashes to real lines
@@ -936,7 +850,7 @@ impl Dmp {
         }
     }
 
-    pub fn diff_common_prefix(&self, text1: &[char], text2: &[char]) -> i32 {
+    pub fn diff_common_prefix<T: PartialEq>(&self, text1: &[T], text2: &[T]) -> i32 {
         /*
         Determine the common prefix of two chars.
 
@@ -964,7 +878,7 @@ impl Dmp {
         pointermax
     }
 
-    pub fn diff_common_suffix(&self, text1: &[char], text2: &[char]) -> i32 {
+    pub fn diff_common_suffix<T: PartialEq>(&self, text1: &[T], text2: &[T]) -> i32 {
         /*
         Determine the common suffix of two strings.
 
@@ -1049,7 +963,7 @@ impl Dmp {
 
 
     #[allow(dead_code)]
-    pub fn split_by_char(&mut self, text: &str, ch: char) -> Vec<String> {
+    pub fn split_by_char(&self, text: &str, ch: char) -> Vec<String> {
         
         /*
         split the string accoring to given character
@@ -1070,7 +984,7 @@ impl Dmp {
     }
 
     #[allow(dead_code)]
-    pub fn split_by_chars(&mut self, text: &str) -> Vec<String> {
+    pub fn split_by_chars(&self, text: &str) -> Vec<String> {
         /*
         split the string accoring to given characters "@@ ".
 
@@ -1088,7 +1002,7 @@ impl Dmp {
         temp1
     }
 
-    pub fn diff_half_match(&self, text1: &Vec<char>, text2: &Vec<char>) -> Vec<String> {
+    pub fn diff_half_match(&self, text1: &[char], text2: &[char]) -> Vec<String> {
         /* Do the two texts share a substring which is at least half the length of
         the longer text?
         This speedup can produce non-minimal diffs.
@@ -1103,102 +1017,18 @@ impl Dmp {
         if there was no match.
         */
 
-        // Don't risk returning a non-optimal diff if we have unlimited time.
-        if self.diff_timeout.is_none() {
-            return vec![]
-        }
-
-        let (long_text, short_text) = if text1.len() > text2.len() {
-            (text1, text2)
-        }
-        else {
-            (text2, text1)
-        };
-        let len1 = short_text.len();
-        let len2 = long_text.len();
-        if len2 < 4 || len1*2 <len2 {
-            return vec![];
-        }
-
-        let mut hm: Vec<String>;
-        //First check if the second quarter is the seed for a half-match.
-        let hm1 = self.diff_half_matchi(long_text, short_text, (len2 as i32 + 3)/4);
-        // Check again based on the third quarter.
-        let hm2 = self.diff_half_matchi(long_text, short_text, (len2 as i32 + 1)/2);
-        
-        if hm1.is_empty() && hm2.is_empty() {
-            return vec![];
+        match self.diff_half_match_tokens(text1, text2) {
+            Some(hm) => vec![
+                hm.text1_a.iter().collect(),
+                hm.text1_b.iter().collect(),
+                hm.text2_a.iter().collect(),
+                hm.text2_b.iter().collect(),
+                hm.mid_common.iter().collect()
+            ],
+            None => vec![]
         }
-        else if hm1.is_empty() {
-            hm = hm2;
-        }
-        else if hm2.is_empty() {
-            hm = hm1;
-        }
-        else {
-            // Both matched.  Select the longest.
-            hm = if hm1[4].len() > hm2[4].len() {
-                hm1
-            }
-            else {
-                hm2
-            };
-        }
-        if text1.len() > text2.len() {
-            return hm;
-        }
-        let mut temp2 = hm[0].clone();
-        let mut temp3 = hm[2].clone();
-        hm[0].clone_from(&temp3);
-        hm[2].clone_from(&temp2);
-        temp2.clone_from(&hm[1]);
-        temp3.clone_from(&hm[3]);
-        hm[1] = temp3;
-        hm[3] = temp2;
-        hm
     }
 
-    fn diff_half_matchi(&self, long_text: &[char], short_text: &[char], i: i32) -> Vec<String> {
-        /*
-        Does a substring of shorttext exist within longtext such that the
-        substring is at least half the length of longtext?
-        Closure, but does not reference any external variables.
-
-        Args:
-            longtext: Longer chars.
-            shorttext: Shorter chars.
-            i: Start index of quarter length substring within longtext.
-
-        Returns:
-            Five element vector, containing the prefix of longtext, the suffix of
-            longtext, the prefix of shorttext, the suffix of shorttext and the
-            common middle.  Or empty vector if there was no match.
-        */
-        let long_len = long_text.len();
-        let seed = Vec::from_iter(long_text[(i as usize)..(i as usize + long_len / 4)].iter().cloned());
-        let mut best_common = "".to_string();
-        let mut best_longtext_a = "".to_string();
-        let mut best_longtext_b = "".to_string();
-        let mut best_shorttext_a = "".to_string();
-        let mut best_shorttext_b = "".to_string();
-        let mut j: i32 = self.kmp(short_text, &seed, 0);
-        while j != -1 {
-            let prefix_length = self.diff_common_prefix(&long_text[(i as usize)..], &short_text[(j as usize)..]);
-            let suffix_length = self.diff_common_suffix(&long_text[..(i as usize)], &short_text[..(j as usize)]);
-            if best_common.len() < suffix_length as usize + prefix_length as usize {
-                best_common = short_text[(j as usize - suffix_length as usize)..(j as usize + prefix_length as usize)].iter().collect();
-                best_longtext_a = long_text[..((i - suffix_length) as usize)].iter().collect();
-                best_longtext_b = long_text[((i + prefix_length) as usize)..].iter().collect();
-                best_shorttext_a = short_text[..((j - suffix_length) as usize)].iter().collect();
-                best_shorttext_b = short_text[((j + prefix_length) as usize)..].iter().collect();
-            }
-            j = self.kmp(short_text, &seed, j as usize + 1);
-        }
-        if best_common.chars().count() * 2 >= long_text.len() {
-            return vec![best_longtext_a, best_longtext_b, best_shorttext_a, best_shorttext_b, best_common];
-        }
-        vec![]
-    }
     pub fn diff_cleanup_semantic(&self, diffs: &mut Vec<Diff>) {
         /*
         Reduce the number of edits by eliminating semantically trivial
@@ -1218,7 +1048,7 @@ impl Dmp {
         let mut length_insertions2 = 0;
         let mut length_deletions2 = 0;
         while (pointer as usize) < diffs.len() {
-            if diffs[pointer as usize].operation == 0 { // Equality found.
+            if diffs[pointer as usize].operation == Operation::Equal { // Equality found.
                 equalities.push(pointer);
                 length_insertions1 = length_insertions2;
                 length_insertions2 = 0;
@@ -1227,7 +1057,7 @@ impl Dmp {
                 last_equality.clone_from(&diffs[pointer as usize].text);
             }
             else { // An insertion or deletion.
-                if diffs[pointer as usize].operation == 1 {
+                if diffs[pointer as usize].operation == Operation::Insert {
                     length_insertions2 += diffs[pointer as usize].text.chars().count() as i32;
                 }
                 else {
@@ -1239,9 +1069,9 @@ impl Dmp {
                 if last_equality_len > 0 && last_equality_len <= max(length_insertions1, length_deletions1) && 
                                             last_equality_len <= max(length_insertions2, length_deletions2) {
                     // Duplicate record.
-                    diffs.insert(equalities[equalities.len() - 1] as usize, Diff::new(-1, last_equality.clone()));
+                    diffs.insert(equalities[equalities.len() - 1] as usize, Diff::new(Operation::Delete, last_equality.clone()));
                     // Change second copy to insert.
-                    diffs[equalities[equalities.len() - 1] as usize + 1] = Diff::new(1, diffs[equalities[equalities.len() - 1] as usize + 1].text.clone());
+                    diffs[equalities[equalities.len() - 1] as usize + 1] = Diff::new(Operation::Insert, diffs[equalities[equalities.len() - 1] as usize + 1].text.clone());
                     // Throw away the equality we just deleted.
                     equalities.pop();
                     // Throw away the previous equality (it needs to be reevaluated).
@@ -1275,7 +1105,7 @@ impl Dmp {
         let mut overlap_length2: i32;
         pointer = 1;
         while (pointer as usize) < diffs.len() {
-             if diffs[pointer as usize - 1].operation == -1 && diffs[pointer as usize].operation == 1 {
+             if diffs[pointer as usize - 1].operation == Operation::Delete && diffs[pointer as usize].operation == Operation::Insert {
                 let deletion_vec: Vec<char> = diffs[pointer as usize - 1].text.chars().collect();
                 let insertion_vec: Vec<char> = diffs[pointer as usize].text.chars().collect();
                 overlap_length1 = self.diff_common_overlap(&deletion_vec, &insertion_vec);
@@ -1284,19 +1114,19 @@ impl Dmp {
                     if (overlap_length1 as f32) >= (deletion_vec.len() as f32 / 2.0) || (overlap_length1 as f32) >= (insertion_vec.len() as f32 / 2.0)
                     {
                         // Overlap found.  Insert an equality and trim the surrounding edits.
-                        diffs.insert(pointer as usize, Diff::new(0, insertion_vec[..(overlap_length1 as usize)].iter().collect()));
-                        diffs[pointer as usize - 1] = Diff::new(-1, deletion_vec[..(deletion_vec.len() - overlap_length1 as usize)].iter().collect());
-                        diffs[pointer as usize + 1] = Diff::new(1, insertion_vec[(overlap_length1 as usize)..].iter().collect());
+                        diffs.insert(pointer as usize, Diff::new(Operation::Equal, insertion_vec[..(overlap_length1 as usize)].iter().collect()));
+                        diffs[pointer as usize - 1] = Diff::new(Operation::Delete, deletion_vec[..(deletion_vec.len() - overlap_length1 as usize)].iter().collect());
+                        diffs[pointer as usize + 1] = Diff::new(Operation::Insert, insertion_vec[(overlap_length1 as usize)..].iter().collect());
                         pointer += 1;
                     }
                 }
                 else if (overlap_length2 as f32) >= (deletion_vec.len() as f32 / 2.0) || (overlap_length2 as f32) >= (insertion_vec.len() as f32 / 2.0){
                     // Reverse overlap found.
                     // Insert an equality and swap and trim the surrounding edits.
-                    diffs.insert(pointer as usize, Diff::new(0, deletion_vec[..(overlap_length2 as usize)].iter().collect()));
+                    diffs.insert(pointer as usize, Diff::new(Operation::Equal, deletion_vec[..(overlap_length2 as usize)].iter().collect()));
                     let insertion_vec_len = insertion_vec.len();
-                    diffs[pointer as usize - 1] = Diff::new(1, insertion_vec[..(insertion_vec_len - overlap_length2 as usize)].iter().collect());
-                    diffs[pointer as usize + 1] = Diff::new(-1, deletion_vec[(overlap_length2 as usize)..].iter().collect());
+                    diffs[pointer as usize - 1] = Diff::new(Operation::Insert, insertion_vec[..(insertion_vec_len - overlap_length2 as usize)].iter().collect());
+                    diffs[pointer as usize + 1] = Diff::new(Operation::Delete, deletion_vec[(overlap_length2 as usize)..].iter().collect());
                     pointer += 1;
                 }
                 pointer += 1;
@@ -1328,7 +1158,7 @@ impl Dmp {
 
         //Intentionally ignore the first and last element (don't need checking).
         while pointer < diffs.len() as i32 - 1 {
-            if diffs[pointer as usize - 1].operation == 0 && diffs[pointer as usize + 1].operation == 0 {
+            if diffs[pointer as usize - 1].operation == Operation::Equal && diffs[pointer as usize + 1].operation == Operation::Equal {
                 //  This is a single edit surrounded by equalities.
                 equality1 = diffs[pointer as usize - 1].text.clone();
                 edit = diffs[pointer as usize].text.clone();
@@ -1350,10 +1180,17 @@ impl Dmp {
                     equality1_vec = equality1.chars().collect();
                 }
                 // Second, step character by character right, looking for the best fit.
-                best_equality1 = equality1.clone();
-                best_edit = edit;
-                best_equality2 = equality2;
-                best_score = self.diff_cleanup_semantic_score(&equality1_vec, &edit_vec) + self.diff_cleanup_semantic_score(&edit_vec, &equality2_vec);
+                // Shifts splitting a grapheme cluster don't count, keep the edit where it is if none else fits.
+                best_equality1 = diffs[pointer as usize - 1].text.clone();
+                best_edit = diffs[pointer as usize].text.clone();
+                best_equality2 = diffs[pointer as usize + 1].text.clone();
+                best_score = i32::MIN;
+                if self.grapheme_fits(&equality1_vec, &edit_vec, &equality2_vec) {
+                    best_equality1 = equality1.clone();
+                    best_edit = edit;
+                    best_equality2 = equality2;
+                    best_score = self.diff_cleanup_semantic_score(&equality1_vec, &edit_vec) + self.diff_cleanup_semantic_score(&edit_vec, &equality2_vec);
+                }
                 let edit_len = edit_vec.len();
                 let mut equality2_len = equality2_vec.len();
                 while equality2_len > 0 && edit_len > 0 {
@@ -1368,7 +1205,7 @@ impl Dmp {
                     equality2_vec = equality2_vec[1..].to_vec();
                     score = self.diff_cleanup_semantic_score(&equality1_vec, &edit_vec) + self.diff_cleanup_semantic_score(&edit_vec, &equality2_vec);
                     // The >= encourages trailing rather than leading whitespace on edits.
-                    if score >= best_score {
+                    if score >= best_score && self.grapheme_fits(&equality1_vec, &edit_vec, &equality2_vec) {
                         best_score = score;
                         best_equality1 = equality1_vec[0..].iter().collect();
                         best_edit = edit_vec[..].iter().collect();
@@ -1473,7 +1310,7 @@ impl Dmp {
         0
     }
 
-    pub fn diff_cleanup_efficiency(&mut self, diffs: &mut Vec<Diff> ) {
+    pub fn diff_cleanup_efficiency(&self, diffs: &mut Vec<Diff> ) {
         /*
         Reduce the number of edits by eliminating operationally trivial
         equalities.
@@ -1493,7 +1330,7 @@ impl Dmp {
         let mut post_ins = false; // Is there an insertion operation after the last equality.
         let mut post_del = false; // Is there a deletion operation after the last equality.
         while (pointer as usize) < diffs.len() {
-            if diffs[pointer as usize].operation == 0 {
+            if diffs[pointer as usize].operation == Operation::Equal {
                 if diffs[pointer as usize].text.chars().count() < self.edit_cost as usize && (post_del || post_ins) {
                     // Candidate found.
                     equalities.push(pointer);
@@ -1510,7 +1347,7 @@ impl Dmp {
                 post_del = false;
             }
             else { // An insertion or deletion.
-                if diffs[pointer as usize].operation == -1 {
+                if diffs[pointer as usize].operation == Operation::Delete {
                     post_del = true;
                 }
                 else {
@@ -1531,9 +1368,9 @@ impl Dmp {
                     (pre_ins as i32 + pre_del as i32 + post_del as i32 + post_ins as i32) == 3)) {
                     
                     // Duplicate record.
-                    diffs.insert(equalities[equalities.len() - 1] as usize, Diff::new(-1, last_equality));
+                    diffs.insert(equalities[equalities.len() - 1] as usize, Diff::new(Operation::Delete, last_equality));
                     // Change second copy to insert.
-                    diffs[equalities[equalities.len() - 1] as usize + 1] = Diff::new(1, diffs[equalities[equalities.len() - 1] as usize + 1].text.clone());
+                    diffs[equalities[equalities.len() - 1] as usize + 1] = Diff::new(Operation::Insert, diffs[equalities[equalities.len() - 1] as usize + 1].text.clone());
                     equalities.pop(); // Throw away the equality we just deleted.
                     last_equality = "".to_string();
                     if pre_ins && pre_del {
@@ -1573,184 +1410,13 @@ impl Dmp {
         Args:
             diffs: vectors of diff object.
       */
-        if diffs.is_empty() {
-            return;
-        }
-        diffs.push(Diff::new(0, "".to_string()));
-        let mut text_insert: String = "".to_string();
-        let mut text_delete: String = "".to_string();
-        let mut i: i32 = 0;
-        let mut count_insert = 0;
-        let mut count_delete = 0;
-        while (i as usize) < diffs.len() {
-            if diffs[i as usize].operation == -1 {
-                text_delete += diffs[i as usize].text.as_str();
-                count_delete += 1;
-                i += 1; 
-            }
-            else if diffs[i as usize].operation == 1 {
-                text_insert += diffs[i as usize].text.as_str();
-                count_insert += 1;
-                i += 1;
-            }
-            else {
-                // Upon reaching an equality, check for prior redundancies.
-                if count_delete + count_insert > 1 {
-                    let mut delete_vec: Vec<char> = text_delete.chars().collect();
-                    let mut insert_vec: Vec<char> = text_insert.chars().collect();
-                    if count_delete > 0 && count_insert > 0 {
-                        // Factor out any common prefixies.
-                        let mut commonlength = self.diff_common_prefix(&insert_vec, &delete_vec);
-                        if commonlength != 0 {
-                            let temp1: String = (&insert_vec)[..(commonlength as usize)].iter().collect();
-                            let x = i - count_delete - count_insert - 1;
-                            if x >= 0 && diffs[x as usize].operation == 0 {
-                                diffs[x as usize] = Diff::new(diffs[x as usize].operation, diffs[x as usize].text.clone() + temp1.as_str());
-                            }
-                            else {
-                                diffs.insert(0, Diff::new(0, temp1));
-                                i += 1;
-                            }
-                            insert_vec = insert_vec[(commonlength as usize)..].to_vec();
-                            delete_vec = delete_vec[(commonlength as usize)..].to_vec();
-                        }
-
-                        // Factor out any common suffixies.
-                        commonlength = self.diff_common_suffix(&insert_vec, &delete_vec);
-                        if commonlength != 0 {
-                            let temp1: String = (&insert_vec)[(insert_vec.len() - commonlength as usize)..].iter().collect();
-                            diffs[i as usize] = Diff::new(diffs[i as usize].operation, temp1 + diffs[i as usize].text.as_str());
-                            insert_vec = insert_vec[..(insert_vec.len() - commonlength as usize)].to_vec();
-                            delete_vec = delete_vec[..(delete_vec.len() - commonlength as usize)].to_vec();
-                        }
-                    }
-
-                    // Delete the offending records and add the merged ones.
-                    i -= count_delete + count_insert;
-                    for _j in 0..(count_delete + count_insert) as usize {
-                        diffs.remove(i as usize);
-                    }
-                    if !delete_vec.is_empty() {
-                        diffs.insert(i as usize, Diff::new(-1, delete_vec.iter().collect()));
-                        i += 1;
-                    }
-                    if !insert_vec.is_empty() {
-                        diffs.insert(i as usize, Diff::new(1, insert_vec.iter().collect()));
-                        i+= 1;
-                    }
-                    i += 1;
-                }
-                else if i != 0 && diffs[i as usize - 1].operation == 0 {
-                    // Merge this equality with the previous one.
-                    diffs[i as usize - 1] = Diff::new(diffs[i as usize - 1].operation, diffs[i as usize - 1].text.clone() + diffs[i as usize].text.as_str());
-                    diffs.remove(i as usize);
-                }
-                else {
-                    i += 1;
-                }
-                count_delete = 0;
-                text_delete = "".to_string();
-                text_insert = "".to_string();
-                count_insert = 0;
-            }
-        }
-        // Remove the dummy entry at the end.
-        if diffs[diffs.len() - 1].text.is_empty() {
-            diffs.pop();
-        }
-
-        /*
-        Second pass: look for single edits surrounded on both sides by equalities
-        which can be shifted sideways to eliminate an equality.
-        e.g: A<ins>BA</ins>C -> <ins>AB</ins>AC
-        */
-        let mut changes = false;
-        i = 1;
-        // Intentionally ignore the first and last element (don't need checking).
-        while (i as usize) < diffs.len() - 1 {
-            if diffs[i as usize -1].operation == 0 && diffs[i as usize + 1].operation == 0 {
-                // This is a single edit surrounded by equalities.
-                let text_vec = diffs[i as usize].text.chars().collect::<Vec<_>>();
-                let text1_vec = diffs[i as usize - 1].text.chars().collect::<Vec<_>>();
-                let text2_vec: Vec<char> = diffs[i as usize + 1].text.chars().collect();
-                if self.endswith(&text_vec, &text1_vec) {
-                    // Shift the edit over the previous equality.
-                    if !diffs[i as usize - 1].text.is_empty() {
-                        let temp1: String = diffs[i as usize - 1].text.clone();
-                        let temp2: String = text_vec[..(text_vec.len() - text1_vec.len())].iter().collect();
-                        diffs[i as usize].text = temp1 + temp2.as_str();
-                        diffs[i as usize + 1].text = diffs[i as usize - 1].text.clone() + diffs[i as usize + 1].text.as_str();
-                    }
-                    diffs.remove(i as usize - 1);
-                    changes = true;
-                }
-                else if self.startswith(&text_vec, &text2_vec) {
-                    // Shift the edit over the next equality.
-                    diffs[i as usize - 1].text = diffs[i as usize - 1].text.clone() + diffs[i as usize + 1].text.as_str();
-                    let temp1: String = text_vec[text2_vec.len()..].iter().collect();
-                    diffs[i as usize].text = temp1 + diffs[i as usize + 1].text.as_str();
-                    diffs.remove(i as usize + 1);
-                    changes = true;
-                }
-            }
-            i += 1;
-        }
-        // If shifts were made, the diff needs reordering and another shift sweep.
-        if changes {
-            self.diff_cleanup_merge(diffs);
-        }
-    }
-
-    fn endswith(&self, first: &[char], second: &[char]) -> bool {
-        /*
-        It will check if first chars vector is endswith second chars vector or not.
-        
-        Args:
-            first: First chars,
-            second: Secodn chars.
-        Returns:
-            Return true if first chars vector endswith second chars vector, false otherwise. 
-        */
-        let mut len1 = first.len();
-        let mut len2 = second.len();
-        if len1 < len2 {
-            return false;
-        }
-        while len2 > 0 {
-            if first[len1 - 1] != second[len2 - 1] {
-                return false;
-            }
-            len1 -= 1;
-            len2 -= 1;
-        }
-        true
-    }
-
-    fn startswith(&self, first: &[char], second: &[char]) -> bool {
-        /*
-        It will check if first chars vector is startswith second chars vector or not.
-        
-        Args:
-            first: First chars,
-            second: Secodn chars.
-        Returns:
-            Return true if first chars vector startswith second chars vector, false otherwise. 
-        */
-        let len1 = first.len();
-        let len2 = second.len();
-        if len1 < len2 {
-            return false;
-        }
-        for i in 0..len2 {
-            if first[i] != second[i] {
-                return false;
-            }
-        }
-        true
+        let mut ops: Vec<DiffOp<char>> = diffs.iter().map(DiffOp::from).collect();
+        self.diff_cleanup_merge_tokens(&mut ops);
+        *diffs = ops.into_iter().map(Diff::from).collect();
     }
 
     #[allow(dead_code)]
-    pub fn diff_xindex(&mut self, diffs: &Vec<Diff>, loc: i32) -> i32 {
+    pub fn diff_xindex(&self, diffs: &[Diff], loc: i32) -> i32 {
         /*
         loc is a location in text1, compute and return the equivalent location
         in text2.  e.g. "The cat" vs "The big cat", 1->1, 5->8
@@ -1767,13 +1433,13 @@ impl Dmp {
         let mut chars2 = 0;
         let mut last_chars1 = 0;
         let mut last_chars2 = 0;
-        let mut lastdiff = Diff::new(0, "".to_string());
+        let mut lastdiff = Diff::new(Operation::Equal, "".to_string());
         let z = 0;
         for diffs_item in diffs {
-            if diffs_item.operation != 1 { // Equality or deletion.
+            if diffs_item.operation != Operation::Insert { // Equality or deletion.
                 chars1 += diffs_item.text.chars().count() as i32;
             }
-            if diffs_item.operation != -1 { // Equality or insertion.
+            if diffs_item.operation != Operation::Delete { // Equality or insertion.
                 chars2 += diffs_item.text.chars().count() as i32;
             }
             if chars1 > loc { // Overshot the location.
@@ -1783,7 +1449,7 @@ impl Dmp {
             last_chars1 = chars1;
             last_chars2 = chars2;
         }
-        if lastdiff.operation == -1 && diffs.len() != z {
+        if lastdiff.operation == Operation::Delete && diffs.len() != z {
             // The location was deleted.
             return last_chars2;
         }
@@ -1791,8 +1457,21 @@ impl Dmp {
         last_chars2 + (loc - last_chars1)
     }
 
+    pub fn diff_pretty_html(&self, diffs: &[Diff]) -> String {
+        /*
+        Convert a diff array into a pretty HTML report.
+
+        Args:
+            diffs: Vector of diff object.
+
+        Returns:
+            HTML representation.
+        */
+        HtmlFormatter::new().format(diffs)
+    }
+
     #[allow(dead_code)]
-    pub fn diff_text1(&mut self, diffs: &mut Vec<Diff>) -> String {
+    pub fn diff_text1(&self, diffs: &[Diff]) -> String {
         /*
         Compute and return the source text (all equalities and deletions).
 
@@ -1804,7 +1483,7 @@ impl Dmp {
       */
         let mut text: String = "".to_string();
         for adiff in diffs {
-            if adiff.operation != 1 {
+            if adiff.operation != Operation::Insert {
                 text += adiff.text.as_str();
             }
         }
@@ -1812,7 +1491,7 @@ impl Dmp {
     }
 
     #[allow(dead_code)]
-    pub fn diff_text2(&mut self, diffs: &mut Vec<Diff>) -> String {
+    pub fn diff_text2(&self, diffs: &[Diff]) -> String {
         /*
         Compute and return the destination text (all equalities and insertions).
 
@@ -1824,14 +1503,14 @@ impl Dmp {
       */
         let mut text: String = "".to_string();
         for adiff in diffs {
-            if adiff.operation != -1 {
+            if adiff.operation != Operation::Delete {
                 text += adiff.text.as_str();
             }
         }
         text
     }
     
-    pub fn diff_text2_from_delta_u16(&self, text1: &str, delta: &str) -> String {
+    pub fn diff_text2_from_delta_u16(&self, text1: &str, delta: &str) -> Result<String, DmpError> {
         /*
         Compute and return the destination text (all equalities and insertions).
         Delta offsets are interpreted in u16 code units
@@ -1841,30 +1520,28 @@ impl Dmp {
             delta: Text delta
 
         Returns:
-            Destination text
+            Destination text, or an error if the delta is malformed or doesn't
+            fit text1.
         */
 
         let text1_u16: Vec<u16> = text1.encode_utf16().collect();
         let mut text2_u16: Vec<u16> = Vec::new();
 
-        let tokens: Vec<&str> = (*delta).split('\t').collect();
-
         let mut text_offset = 0;
-        for token in tokens {
-            if token.is_empty() {
-                continue;
-            }
-
-            let operation = &token[0..1];
-            let operation_content = &token[1..];
-
-            if operation == "+" {
-                let decoded = percent_decode_u16(operation_content.as_bytes()).unwrap();
+        for (column, operation, operation_content) in delta_tokens(delta) {
+            if operation == '+' {
+                let decoded = percent_decode_u16(operation_content.as_bytes()).map_err(|err| match err {
+                    DecodeError::MalformedEscape => DmpError::BadEscape { line: 1, column: column + 1 },
+                    DecodeError::MalformedString => DmpError::InvalidUtf8 { line: 1, column: column + 1 },
+                })?;
                 text2_u16.extend(decoded);
             } else {
-                let content_length = operation_content.parse::<usize>().unwrap();
+                let content_length = delta_length(operation, operation_content, column)?;
+                if content_length > text1_u16.len() - text_offset {
+                    return Err(DmpError::LengthMismatch { line: 1, column, expected: text1_u16.len(), actual: content_length.saturating_add(text_offset) });
+                }
 
-                if operation == "=" {
+                if operation == '=' {
                     let range = text_offset..(content_length + text_offset);
                     text2_u16.extend(&text1_u16[range]);
                 }
@@ -1875,14 +1552,14 @@ impl Dmp {
 
         // we should have consumed all text
         if text1_u16.len() != text_offset {
-            panic!("wrong patern or text");
+            return Err(DmpError::LengthMismatch { line: 1, column: delta.len() + 1, expected: text1_u16.len(), actual: text_offset });
         }
 
-        String::from_utf16(&text2_u16).unwrap()
+        String::from_utf16(&text2_u16).map_err(|_| DmpError::InvalidUtf16 { line: 1, column: 1 })
     }
 
     #[allow(dead_code)]
-    pub fn diff_levenshtein(&mut self, diffs: &Vec<Diff>) -> i32 {
+    pub fn diff_levenshtein(&self, diffs: &[Diff]) -> i32 {
         /*
         Compute the Levenshtein distance; the number of inserted, deleted or
         substituted characters.
@@ -1897,10 +1574,10 @@ impl Dmp {
         let mut insertions = 0;
         let mut deletions = 0;
         for adiff in diffs {
-            if adiff.operation == 1 {
+            if adiff.operation == Operation::Insert {
                 insertions += adiff.text.chars().count();
             }
-            else if adiff.operation == -1 {
+            else if adiff.operation == Operation::Delete {
                 deletions += adiff.text.chars().count();
             }
             else {
@@ -1915,12 +1592,12 @@ impl Dmp {
     }
 
     #[allow(dead_code)]
-    pub fn diff_todelta(&mut self, diffs: &mut [Diff]) -> String {
+    pub fn diff_todelta(&self, diffs: &[Diff]) -> String {
         self.diff_todelta_unit(diffs, LengthUnit::UnicodeScalar)
     }
 
     #[allow(dead_code)]
-    pub fn diff_todelta_unit(&mut self, diffs: &mut [Diff], length_unit: LengthUnit) -> String {
+    pub fn diff_todelta_unit(&self, diffs: &[Diff], length_unit: LengthUnit) -> String {
         /*
         Crush the diff into an encoded string which describes the operations
         required to transform text1 into text2.
@@ -1940,31 +1617,12 @@ impl Dmp {
         let mut text: String = "".to_string();
         let len = diffs.len();
         for (k, diffs_item) in diffs.iter().enumerate() {
-            if diffs_item.operation == 1 {
-                // High ascii will raise UnicodeDecodeError.  Use Unicode instead.
-                let temp5: Vec<char> = vec!['!', '~', '*', '(', ')', ';', '/', '?', ':', '@', '&', '=', '+', '$', ',', '#', ' ', '\''];
-                let temp4: Vec<char> = diffs_item.text.chars().collect();
+            if diffs_item.operation == Operation::Insert {
                 text += "+";
-                for temp4_item in &temp4 {
-                    let mut is = false;
-                    for temp5_item in &temp5 {
-                        if *temp5_item == *temp4_item {
-                            text.push(*temp4_item);
-                            is = true;
-                            break;
-                        }
-                    }
-                    if is {
-                        continue;
-                    }
-                    let mut temp6 = "".to_string();
-                    temp6.push(*temp4_item);
-                    temp6 = utf8_percent_encode(temp6.as_str(), USERINFO_ENCODE_SET).collect();
-                    text += temp6.as_str();
-                }
+                text += delta_escape(&diffs_item.text).as_str();
             }
             else {
-                if diffs_item.operation == -1 {
+                if diffs_item.operation == Operation::Delete {
                     text += "-";
                 }
                 else {
@@ -1990,12 +1648,12 @@ impl Dmp {
     }
 
     #[allow(dead_code)]
-    pub fn diff_from_delta(&mut self, text1: &str, delta: &str) -> Vec<Diff> {
+    pub fn diff_from_delta(&self, text1: &str, delta: &str) -> Result<Vec<Diff>, DmpError> {
         self.diff_from_delta_unit(text1, delta, LengthUnit::UnicodeScalar)
     }
 
     #[allow(dead_code)]
-    pub fn diff_from_delta_unit(&mut self, text1: &str, delta: &str, length_unit: LengthUnit) -> Vec<Diff> {
+    pub fn diff_from_delta_unit(&self, text1: &str, delta: &str, length_unit: LengthUnit) -> Result<Vec<Diff>, DmpError> {
         /*
         Given the original text1, and an encoded string which describes the
         operations required to transform text1 into text2, compute the full diff.
@@ -2008,51 +1666,49 @@ impl Dmp {
         Returns:
             Vector of diff object.
 
-        Raises:
-            ValueError: If invalid input.
+        Errors:
+            DmpError: If invalid input.
         */
 
         match length_unit {
             LengthUnit::UnicodeScalar => {
                 let text = StringScalarView::new(text1);
-                self.diff_from_delta_string_view(&text, delta).unwrap()
+                self.diff_from_delta_string_view(&text, delta)
             },
             LengthUnit::UTF16 => {
                 let text = StringUTF16View::new(text1);
                 match self.diff_from_delta_string_view(&text, delta) {
-                    Ok(diff) => diff,
+                    // The delta splits or escapes half a surrogate pair, rebuild
+                    // text2 from UTF-16 code units and rediff.  Its errors are the
+                    // ones reported.
                     Err(_) => {
-                        let text2 = self.diff_text2_from_delta_u16(text1, delta);
-                        self.diff_main(text1, &text2, true)
-                    }
+                        let text2 = self.diff_text2_from_delta_u16(text1, delta)?;
+                        Ok(self.diff_exact(text1, &text2, true))
+                    },
+                    result => result
                 }
             },
         }
     }
 
-    fn diff_from_delta_string_view(&self, text1: &impl StringView, delta: &str) -> Result<Vec<Diff>, Box<dyn Error>> {
+    fn diff_from_delta_string_view(&self, text1: &impl StringView, delta: &str) -> Result<Vec<Diff>, DmpError> {
         let mut diffs: Vec<Diff> = vec![];
-        let tokens: Vec<&str> = (*delta).split('\t').collect();
 
         let mut text_offset = 0;
-        for token in tokens {
-            if token.is_empty() {
-                continue;
-            }
-            
-            let operation = &token[0..1];
-            let operation_content = &token[1..];
-
-            if operation == "+" {
-                let text = percent_decode(operation_content.as_bytes()).decode_utf8()?.to_string();
-                diffs.push(Diff::new(1, text));
+        for (column, operation, operation_content) in delta_tokens(delta) {
+            if operation == '+' {
+                let text = percent_decode_checked(operation_content, 1, column + 1)?;
+                diffs.push(Diff::new(Operation::Insert, text));
             } else {
-                let content_length = operation_content.parse::<usize>().unwrap();
+                let content_length = delta_length(operation, operation_content, column)?;
+                if content_length > text1.len() - text_offset {
+                    return Err(DmpError::LengthMismatch { line: 1, column, expected: text1.len(), actual: content_length.saturating_add(text_offset) });
+                }
                 let range = text_offset..(content_length + text_offset);
 
                 diffs.push(Diff::new(
-                    if operation == "=" { 0 } else { -1 }, 
-                    text1.slice(range)?
+                    if operation == '=' { Operation::Equal } else { Operation::Delete }, 
+                    text1.slice(range).map_err(|_| DmpError::InvalidUtf16 { line: 1, column })?
                 ));
 
                 text_offset += content_length;
@@ -2061,14 +1717,14 @@ impl Dmp {
 
         // we should have consumed all text
         if text1.len() != text_offset {
-            panic!("wrong patern or text");
+            return Err(DmpError::LengthMismatch { line: 1, column: delta.len() + 1, expected: text1.len(), actual: text_offset });
         }
 
         Ok(diffs)
     }
 
     #[allow(dead_code)]
-    pub fn match_main(&mut self, text1: &str, patern1: &str, mut loc: i32) -> i32 {
+    pub fn match_main(&self, text1: &str, patern1: &str, mut loc: i32) -> i32 {
         /*
         Locate the best instance of 'pattern' in 'text' near 'loc'.
 
@@ -2101,7 +1757,7 @@ impl Dmp {
     }
 
     #[allow(dead_code)]
-    pub fn match_bitap(&mut self, text: &[char], patern: &[char], loc: i32) -> i32 {
+    pub fn match_bitap(&self, text: &[char], patern: &[char], loc: i32) -> i32 {
         /*
         Locate the best instance of 'pattern' in 'text' near 'loc' using the
         Bitap algorithm.
@@ -2219,7 +1875,7 @@ impl Dmp {
         best_loc
     }
 
-    pub fn match_bitap_score(&mut self, e: i32, x: i32, loc: i32, patern: &[char]) -> f32 {
+    pub fn match_bitap_score(&self, e: i32, x: i32, loc: i32, patern: &[char]) -> f32 {
         /*
         Compute and return the score for a match with e errors and x location.
         Accesses loc and pattern through being a closure.
@@ -2244,7 +1900,7 @@ impl Dmp {
         }
         accuracy + ((proximity as f32) / (self.match_distance as f32))
     }
-    pub fn match_alphabet(&mut self, patern: &[char]) -> HashMap<char,i32> {
+    pub fn match_alphabet(&self, patern: &[char]) -> HashMap<char,i32> {
         /*
         Initialise the alphabet for the Bitap algorithm.
 
@@ -2271,7 +1927,7 @@ impl Dmp {
     }
 
     #[allow(dead_code)]
-    pub fn patch_add_context(&mut self, patch: &mut Patch, text: &mut [char]) {
+    pub fn patch_add_context(&self, patch: &mut Patch, text: &mut [char]) {
         /*
         Increase the context until it is unique,
         but don't let the pattern expand beyond Match_MaxBits.
@@ -2300,18 +1956,30 @@ impl Dmp {
         // Add one chunk for good luck.
         padding += self.patch_margin;
 
+        // Widen the context to whole grapheme clusters.
+        let mut prefix_start = max(0, patch.start2 - padding) as usize;
+        let mut suffix_end = min(text.len() as i32, patch.start2 + patch.length1 + padding) as usize;
+        if self.grapheme_clusters {
+            while !grapheme_boundary(text, prefix_start) {
+                prefix_start -= 1;
+            }
+            while !grapheme_boundary(text, suffix_end) {
+                suffix_end += 1;
+            }
+        }
+
         // Add the prefix.
-        let prefix: String = text[max(0, patch.start2 - padding) as usize..patch.start2 as usize].iter().collect();
+        let prefix: String = text[prefix_start..patch.start2 as usize].iter().collect();
         let prefix_length = prefix.chars().count() as i32;
         if !prefix.is_empty() {
-            patch.diffs.insert(0, Diff::new(0, prefix.clone()));
+            patch.diffs.insert(0, Diff::new(Operation::Equal, prefix.clone()));
         }
 
         // Add the suffix.
-        let suffix: String = text[(patch.start2 + patch.length1) as usize..min(text.len() as i32, patch.start2 + patch.length1 + padding) as usize].iter().collect();
+        let suffix: String = text[(patch.start2 + patch.length1) as usize..suffix_end].iter().collect();
         let suffix_length = suffix.chars().count() as i32;
         if !suffix.is_empty() {
-            patch.diffs.push(Diff::new(0, suffix));
+            patch.diffs.push(Diff::new(Operation::Equal, suffix));
         }
         // Roll back the start points.
         patch.start1 -= prefix_length;
@@ -2322,7 +1990,7 @@ impl Dmp {
     }
 
     #[allow(dead_code)]
-    pub fn patch_make1(&mut self, text1: &str, text2: &str) -> Vec<Patch> {
+    pub fn patch_make1(&self, text1: &str, text2: &str) -> Vec<Patch> {
         /*
         Compute a list of patches to turn text1 into text2.
         compute diffs.
@@ -2332,16 +2000,19 @@ impl Dmp {
         Returns:
             Vector of Patch objects.
       */
-        let mut diffs: Vec<Diff> = self.diff_main(text1, text2, true);
+        let mut diffs: Vec<Diff> = self.diff_exact(text1, text2, true);
         if diffs.len() > 2 {
             self.diff_cleanup_semantic(&mut diffs);
             self.diff_cleanup_efficiency(&mut diffs);
+            if self.grapheme_clusters {
+                self.diff_cleanup_graphemes(&mut diffs);
+            }
         }
-        self.patch_make4(text1, &mut diffs)
+        self.patch_make4(text1, &diffs)
     }
 
     #[allow(dead_code)]
-    pub fn patch_make2(&mut self, diffs: &mut Vec<Diff>) -> Vec<Patch> {
+    pub fn patch_make2(&self, diffs: &[Diff]) -> Vec<Patch> {
         /*
         Compute a list of patches to turn text1 into text2.
         Use diffs to compute first text.
@@ -2356,7 +2027,7 @@ impl Dmp {
     }
 
     #[allow(dead_code)]
-    pub fn patch_make3(&mut self, text1: &str, _text2: &str, diffs: &mut [Diff]) -> Vec<Patch> {
+    pub fn patch_make3(&self, text1: &str, _text2: &str, diffs: &[Diff]) -> Vec<Patch> {
         /*
         Compute a list of patches to turn text1 into text2.
 
@@ -2370,7 +2041,7 @@ impl Dmp {
       */
         self.patch_make4(text1, diffs)
     }
-    pub fn patch_make4(&mut self, text1: &str, diffs: &mut [Diff]) -> Vec<Patch> {
+    pub fn patch_make4(&self, text1: &str, diffs: &[Diff]) -> Vec<Patch> {
         /*
         Compute a list of patches to turn text1 into text2.
 
@@ -2391,12 +2062,12 @@ impl Dmp {
         let mut postpatch: Vec<char> = (text1.to_string()).chars().collect();
         for i in 0..diffs.len() {
             let temp1: &Vec<char> = &(diffs[i].text.chars().collect());
-            if patch.diffs.is_empty() && diffs[i].operation != 0 {
+            if patch.diffs.is_empty() && diffs[i].operation != Operation::Equal {
                 // A new patch starts here.
                 patch.start1 = char_count1;
                 patch.start2 = char_count2;
             }
-            if diffs[i].operation == 1 { // Insertion
+            if diffs[i].operation == Operation::Insert { // Insertion
                 patch.diffs.push(Diff::new(diffs[i].operation, diffs[i].text.clone()));
                 let temp: Vec<char> = postpatch[char_count2 as usize..].to_vec();
                 postpatch = postpatch[..char_count2 as usize].to_vec();
@@ -2408,7 +2079,7 @@ impl Dmp {
                     postpatch.push(ch);
                 }
             }
-            else if diffs[i].operation == -1 { // Deletion.
+            else if diffs[i].operation == Operation::Delete { // Deletion.
                 patch.diffs.push(Diff::new(diffs[i].operation, diffs[i].text.clone()));
                 let temp: Vec<char> = postpatch[(temp1.len() + char_count2 as usize)..].to_vec();
                 postpatch = postpatch[..char_count2 as usize].to_vec();
@@ -2436,10 +2107,10 @@ impl Dmp {
             }
             
             // Update the current character count.
-            if diffs[i].operation != 1 {
+            if diffs[i].operation != Operation::Insert {
                 char_count1 += temp1.len() as i32; 
             }
-            if diffs[i].operation != -1 {
+            if diffs[i].operation != Operation::Delete {
                 char_count2 += temp1.len() as i32;
             }
         }
@@ -2454,7 +2125,7 @@ impl Dmp {
     }
 
     #[allow(dead_code)]
-    pub fn patch_deep_copy(&mut self, patches: &mut Vec<Patch>) -> Vec<Patch> {
+    pub fn patch_deep_copy(&self, patches: &[Patch]) -> Vec<Patch> {
         /*
         Given an Vector of patches, return another Vector that is identical.
 
@@ -2480,8 +2151,49 @@ impl Dmp {
         patches_copy
     }
 
+    pub fn patch_invert(&self, patches: &[Patch]) -> Vec<Patch> {
+        /*
+        Given patches taking text1 to text2, return patches taking text2
+        back to text1.  Insertions become deletions and vice versa, and the
+        two sides of every patch trade places.  Patch lists have a rolling
+        context, so every patch also moves back by what the patches before it
+        added.
+
+        Args:
+            patches: Vector of Patch objects.
+
+        Returns:
+            Vector of Patch objects.
+        */
+        // Chars added by the patches so far.
+        let mut delta = 0;
+        patches.iter().map(|patch| {
+            let mut diffs: Vec<Diff> = patch.diffs.iter().map(|diff| {
+                let operation = match diff.operation {
+                    Operation::Insert => Operation::Delete,
+                    Operation::Delete => Operation::Insert,
+                    Operation::Equal => Operation::Equal
+                };
+                Diff::new(operation, diff.text.clone())
+            }).collect();
+            // Keep deletions ahead of insertions, as diff_cleanup_merge does.
+            let mut i = 0;
+            while i < diffs.len() {
+                let mut j = i;
+                while j < diffs.len() && diffs[j].operation != Operation::Equal {
+                    j += 1;
+                }
+                diffs[i..j].sort_by_key(|diff| diff.operation == Operation::Insert);
+                i = j + 1;
+            }
+            let inverted = Patch::new(diffs, patch.start2 - delta, patch.start1 - delta, patch.length2, patch.length1);
+            delta += patch.length2 - patch.length1;
+            inverted
+        }).collect()
+    }
+
     #[allow(dead_code)]
-    pub fn patch_apply(&mut self, patches: &mut Vec<Patch>, source_text: &str) -> (Vec<char>, Vec<bool>) {
+    pub fn patch_apply(&self, patches: &[Patch], source_text: &str) -> (String, Vec<bool>) {
         /*
         Merge a set of patches onto the text.  Return a patched text, as well
         as a list of true/false values indicating which patches were applied.
@@ -2491,33 +2203,104 @@ impl Dmp {
             text: Old text.
 
         Returns:
-            Two element Vector, containing the new chars and an Vector of boolean values.
+            Two element Vector, containing the new text and an Vector of boolean
+            values, one per patch after patch_splitmax.
       */
+        let (text, pieces) = self.patch_apply_internal(patches, source_text);
+        (text, pieces.into_iter().map(|(_, report)| report.applied).collect())
+    }
+
+    pub fn patch_apply_report(&self, patches: &[Patch], source_text: &str) -> (String, Vec<ApplyReport>) {
+        /*
+        Merge a set of patches onto the text, describing what happened to
+        each of the given patches.  A patch split up by patch_splitmax is
+        reported once, as applied only if all of its pieces were.
+
+        Args:
+            patches: Vector of Patch objects.
+            text: Old text.
+
+        Returns:
+            The new text and one ApplyReport per patch.
+        */
+        let (text, pieces) = self.patch_apply_internal(patches, source_text);
+        let mut reports: Vec<ApplyReport> = vec![];
+        let mut last_patch = None;
+        for (x, piece) in pieces {
+            if last_patch != Some(x) {
+                last_patch = Some(x);
+                reports.push(piece);
+                continue;
+            }
+            let report = reports.last_mut().unwrap();
+            report.applied &= piece.applied;
+            report.exact &= piece.exact;
+            report.levenshtein_ratio = report.levenshtein_ratio.max(piece.levenshtein_ratio);
+            if report.actual_loc.is_none() {
+                report.actual_loc = piece.actual_loc;
+                report.delta = piece.delta;
+            }
+            report.range = match (report.range.take(), piece.range) {
+                (Some(range1), Some(range2)) => Some(range1.start.min(range2.start)..range1.end.max(range2.end)),
+                (range1, range2) => range1.or(range2)
+            };
+        }
+        (text, reports)
+    }
+
+    fn patch_apply_internal(&self, patches: &[Patch], source_text: &str) -> (String, Vec<(usize, ApplyReport)>) {
+        /*
+        Merge a set of patches onto the text.
+
+        Args:
+            patches: Vector of Patch objects.
+            text: Old text.
+
+        Returns:
+            The new text and a report for each piece patch_splitmax cut the
+            patches into, along with the index of the patch it came from.
+        */
 
         if patches.is_empty() {
             return (source_text.chars().collect(), vec![]);
         }
 
         // Deep copy the patches so that no changes are made to originals.
-        let mut patches_copy: Vec<Patch> =self.patch_deep_copy(patches);
+        let mut patches_copy: Vec<Patch> = patches.to_vec();
         
         let null_padding: Vec<char> = self.patch_add_padding(&mut patches_copy);
+        let padding_length = null_padding.len() as i32;
 
         let mut text = null_padding.clone();
         text.extend(source_text.chars());
         text.extend(&null_padding);
 
-        self.patch_splitmax(&mut patches_copy);
+        // Split the patches one by one, to remember where every piece came from.
+        let mut pieces: Vec<(usize, Patch)> = vec![];
+        for (x, patch) in patches_copy.into_iter().enumerate() {
+            let mut split = vec![patch];
+            self.patch_splitmax(&mut split);
+            pieces.extend(split.into_iter().map(|patch| (x, patch)));
+        }
         
         // delta keeps track of the offset between the expected and actual location
         // of the previous patch.  If there are patches expected at positions 10 and
         // 20, but the first patch was found at 12, delta is 2 and the second patch
         // has an effective expected position of 22.
         let mut delta: i32 = 0;
-        let mut results: Vec<bool> = vec![false; patches_copy.len()];
-        for x in 0..patches_copy.len() {
-            let expected_loc: i32 = patches_copy[x].start2 + delta;
-            let text1: Vec<char> = self.diff_text1(&mut patches_copy[x].diffs).chars().collect();
+        let mut results: Vec<(usize, ApplyReport)> = vec![];
+        for (x, patch) in pieces.iter_mut() {
+            let expected_loc: i32 = patch.start2 + delta;
+            let mut report = ApplyReport {
+                applied: false,
+                expected_loc: (expected_loc - padding_length).max(0),
+                actual_loc: None,
+                delta: 0,
+                exact: false,
+                levenshtein_ratio: 0.0,
+                range: None
+            };
+            let text1: Vec<char> = self.diff_text1(&patch.diffs).chars().collect();
             let mut start_loc: i32;
             let mut end_loc = -1;
             if text1.len() as i32 > self.match_maxbits {
@@ -2542,14 +2325,15 @@ impl Dmp {
             }
             if start_loc == -1 {
                 // No match found.  :(
-                results[x] = false;
                 // Subtract the delta for this failed patch from subsequent patches.
-                delta -= patches_copy[x].length2 - patches_copy[x].length1;
+                delta -= patch.length2 - patch.length1;
             }
             else {
                 // Found a match.  :)
-                results[x] = true;
+                report.applied = true;
+                report.actual_loc = Some((start_loc - padding_length).max(0));
                 delta = start_loc - expected_loc;
+                report.delta = delta;
 
                 let mut end_index: usize;
                 if end_loc == -1 {
@@ -2558,13 +2342,15 @@ impl Dmp {
                     end_index = (end_loc + self.match_maxbits) as usize;
                 }
                 end_index = std::cmp::min(text.len(), end_index);
+                let old_length = text.len();
 
                 let text2: Vec<char> = text[start_loc as usize..end_index].to_vec();
 
                 if text1 == text2 {
                     // Perfect match, just shove the replacement text in.
+                    report.exact = true;
                     let temp3: String = text[..start_loc as usize].iter().collect();
-                    let temp4 = self.diff_text2(&mut patches_copy[x].diffs);
+                    let temp4 = self.diff_text2(&patch.diffs);
                     let temp5: String = text[(start_loc as usize + text1.len())..].iter().collect();
                     let temp6 = temp3 + temp4.as_str() + temp5.as_str();
                     text = temp6.chars().collect();
@@ -2574,26 +2360,26 @@ impl Dmp {
                     // Run a diff to get a framework of equivalent indices.
                     let temp3: String = text1[..].iter().collect();
                     let temp4: String = text2[..].iter().collect();
-                    let mut diffs: Vec<Diff> = self.diff_main(temp3.as_str(), temp4.as_str(), false);
-                    if text1.len() as i32 > self.match_maxbits &&
-                       (self.diff_levenshtein(&diffs) as f32 / (text1.len() as f32) > self.patch_delete_threshold) {
+                    let mut diffs: Vec<Diff> = self.diff_exact(temp3.as_str(), temp4.as_str(), false);
+                    report.levenshtein_ratio = self.diff_levenshtein(&diffs) as f32 / (text1.len() as f32);
+                    if text1.len() as i32 > self.match_maxbits && report.levenshtein_ratio > self.patch_delete_threshold {
                         // The end points match, but the content is unacceptably bad.
-                           results[x] = false;
+                        report.applied = false;
                     }
                     else {
                         self.diff_cleanup_semantic_lossless(&mut diffs);
                         let mut index1: i32 = 0;
-                        for y in 0..patches_copy[x].diffs.len() {
-                            let mod1 = patches_copy[x].diffs[y].clone();
-                            if mod1.operation != 0 {
+                        for y in 0..patch.diffs.len() {
+                            let mod1 = patch.diffs[y].clone();
+                            if mod1.operation != Operation::Equal {
                                 let index2: i32 = self.diff_xindex(&diffs, index1);
-                                if mod1.operation == 1 { // Insertion
+                                if mod1.operation == Operation::Insert { // Insertion
                                     let temp3: String = text[..(start_loc + index2) as usize].iter().collect();
                                     let temp4: String = text[(start_loc + index2) as usize..].iter().collect();
                                     let temp5 = temp3 + mod1.text.as_str() + temp4.as_str();
                                     text = temp5.chars().collect();
                                 }
-                                else if mod1.operation == -1 { // Deletion
+                                else if mod1.operation == Operation::Delete { // Deletion
                                     let temp3: String = text[..(start_loc + index2) as usize].iter().collect();
                                     let diffs_text_len = mod1.text.chars().count();
                                     let temp4: String = text[(start_loc + self.diff_xindex(&diffs, index1 + diffs_text_len as i32)) as usize..].iter().collect();
@@ -2601,20 +2387,44 @@ impl Dmp {
                                     text = temp5.chars().collect();
                                 }
                             }
-                            if mod1.operation != -1 {
+                            if mod1.operation != Operation::Delete {
                                 index1 += mod1.text.chars().count() as i32;
                             }
                         }
                     }
                 }
+                if report.applied {
+                    // Where the piece ended up, and how it moved the text behind it.
+                    let start = start_loc as usize;
+                    let shift = text.len() as i64 - old_length as i64;
+                    let end = (end_index as i64 + shift).max(start as i64) as usize;
+                    for (_, earlier) in results.iter_mut() {
+                        if let Some(range) = earlier.range.as_mut() {
+                            if range.start >= end_index {
+                                range.start = (range.start as i64 + shift) as usize;
+                                range.end = (range.end as i64 + shift) as usize;
+                            }
+                        }
+                    }
+                    report.range = Some(start..end);
+                }
             }
+            results.push((*x, report));
         }
         // Strip the padding off.
         text = text[null_padding.len()..(text.len() - null_padding.len())].to_vec();
-        (text, results)
+        let text_length = text.len();
+        for (_, report) in results.iter_mut() {
+            if let Some(range) = report.range.as_mut() {
+                let start = range.start.saturating_sub(null_padding.len()).min(text_length);
+                let end = range.end.saturating_sub(null_padding.len()).min(text_length);
+                *range = start..end;
+            }
+        }
+        (text.into_iter().collect(), results)
     }
 
-    pub fn patch_add_padding(&mut self, patches: &mut [Patch]) -> Vec<char> {
+    pub fn patch_add_padding(&self, patches: &mut [Patch]) -> Vec<char> {
         /*
         Add some padding on text start and end so that edges can match
         something.  Intended to be called only from within patch_apply.
@@ -2641,9 +2451,9 @@ impl Dmp {
         let mut patch = patches[0].clone();
         let mut diffs = patch.diffs;
         let mut text_len = diffs[0].text.chars().count() as i32;
-        if diffs.is_empty() || diffs[0].operation != 0 {
+        if diffs.is_empty() || diffs[0].operation != Operation::Equal {
             // Add nullPadding equality.
-            diffs.insert(0, Diff::new(0, nullpadding.clone().iter().collect()));
+            diffs.insert(0, Diff::new(Operation::Equal, nullpadding.clone().iter().collect()));
             patch.start1 -= padding_length; // Should be 0.
             patch.start2 -= padding_length; // Should be 0.
             patch.length1 +=padding_length;
@@ -2667,9 +2477,9 @@ impl Dmp {
         patch = patches[patches.len() - 1].clone();
         diffs = patch.diffs;
         text_len = diffs[diffs.len() - 1].text.chars().count() as i32;
-        if diffs.is_empty() || diffs[diffs.len() - 1].operation != 0 {
+        if diffs.is_empty() || diffs[diffs.len() - 1].operation != Operation::Equal {
             // Add nullPadding equality.
-            diffs.push(Diff::new(0, nullpadding.clone().iter().collect()));
+            diffs.push(Diff::new(Operation::Equal, nullpadding.clone().iter().collect()));
             patch.length1 += padding_length;
             patch.length2 += padding_length;
         }
@@ -2689,7 +2499,7 @@ impl Dmp {
         nullpadding
     }
 
-    pub fn patch_splitmax(&mut self, patches: &mut Vec<Patch>) {
+    pub fn patch_splitmax(&self, patches: &mut Vec<Patch>) {
         /*
         Look through the patches and break up any which are longer than the
         maximum limit of the match algorithm.
@@ -2723,12 +2533,12 @@ impl Dmp {
                 if !precontext.is_empty() {
                     patch.length1 = precontext.len() as i32;
                     patch.length2 = precontext.len() as i32;
-                    patch.diffs.push(Diff::new(0, precontext.clone().iter().collect()));
+                    patch.diffs.push(Diff::new(Operation::Equal, precontext.clone().iter().collect()));
                 }
                 while !bigpatch.diffs.is_empty() && patch.length1 < patch_size - self.patch_margin {
                     let diff_type = bigpatch.diffs[0].operation;
                     let mut diff_text: Vec<char> = bigpatch.diffs[0].text.chars().collect();
-                    if diff_type == 1 {
+                    if diff_type == Operation::Insert {
                         // Insertions are harmless.
                         patch.length2 += diff_text.len() as i32;
                         start2 += diff_text.len() as i32;
@@ -2736,8 +2546,8 @@ impl Dmp {
                         bigpatch.diffs.remove(0);
                         empty = false;
                     }
-                    else if diff_type == -1 && patch.diffs.len() == 1 && 
-                            patch.diffs[0].operation == 0 && 
+                    else if diff_type == Operation::Delete && patch.diffs.len() == 1 && 
+                            patch.diffs[0].operation == Operation::Equal && 
                             (diff_text.len() as i32) > 2 * patch_size {
                         // This is a large deletion.  Let it pass in one chunk.
                         patch.length1 += diff_text.len() as i32;
@@ -2752,7 +2562,7 @@ impl Dmp {
                         diff_text = diff_text[..min(diff_text_len, patch_size - patch.length1 - self.patch_margin) as usize].to_vec();
                         patch.length1 += diff_text.len() as i32;
                         start1 += diff_text.len() as i32;
-                        if diff_type == 0 {
+                        if diff_type == Operation::Equal {
                             patch.length2 += diff_text.len() as i32;
                             start2 += diff_text.len() as i32;
                         }
@@ -2771,26 +2581,26 @@ impl Dmp {
                     }
                 }
                 // Compute the head context for the next patch.
-                precontext = self.diff_text2(&mut patch.diffs).chars().collect();
+                precontext = self.diff_text2(&patch.diffs).chars().collect();
                 precontext = precontext[(precontext.len() - min(self.patch_margin, precontext.len() as i32) as usize)..].to_vec();
                 // Append the end context for this patch.
-                let postcontext = if self.diff_text1(&mut bigpatch.diffs).chars().count() as i32 > self.patch_margin { 
-                    let temp: Vec<char> = self.diff_text1(&mut bigpatch.diffs).chars().collect();
+                let postcontext = if self.diff_text1(&bigpatch.diffs).chars().count() as i32 > self.patch_margin { 
+                    let temp: Vec<char> = self.diff_text1(&bigpatch.diffs).chars().collect();
                     temp[..self.patch_margin as usize].iter().collect()
                 }
                 else {
-                    self.diff_text1(&mut bigpatch.diffs)
+                    self.diff_text1(&bigpatch.diffs)
                 };
                 let postcontext_len = postcontext.chars().count() as i32;
                 if !postcontext.is_empty() {
                     patch.length1 += postcontext_len;
                     patch.length2 += postcontext_len;
-                    if !patch.diffs.is_empty() && patch.diffs[patch.diffs.len() - 1].operation == 0 {
+                    if !patch.diffs.is_empty() && patch.diffs[patch.diffs.len() - 1].operation == Operation::Equal {
                         let len = patch.diffs.len();
                         patch.diffs[len - 1].text += postcontext.as_str();
                     }
                     else {
-                        patch.diffs.push(Diff::new(0, postcontext));
+                        patch.diffs.push(Diff::new(Operation::Equal, postcontext));
                     }
 
                 }
@@ -2804,7 +2614,7 @@ impl Dmp {
     }
 
     #[allow(dead_code)]
-    pub fn patch_to_text(&mut self, patches: &mut Vec<Patch>) -> String {
+    pub fn patch_to_text(&self, patches: &[Patch]) -> String {
         /*
         Take a list of patches and return a textual representation.
 
@@ -2822,7 +2632,7 @@ impl Dmp {
     }
 
     #[allow(dead_code)]
-    pub fn patch_from_text(&mut self, textline: String) -> Vec<Patch> {
+    pub fn patch_from_text(&self, textline: String) -> Result<Vec<Patch>, DmpError> {
         /*
         Parse a textual representation of patches and return a list of patch
         objects.
@@ -2833,98 +2643,82 @@ impl Dmp {
         Returns:
             Vector of Patch objects.
 
-        Raises:
-            ValueError: If invalid input.
+        Errors:
+            DmpError: If invalid input, with the offending line and column.
       */
         let text: Vec<String>  = self.split_by_chars(textline.as_str());
         let mut patches: Vec<Patch> = vec![];
+        let mut line = 1;
         for (i, text_item) in text.iter().enumerate() {
-            if text_item.is_empty() {
-                if i == 0 {
-                    continue;
-                } 
-                panic!("wrong patch string");
+            if i == 0 {
+                // Anything before the first "@@ " is not a patch.
+                if !text_item.is_empty() {
+                    return Err(DmpError::BadHeader { line, column: 1 });
+                }
+                continue;
             }
-            patches.push(self.patch1_from_text(text_item.clone()));
+            patches.push(self.patch_from_chunk(text_item, line)?);
+            line += text_item.matches('\n').count();
         }
-        patches
+        Ok(patches)
     }
 
 
-    pub fn patch1_from_text(&mut self, textline: String) -> Patch {
-        let text: Vec<String>  = self.split_by_char(textline.as_str(), '\n');
-        let mut text_vec: Vec<char> = text[0].chars().collect();
-        if text_vec.len() < 8 || text_vec[text_vec.len() - 1] != '@' || text_vec[text_vec.len() - 2] != '@' {
-            panic!("Invalid patch string");
-        } 
+    pub fn patch1_from_text(&self, textline: String) -> Result<Patch, DmpError> {
+        /*
+        Parse a single patch whose leading "@@ " has already been stripped.
+
+        Args:
+            textline: Text representation of one patch.
+
+        Returns:
+            Patch object.
+        */
+        self.patch_from_chunk(textline.as_str(), 1)
+    }
+
+    fn patch_from_chunk(&self, textline: &str, line: usize) -> Result<Patch, DmpError> {
+        // line is the number of the header line within the whole patch text.
+        let text: Vec<&str> = textline.split('\n').collect();
+        static HEADER: OnceLock<Regex> = OnceLock::new();
+        let header = HEADER.get_or_init(|| Regex::new(r"^-(\d+)(?:,(\d+))? \+(\d+)(?:,(\d+))? @@$").unwrap());
+        let bad_header = DmpError::BadHeader { line, column: 4 };
+        let captures = header.captures(text[0]).ok_or_else(|| bad_header.clone())?;
         let mut patch = Patch::new(vec![], 0, 0, 0, 0);
-        let mut i = 0;
-        let mut temp: i32 = 0;
-        while i < text_vec.len() {
-            if text_vec[i] < '0' || text_vec[i] > '9' {
-                i += 1;
-                continue;
-            }
-            if (temp == 1 || temp == 3) && text_vec[i-1] != ',' {
-                temp += 1;
-            }
-            let mut s = "".to_string();
-            while i < text_vec.len() && text_vec[i] >= '0' && text_vec[i] <='9' {
-                s.push(text_vec[i]);
-                i += 1;
-            }
-            if temp == 0 {
-                patch.start1 = s.parse::<i32>().unwrap() - 1;
-                temp += 1;
-            }
-            else if temp == 1 {
-                patch.length1 = s.parse::<i32>().unwrap();
-                temp += 1;
-            }
-            else if temp == 2 {
-                patch.start2 = s.parse::<i32>().unwrap() - 1;
-                temp += 1;
-            }
-            else if temp == 3 {
-                patch.length2 = s.parse::<i32>().unwrap();
-                temp += 1;
-            }
-            else {
-                panic!("Invalid patch string");
-            }
-            i += 1;
-        }
-        patch.length1 = 0;
-        patch.length2 = 0;
-        for text_item in text.iter().take(text.len() - 1).skip(1) {
-            text_vec = text_item.chars().collect();
-            if text_vec[0] == '+' {
-                // Insertion.
-                let mut temp6: String = text_vec[1..].iter().collect();
-                temp6 = percent_decode(temp6.as_bytes()).decode_utf8().unwrap().to_string();
-                patch.length2 += temp6.chars().count() as i32;
-                patch.diffs.push(Diff::new(1, temp6));
-            }
-            else if text_vec[0] == '-' {
-                // Deletion.
-                let mut temp6: String = text_vec[1..].iter().collect();
-                temp6 = percent_decode(temp6.as_bytes()).decode_utf8().unwrap().to_string();
-                patch.length1 += temp6.chars().count() as i32;
-                patch.diffs.push(Diff::new(-1, temp6));
-            }
-            else if text_vec[0] == ' ' {
-                // Minor equality.
-                let mut temp6: String = text_vec[1..].iter().collect();
-                temp6 = percent_decode(temp6.as_bytes()).decode_utf8().unwrap().to_string();
-                patch.length1 += temp6.chars().count() as i32;
-                patch.length2 += temp6.chars().count() as i32;
-                patch.diffs.push(Diff::new(0, temp6));
+        // Lengths are recomputed from the diffs below, but must still be numbers.
+        for group in [2, 4].iter() {
+            if let Some(length) = captures.get(*group) {
+                length.as_str().parse::<i32>().map_err(|_| bad_header.clone())?;
+            }
+        }
+        patch.start1 = captures[1].parse::<i32>().map_err(|_| bad_header.clone())? - 1;
+        patch.start2 = captures[3].parse::<i32>().map_err(|_| bad_header.clone())? - 1;
+
+        for (i, text_item) in text.iter().enumerate().skip(1) {
+            let line = line + i;
+            let mut chars = text_item.chars();
+            let sign = match chars.next() {
+                Some(sign) => sign,
+                // Blank line?  Whatever.
+                None => continue
+            };
+            let operation = match sign {
+                '+' => Operation::Insert,
+                '-' => Operation::Delete,
+                ' ' => Operation::Equal,
+                _ => return Err(DmpError::BadLinePrefix { line, column: 1, prefix: sign })
+            };
+            let temp6 = percent_decode_checked(chars.as_str(), line, 2)?;
+            let temp6_len = temp6.chars().count() as i32;
+            if operation != Operation::Insert {
+                patch.length1 += temp6_len;
             }
-            else {
-                panic!("wrong patch string");
+            if operation != Operation::Delete {
+                patch.length2 += temp6_len;
             }
+            patch.diffs.push(Diff::new(operation, temp6));
         }
-        patch
+        Ok(patch)
     }
 }
 
@@ -2977,16 +2771,11 @@ impl Display for Patch {
         }
         text += " @@\n";
         for i in 0..self.diffs.len() {
-            let ch: char;
-            if self.diffs[i].operation == 0 {
-                ch = ' ';
-            }
-            else if self.diffs[i].operation == -1 {
-                ch = '-';
-            }
-            else {
-                ch = '+';
-            }
+            let ch: char = match self.diffs[i].operation {
+                Operation::Equal => ' ',
+                Operation::Delete => '-',
+                Operation::Insert => '+'
+            };
             text.push(ch);
             let text_vec: Vec<char> = self.diffs[i].text.chars().collect();
             let temp5: Vec<char> = vec!['!', '~', '*', '(', ')', ';', '/', '?', ':', '@', '&', '=', '+', '$', ',', '#', ' ', '\''];
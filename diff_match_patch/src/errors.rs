@@ -0,0 +1,64 @@
+use std::fmt;
+
+/*Errors raised while parsing patch text and deltas, and while synchronizing.
+
+Line and column numbers are 1-based.  Deltas are a single line, so their
+errors always report line 1 and a byte column within the delta string.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DmpError {
+    // A patch header is not of the form "@@ -a,b +c,d @@".
+    BadHeader { line: usize, column: usize },
+    // A patch line or delta token starts with an unknown operation.
+    BadLinePrefix { line: usize, column: usize, prefix: char },
+    // A delta token does not carry a valid length.
+    BadNumber { line: usize, column: usize },
+    // A '%' escape is not followed by two hex digits.
+    BadEscape { line: usize, column: usize },
+    // Decoded text is not valid UTF-8.
+    InvalidUtf8 { line: usize, column: usize },
+    // Decoded text is not valid UTF-16 (e.g. an unpaired surrogate).
+    InvalidUtf16 { line: usize, column: usize },
+    // A delta does not consume exactly the length of the source text.
+    LengthMismatch { line: usize, column: usize, expected: usize, actual: usize },
+    // A sync message does not follow on from the versions of the shadow.
+    VersionMismatch { expected: u32, actual: u32 },
+    // The shadows of both peers differ after a sync message.
+    ChecksumMismatch { expected: u32, actual: u32 }
+}
+
+impl fmt::Display for DmpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DmpError::BadHeader { line, column } => {
+                write!(f, "Invalid patch header at line {line}, column {column}")
+            },
+            DmpError::BadLinePrefix { line, column, prefix } => {
+                write!(f, "Invalid operation {prefix:?} at line {line}, column {column}")
+            },
+            DmpError::BadNumber { line, column } => {
+                write!(f, "Invalid length at line {line}, column {column}")
+            },
+            DmpError::BadEscape { line, column } => {
+                write!(f, "Malformed escape sequence at line {line}, column {column}")
+            },
+            DmpError::InvalidUtf8 { line, column } => {
+                write!(f, "Invalid UTF-8 at line {line}, column {column}")
+            },
+            DmpError::InvalidUtf16 { line, column } => {
+                write!(f, "Invalid UTF-16 at line {line}, column {column}")
+            },
+            DmpError::LengthMismatch { line, column, expected, actual } => {
+                write!(f, "Length mismatch at line {line}, column {column}: expected {expected}, found {actual}")
+            },
+            DmpError::VersionMismatch { expected, actual } => {
+                write!(f, "Version mismatch: expected {expected}, found {actual}")
+            },
+            DmpError::ChecksumMismatch { expected, actual } => {
+                write!(f, "Checksum mismatch: expected {expected:08x}, found {actual:08x}")
+            },
+        }
+    }
+}
+
+impl std::error::Error for DmpError {}
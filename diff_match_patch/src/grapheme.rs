@@ -0,0 +1,151 @@
+/*Grapheme clusters.
+
+An emoji ZWJ sequence, a flag or a letter with combining marks is several
+chars but a single grapheme cluster.  With grapheme_clusters set, diff_main,
+diff_cleanup_semantic_lossless and patch_add_context keep every boundary
+between diffs on a cluster boundary of both texts, so no cluster ends up
+half in an equality and half in an edit.  The other cleanups may split a
+cluster again, diff_cleanup_graphemes puts it back together.
+*/
+
+use std::ops::Range;
+
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
+
+use super::dmp::{Diff, Dmp, Operation};
+use super::tokenizer::Tokenizer;
+
+pub struct GraphemeTokenizer;
+
+impl Tokenizer for GraphemeTokenizer {
+    fn tokenize(&self, text: &[char]) -> Vec<Range<usize>> {
+        let string: String = text.iter().collect();
+        let mut spans: Vec<Range<usize>> = vec![];
+        let mut start = 0;
+        for cluster in string.graphemes(true) {
+            let end = start + cluster.chars().count();
+            spans.push(start..end);
+            start = end;
+        }
+        spans
+    }
+}
+
+pub(crate) fn grapheme_boundaries(text: &[char]) -> Vec<bool> {
+    // whether every position from 0 to len inclusive is between two clusters.
+    let mut boundaries = vec![false; text.len() + 1];
+    for span in GraphemeTokenizer.tokenize(text) {
+        boundaries[span.start] = true;
+    }
+    boundaries[text.len()] = true;
+    boundaries
+}
+
+pub(crate) fn grapheme_boundary(text: &[char], pos: usize) -> bool {
+    // whether pos is between two clusters, segmenting only the chars before
+    // it as far back as the rules need.
+    let mut before = 16;
+    loop {
+        let start = pos.saturating_sub(before);
+        let end = (pos + 2).min(text.len());
+        let window: String = text[start..end].iter().collect();
+        // Make the cursor believe there is more text around the window,
+        // unless the window reaches that end of text.
+        let base = if start == 0 { 0 } else { usize::MAX / 4 };
+        let length = base + window.len() + if end == text.len() { 0 } else { usize::MAX / 4 };
+        let offset = base + text[start..pos].iter().map(|ch| ch.len_utf8()).sum::<usize>();
+        match GraphemeCursor::new(offset, length, true).is_boundary(&window, base) {
+            Ok(boundary) => return boundary,
+            // Only asks for more of what comes before.
+            Err(_) => before *= 2
+        }
+    }
+}
+
+fn grapheme_flush(diffs: &mut Vec<Diff>, deleted: &mut Vec<char>, inserted: &mut Vec<char>) {
+    // append the pending change to diffs.
+    if !deleted.is_empty() {
+        diffs.push(Diff::new(Operation::Delete, deleted.drain(..).collect()));
+    }
+    if !inserted.is_empty() {
+        diffs.push(Diff::new(Operation::Insert, inserted.drain(..).collect()));
+    }
+}
+
+impl Dmp {
+    pub fn diff_cleanup_graphemes(&self, diffs: &mut Vec<Diff>) {
+        /*
+        Widen every change whose ends split a grapheme cluster of text1 or
+        text2, moving the chars of the cluster out of the equalities around it.
+        e.g: e<del>\u{301}</del><ins>\u{300}</ins> -> <del>e\u{301}</del><ins>e\u{300}</ins>
+
+        Args:
+            diffs: Vector of diff object.
+      */
+        let text1: Vec<char> = self.diff_text1(diffs).chars().collect();
+        let text2: Vec<char> = self.diff_text2(diffs).chars().collect();
+        let boundaries1 = grapheme_boundaries(&text1);
+        let boundaries2 = grapheme_boundaries(&text2);
+        let boundary = |chars1: usize, chars2: usize| boundaries1[chars1] && boundaries2[chars2];
+
+        let mut cleaned: Vec<Diff> = vec![];
+        // The change being built, every deletion and insertion since the last equality.
+        let mut deleted: Vec<char> = vec![];
+        let mut inserted: Vec<char> = vec![];
+        let mut chars1 = 0;
+        let mut chars2 = 0;
+        for (i, diff) in diffs.iter().enumerate() {
+            let text: Vec<char> = diff.text.chars().collect();
+            match diff.operation {
+                Operation::Delete => {
+                    deleted.extend(&text);
+                    chars1 += text.len();
+                },
+                Operation::Insert => {
+                    inserted.extend(&text);
+                    chars2 += text.len();
+                },
+                Operation::Equal => {
+                    // Grow the change before the equality up to a cluster boundary.
+                    let mut start = 0;
+                    if !deleted.is_empty() || !inserted.is_empty() {
+                        while start < text.len() && !boundary(chars1 + start, chars2 + start) {
+                            start += 1;
+                        }
+                    }
+                    // And the change after it back to one.
+                    let mut end = text.len();
+                    if diffs.get(i + 1).is_some_and(|next| next.operation != Operation::Equal) {
+                        while end > start && !boundary(chars1 + end, chars2 + end) {
+                            end -= 1;
+                        }
+                    }
+                    deleted.extend(&text[..start]);
+                    inserted.extend(&text[..start]);
+                    if start < end {
+                        grapheme_flush(&mut cleaned, &mut deleted, &mut inserted);
+                        cleaned.push(Diff::new(Operation::Equal, text[start..end].iter().collect()));
+                    }
+                    deleted.extend(&text[start.max(end)..]);
+                    inserted.extend(&text[start.max(end)..]);
+                    chars1 += text.len();
+                    chars2 += text.len();
+                }
+            }
+        }
+        grapheme_flush(&mut cleaned, &mut deleted, &mut inserted);
+        *diffs = cleaned;
+    }
+
+    pub(crate) fn grapheme_fits(&self, equality1: &[char], edit: &[char], equality2: &[char]) -> bool {
+        // whether an edit between two equalities starts and ends on cluster
+        // boundaries, on both the side holding it and the side without it.
+        if !self.grapheme_clusters {
+            return true;
+        }
+        let with_edit: Vec<char> = [equality1, edit, equality2].concat();
+        let without_edit: Vec<char> = [equality1, equality2].concat();
+        let boundaries = grapheme_boundaries(&with_edit);
+        boundaries[equality1.len()] && boundaries[equality1.len() + edit.len()] && grapheme_boundaries(&without_edit)[equality1.len()]
+    }
+}
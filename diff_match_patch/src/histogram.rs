@@ -0,0 +1,110 @@
+/*Histogram diff, after git diff --histogram.
+
+The occurrences of every token of text1 are counted.  Each match found while
+scanning text2 is grown into the longest common region around it.  As in
+git's xhistogram.c, a region replaces the current anchor when it is longer,
+or when its rarest token occurs fewer times in text1 than the anchor's
+rarest token does.  Both sides of the anchor are diffed recursively.
+Tokens occurring more than HISTOGRAM_MAX_CHAIN times are never used to
+start a region, and when nothing can anchor the diff falls back to the
+Myers bisect.
+*/
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::Instant;
+
+use super::dmp::{Dmp, Operation};
+use super::tokens::DiffOp;
+
+const HISTOGRAM_MAX_CHAIN: usize = 64;
+
+struct HistogramRegion {
+    start1: usize,
+    start2: usize,
+    length: usize,
+    // occurrences in text1 of the rarest token of the region.
+    count: usize
+}
+
+fn histogram_anchor<T: Eq + Hash>(text1: &[T], text2: &[T]) -> Option<HistogramRegion> {
+    let mut occurrences: HashMap<&T, Vec<usize>> = HashMap::new();
+    for (i, token) in text1.iter().enumerate() {
+        occurrences.entry(token).or_default().push(i);
+    }
+    let count_of = |token: &T| occurrences.get(token).map_or(0, |positions| positions.len());
+
+    let mut best: Option<HistogramRegion> = None;
+    let mut j = 0;
+    while j < text2.len() {
+        let mut next_j = j + 1;
+        let positions = match occurrences.get(&text2[j]) {
+            Some(positions) => positions,
+            None => {
+                j = next_j;
+                continue;
+            }
+        };
+        let best_count = best.as_ref().map_or(HISTOGRAM_MAX_CHAIN, |region| region.count);
+        if positions.len() > best_count {
+            j = next_j;
+            continue;
+        }
+        for &i in positions {
+            // Grow the match into the longest common region around it.
+            let mut count = positions.len();
+            let mut start1 = i;
+            let mut start2 = j;
+            while start1 > 0 && start2 > 0 && text1[start1 - 1] == text2[start2 - 1] {
+                start1 -= 1;
+                start2 -= 1;
+                count = count.min(count_of(&text1[start1]));
+            }
+            let mut end1 = i + 1;
+            let mut end2 = j + 1;
+            while end1 < text1.len() && end2 < text2.len() && text1[end1] == text2[end2] {
+                count = count.min(count_of(&text1[end1]));
+                end1 += 1;
+                end2 += 1;
+            }
+            next_j = next_j.max(end2);
+            let better = match &best {
+                Some(region) => end1 - start1 > region.length || count < region.count,
+                None => true
+            };
+            if better {
+                best = Some(HistogramRegion { start1, start2, length: end1 - start1, count });
+            }
+        }
+        j = next_j;
+    }
+    best
+}
+
+impl Dmp {
+    pub(crate) fn diff_histogram_tokens<T: Eq + Hash + Clone>(&self, text1: &[T], text2: &[T], start_time: Instant) -> Vec<DiffOp<T>> {
+        /*
+        Find the differences between two token sequences using histogram diff.
+        Assumes that they do not have any common prefix or suffix.
+
+        Args:
+            text1: Old tokens to be diffed.
+            text2: New tokens to be diffed.
+            start_time: Time at which the diff started, for the bisect fallback.
+
+        Returns:
+            Vector of token diffs as changes.
+        */
+        let region = match histogram_anchor(text1, text2) {
+            Some(region) => region,
+            // Only very common tokens in common.
+            None => return self.diff_bisect_tokens(text1, text2, start_time)
+        };
+        let end1 = region.start1 + region.length;
+        let end2 = region.start2 + region.length;
+        let mut diffs = self.diff_tokens_internal(&text1[..region.start1], &text2[..region.start2], start_time);
+        diffs.push(DiffOp::new(Operation::Equal, text1[region.start1..end1].to_vec()));
+        diffs.extend(self.diff_tokens_internal(&text1[end1..], &text2[end2..], start_time));
+        diffs
+    }
+}
@@ -1,4 +1,38 @@
+mod bytes;
+mod compose;
 mod dmp;
+mod errors;
+mod grapheme;
+mod histogram;
+mod linemap;
+mod mapping;
+mod merge;
+mod moves;
+mod normalize;
+mod patience;
 mod percent_encoding;
+mod pretty;
+mod review;
+mod side_by_side;
+mod sync;
+mod tokenizer;
+mod tokens;
+mod transform;
+mod unified;
 
-pub use dmp::*;
\ No newline at end of file
+pub use bytes::*;
+pub use dmp::*;
+pub use errors::*;
+pub use grapheme::GraphemeTokenizer;
+pub use linemap::*;
+pub use mapping::*;
+pub use merge::*;
+pub use moves::*;
+pub use normalize::WhitespaceMode;
+pub use pretty::*;
+pub use review::*;
+pub use side_by_side::*;
+pub use sync::*;
+pub use tokenizer::*;
+pub use tokens::{DiffOp, DiffRange};
+pub use unified::*;
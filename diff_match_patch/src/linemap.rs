@@ -0,0 +1,95 @@
+/*Line number mapping.
+
+A line is its chars up to and including its line break; the last line of a
+text may have no line break.  An old line maps to the new line its line
+break ends up in, so a line edited in place still maps, while a line whose
+line break was deleted is gone.  Line numbers are 0-based.
+*/
+
+use super::dmp::{Diff, Dmp, Operation};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineMap {
+    // new line of every old line, None where the line was deleted.
+    old_to_new: Vec<Option<usize>>,
+    // old line of every new line, None where the line was inserted.
+    new_to_old: Vec<Option<usize>>
+}
+
+impl LineMap {
+    pub fn new_line(&self, old_line: usize) -> Option<usize> {
+        // line of text2 old_line of text1 became, None if it was deleted.
+        self.old_to_new.get(old_line).copied().flatten()
+    }
+
+    pub fn old_line(&self, new_line: usize) -> Option<usize> {
+        // line of text1 new_line of text2 came from, None if it was inserted.
+        self.new_to_old.get(new_line).copied().flatten()
+    }
+
+    pub fn old_lines(&self) -> usize {
+        self.old_to_new.len()
+    }
+
+    pub fn new_lines(&self) -> usize {
+        self.new_to_old.len()
+    }
+}
+
+impl Dmp {
+    pub fn diff_line_map(&self, diffs: &[Diff]) -> LineMap {
+        /*
+        Compute which lines of text1 and text2 correspond, from a diff such
+        as the output of diff_linemode or diff_chars_tolines.
+
+        Args:
+            diffs: Vector of Diff objects.
+
+        Returns:
+            LineMap between the lines of text1 and text2.
+        */
+        let mut diffs = diffs.to_vec();
+        self.diff_cleanup_semantic_lossless(&mut diffs);
+        let mut old_to_new: Vec<Option<usize>> = vec![];
+        let mut new_to_old: Vec<Option<usize>> = vec![];
+        // Whether the last line of either text has chars but no line break yet.
+        let mut partial1 = false;
+        let mut partial2 = false;
+        for diff in &diffs {
+            for ch in diff.text.chars() {
+                if ch != '\n' {
+                    partial1 |= diff.operation != Operation::Insert;
+                    partial2 |= diff.operation != Operation::Delete;
+                    continue;
+                }
+                match diff.operation {
+                    Operation::Equal => {
+                        old_to_new.push(Some(new_to_old.len()));
+                        new_to_old.push(Some(old_to_new.len() - 1));
+                        partial1 = false;
+                        partial2 = false;
+                    },
+                    Operation::Delete => {
+                        old_to_new.push(None);
+                        partial1 = false;
+                    },
+                    Operation::Insert => {
+                        new_to_old.push(None);
+                        partial2 = false;
+                    }
+                }
+            }
+        }
+        // The end of both texts acts as a shared line break.
+        match (partial1, partial2) {
+            (true, true) => {
+                old_to_new.push(Some(new_to_old.len()));
+                new_to_old.push(Some(old_to_new.len() - 1));
+            },
+            (true, false) => old_to_new.push(None),
+            (false, true) => new_to_old.push(None),
+            (false, false) => {}
+        }
+        LineMap { old_to_new, new_to_old }
+    }
+}
@@ -0,0 +1,254 @@
+/*Position mapping.
+
+Positions are char offsets between chars, 0 to len inclusive.  A diff (or a
+patch set) is walked as a list of equalities and changes; a change is every
+deletion and insertion between two equalities.  A position at either end of
+a change stays on its side of it, a position inside deleted text is reported
+as deleted.  Bias only decides where a position goes when text is inserted
+right there, or when it falls inside deleted text.
+*/
+
+use std::ops::Range;
+
+use super::compose::patch_gaps;
+use super::dmp::{Diff, Dmp, Operation, Patch};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bias {
+    // Stay before text inserted at the position.
+    Left,
+    // Move past text inserted at the position.
+    Right
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MappedPosition {
+    // position in the other text.
+    pub position: usize,
+    // the position was inside deleted text.
+    pub deleted: bool
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MappedRange {
+    // range in the other text.
+    pub range: Range<usize>,
+    // every char of the range was deleted, so it collapsed.
+    pub deleted: bool
+}
+
+fn diff_segments(diffs: &[Diff], reverse: bool) -> Vec<(Operation, usize)> {
+    // the operation and char length of every diff, with deletions and
+    // insertions swapped when mapping from text2 back to text1.
+    diffs.iter().map(|diff| {
+        let operation = match diff.operation {
+            Operation::Delete if reverse => Operation::Insert,
+            Operation::Insert if reverse => Operation::Delete,
+            operation => operation
+        };
+        (operation, diff.text.chars().count())
+    }).collect()
+}
+
+fn patch_segments(patches: &[Patch]) -> Vec<(Operation, usize)> {
+    // the segments of a patch set, the text between patches being kept.
+    let mut segments: Vec<(Operation, usize)> = vec![];
+    for (gap, patch) in patch_gaps(patches) {
+        if gap > 0 {
+            segments.push((Operation::Equal, gap));
+        }
+        segments.extend(diff_segments(&patch.diffs, false));
+    }
+    segments
+}
+
+fn map_positions(segments: &[(Operation, usize)], locs: &[usize], bias: Bias) -> Vec<MappedPosition> {
+    // map every position in one walk over the segments, in sorted order.
+    let mut order: Vec<usize> = (0..locs.len()).collect();
+    order.sort_by_key(|&i| locs[i]);
+    let mut order = order.into_iter().peekable();
+    let mut mapped = vec![MappedPosition { position: 0, deleted: false }; locs.len()];
+    let mut chars1 = 0;
+    let mut chars2 = 0;
+    let mut k = 0;
+    while k < segments.len() {
+        let (operation, length) = segments[k];
+        if operation == Operation::Equal {
+            while let Some(i) = order.next_if(|&i| locs[i] < chars1 + length) {
+                mapped[i] = MappedPosition { position: chars2 + locs[i] - chars1, deleted: false };
+            }
+            chars1 += length;
+            chars2 += length;
+            k += 1;
+            continue;
+        }
+        let mut deleted = 0;
+        let mut inserted = 0;
+        while let Some((operation, length)) = segments.get(k).filter(|(operation, _)| *operation != Operation::Equal) {
+            if *operation == Operation::Delete {
+                deleted += length;
+            }
+            else {
+                inserted += length;
+            }
+            k += 1;
+        }
+        let before = MappedPosition { position: chars2, deleted: false };
+        let after = MappedPosition { position: chars2 + inserted, deleted: false };
+        while let Some(i) = order.next_if(|&i| locs[i] <= chars1 + deleted) {
+            mapped[i] = if deleted > 0 && locs[i] == chars1 {
+                before
+            }
+            else if deleted > 0 && locs[i] == chars1 + deleted {
+                after
+            }
+            else {
+                let position = if bias == Bias::Left { before } else { after };
+                MappedPosition { deleted: deleted > 0, ..position }
+            };
+        }
+        chars1 += deleted;
+        chars2 += inserted;
+    }
+    // Past the end of text1.
+    for i in order {
+        mapped[i] = MappedPosition { position: chars2 + locs[i] - chars1, deleted: false };
+    }
+    mapped
+}
+
+fn map_ranges(segments: &[(Operation, usize)], ranges: &[Range<usize>]) -> Vec<MappedRange> {
+    // map ranges so that text inserted at either end stays outside.
+    let starts: Vec<usize> = ranges.iter().map(|range| range.start).collect();
+    let ends: Vec<usize> = ranges.iter().map(|range| range.end).collect();
+    let starts = map_positions(segments, &starts, Bias::Right);
+    let ends = map_positions(segments, &ends, Bias::Left);
+    ranges.iter().zip(starts.iter().zip(ends)).map(|(range, (start, end))| {
+        let start = start.position;
+        let end = end.position.max(start);
+        MappedRange { range: start..end, deleted: !range.is_empty() && start == end }
+    }).collect()
+}
+
+impl Dmp {
+    pub fn diff_map_position(&self, diffs: &[Diff], loc: usize, bias: Bias) -> MappedPosition {
+        /*
+        loc is a position in text1, compute the equivalent position in text2.
+        Unlike diff_xindex, text inserted at loc is skipped or not as bias says,
+        and positions inside deleted text are flagged.
+
+        Args:
+            diffs: Vector of Diff objects.
+            loc: Position within text1.
+            bias: Side of text inserted at loc to end up on.
+
+        Returns:
+            MappedPosition within text2.
+        */
+        map_positions(&diff_segments(diffs, false), &[loc], bias)[0]
+    }
+
+    pub fn diff_map_positions(&self, diffs: &[Diff], locs: &[usize], bias: Bias) -> Vec<MappedPosition> {
+        /*
+        Map many positions from text1 to text2 in a single pass over diffs.
+
+        Args:
+            diffs: Vector of Diff objects.
+            locs: Positions within text1, in any order.
+            bias: Side of inserted text to end up on.
+
+        Returns:
+            Vector of MappedPosition within text2, in the order of locs.
+        */
+        map_positions(&diff_segments(diffs, false), locs, bias)
+    }
+
+    pub fn diff_unmap_positions(&self, diffs: &[Diff], locs: &[usize], bias: Bias) -> Vec<MappedPosition> {
+        /*
+        Map positions back from text2 to text1.  Positions inside text
+        inserted by diffs are reported as deleted.
+
+        Args:
+            diffs: Vector of Diff objects.
+            locs: Positions within text2, in any order.
+            bias: Side of deleted text to end up on.
+
+        Returns:
+            Vector of MappedPosition within text1, in the order of locs.
+        */
+        map_positions(&diff_segments(diffs, true), locs, bias)
+    }
+
+    pub fn diff_map_range(&self, diffs: &[Diff], range: Range<usize>) -> MappedRange {
+        /*
+        Map a selection from text1 to text2, see diff_map_ranges.
+
+        Args:
+            diffs: Vector of Diff objects.
+            range: Char range within text1.
+
+        Returns:
+            MappedRange within text2.
+        */
+        map_ranges(&diff_segments(diffs, false), &[range]).remove(0)
+    }
+
+    pub fn diff_map_ranges(&self, diffs: &[Diff], ranges: &[Range<usize>]) -> Vec<MappedRange> {
+        /*
+        Map selections or annotations from text1 to text2.  Text inserted at
+        either end of a range is left out of it, a range whose text is all
+        deleted collapses.  Empty ranges move past inserted text like a caret.
+
+        Args:
+            diffs: Vector of Diff objects.
+            ranges: Char ranges within text1.
+
+        Returns:
+            Vector of MappedRange within text2, in the order of ranges.
+        */
+        map_ranges(&diff_segments(diffs, false), ranges)
+    }
+
+    pub fn diff_unmap_ranges(&self, diffs: &[Diff], ranges: &[Range<usize>]) -> Vec<MappedRange> {
+        /*
+        Map ranges back from text2 to text1.
+
+        Args:
+            diffs: Vector of Diff objects.
+            ranges: Char ranges within text2.
+
+        Returns:
+            Vector of MappedRange within text1, in the order of ranges.
+        */
+        map_ranges(&diff_segments(diffs, true), ranges)
+    }
+
+    pub fn patch_map_positions(&self, patches: &[Patch], locs: &[usize], bias: Bias) -> Vec<MappedPosition> {
+        /*
+        Map positions through a patch set, as if it applied exactly.
+
+        Args:
+            patches: Vector of Patch objects.
+            locs: Positions within the text before patching.
+            bias: Side of inserted text to end up on.
+
+        Returns:
+            Vector of MappedPosition within the patched text.
+        */
+        map_positions(&patch_segments(patches), locs, bias)
+    }
+
+    pub fn patch_map_ranges(&self, patches: &[Patch], ranges: &[Range<usize>]) -> Vec<MappedRange> {
+        /*
+        Map ranges through a patch set, as if it applied exactly.
+
+        Args:
+            patches: Vector of Patch objects.
+            ranges: Char ranges within the text before patching.
+
+        Returns:
+            Vector of MappedRange within the patched text.
+        */
+        map_ranges(&patch_segments(patches), ranges)
+    }
+}
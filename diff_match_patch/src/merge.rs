@@ -0,0 +1,227 @@
+/*Three-way merge.
+
+Both sides are diffed against the common base and their edits are walked in
+base order.  Edits touching the same stretch of base are grouped; a group
+edited by only one side, or edited identically by both, merges cleanly.
+Anything else is a conflict, which is widened to whole lines of the base so
+that it reads like the conflicts of git, and resolved according to
+Dmp::merge_resolution.
+*/
+
+use std::ops::Range;
+
+use super::dmp::{Diff, Dmp, Operation};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeResolution {
+    // Keep both sides between <<<<<<< ======= >>>>>>> markers.
+    Markers,
+    // Take our side of every conflict.
+    Ours,
+    // Take their side of every conflict.
+    Theirs,
+    // Take our side followed by theirs.
+    Union
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeConflict {
+    // conflicting region, as found in each of the three texts.
+    pub base: String,
+    pub ours: String,
+    pub theirs: String,
+    // char range of the resolved region in the merged text.
+    pub range: Range<usize>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeResult {
+    // merged text, and the conflicts found on the way.
+    pub text: String,
+    pub conflicts: Vec<MergeConflict>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MergeEdit {
+    // replace base[start..end] by text.
+    start: usize,
+    end: usize,
+    text: String
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MergeCluster {
+    // region of base, and the edits of each side falling in it.
+    start: usize,
+    end: usize,
+    ours: Range<usize>,
+    theirs: Range<usize>
+}
+
+fn merge_edits(diffs: &[Diff]) -> Vec<MergeEdit> {
+    // base offsets (in chars) of the changes of a diff.
+    let mut edits: Vec<MergeEdit> = vec![];
+    let mut offset = 0;
+    for diff in diffs {
+        let length = diff.text.chars().count();
+        if diff.operation == Operation::Equal {
+            offset += length;
+            continue;
+        }
+        let edit = match edits.last_mut() {
+            Some(edit) if edit.end == offset => edit,
+            _ => {
+                edits.push(MergeEdit { start: offset, end: offset, text: String::new() });
+                edits.last_mut().unwrap()
+            }
+        };
+        if diff.operation == Operation::Delete {
+            edit.end += length;
+            offset += length;
+        }
+        else {
+            edit.text += diff.text.as_str();
+        }
+    }
+    edits
+}
+
+fn merge_overlaps(edit: &MergeEdit, start: usize, end: usize) -> bool {
+    // edits starting at the same place overlap, even if one of them is an insertion.
+    (edit.start < end && start < edit.end) || edit.start == start
+}
+
+fn merge_side(base: &[char], edits: &[MergeEdit], start: usize, end: usize) -> String {
+    // one side's version of base[start..end].
+    let mut text = String::new();
+    let mut position = start;
+    for edit in edits {
+        text.extend(&base[position..edit.start]);
+        text += edit.text.as_str();
+        position = edit.end;
+    }
+    text.extend(&base[position..end]);
+    text
+}
+
+fn merge_line_bounds(base: &[char], mut start: usize, mut end: usize) -> (usize, usize) {
+    // widen base[start..end] to whole lines.
+    while start > 0 && base[start - 1] != '\n' {
+        start -= 1;
+    }
+    while end < base.len() && (end == 0 || base[end - 1] != '\n') {
+        end += 1;
+    }
+    (start, end)
+}
+
+impl Dmp {
+    pub fn merge3(&self, base: &str, ours: &str, theirs: &str) -> MergeResult {
+        /*
+        Merge two texts edited concurrently from a common base.
+
+        Args:
+            base: Common ancestor.
+            ours: Our version of base.
+            theirs: Their version of base.
+
+        Returns:
+            MergeResult with the merged text and every conflict found.
+        */
+        let mut ours_diffs = self.diff_exact(base, ours, true);
+        self.diff_cleanup_semantic(&mut ours_diffs);
+        let mut theirs_diffs = self.diff_exact(base, theirs, true);
+        self.diff_cleanup_semantic(&mut theirs_diffs);
+        let ours_edits = merge_edits(&ours_diffs);
+        let theirs_edits = merge_edits(&theirs_diffs);
+        let base: Vec<char> = base.chars().collect();
+        let conflicting = |cluster: &MergeCluster| {
+            !cluster.ours.is_empty() && !cluster.theirs.is_empty()
+                && merge_side(&base, &ours_edits[cluster.ours.clone()], cluster.start, cluster.end) != merge_side(&base, &theirs_edits[cluster.theirs.clone()], cluster.start, cluster.end)
+        };
+
+        // Group the edits of both sides.
+        let mut clusters: Vec<MergeCluster> = vec![];
+        let mut i = 0;
+        let mut j = 0;
+        while i < ours_edits.len() || j < theirs_edits.len() {
+            let first = match (ours_edits.get(i), theirs_edits.get(j)) {
+                (Some(edit1), Some(edit2)) => if edit1.start <= edit2.start { edit1 } else { edit2 },
+                (Some(edit), None) | (None, Some(edit)) => edit,
+                (None, None) => break
+            };
+            let mut cluster = MergeCluster { start: first.start, end: first.end, ours: i..i, theirs: j..j };
+            loop {
+                let before = cluster.clone();
+                while cluster.ours.end < ours_edits.len() && merge_overlaps(&ours_edits[cluster.ours.end], cluster.start, cluster.end) {
+                    cluster.start = cluster.start.min(ours_edits[cluster.ours.end].start);
+                    cluster.end = cluster.end.max(ours_edits[cluster.ours.end].end);
+                    cluster.ours.end += 1;
+                }
+                while cluster.theirs.end < theirs_edits.len() && merge_overlaps(&theirs_edits[cluster.theirs.end], cluster.start, cluster.end) {
+                    cluster.start = cluster.start.min(theirs_edits[cluster.theirs.end].start);
+                    cluster.end = cluster.end.max(theirs_edits[cluster.theirs.end].end);
+                    cluster.theirs.end += 1;
+                }
+                if conflicting(&cluster) {
+                    let (start, end) = merge_line_bounds(&base, cluster.start, cluster.end);
+                    cluster.start = start;
+                    cluster.end = end;
+                    // The widened region may swallow groups already found on the same lines.
+                    while let Some(last) = clusters.last() {
+                        if last.end <= cluster.start && last.start < cluster.start {
+                            break;
+                        }
+                        cluster.start = cluster.start.min(last.start);
+                        cluster.ours.start = last.ours.start;
+                        cluster.theirs.start = last.theirs.start;
+                        clusters.pop();
+                    }
+                }
+                if cluster == before {
+                    break;
+                }
+            }
+            i = cluster.ours.end;
+            j = cluster.theirs.end;
+            clusters.push(cluster);
+        }
+
+        // Rebuild the text.
+        let mut text: Vec<char> = vec![];
+        let mut conflicts: Vec<MergeConflict> = vec![];
+        let mut position = 0;
+        for cluster in clusters {
+            text.extend(&base[position..cluster.start]);
+            position = cluster.end;
+            let ours_text = merge_side(&base, &ours_edits[cluster.ours.clone()], cluster.start, cluster.end);
+            let theirs_text = merge_side(&base, &theirs_edits[cluster.theirs.clone()], cluster.start, cluster.end);
+            if cluster.theirs.is_empty() || ours_text == theirs_text {
+                text.extend(ours_text.chars());
+                continue;
+            }
+            if cluster.ours.is_empty() {
+                text.extend(theirs_text.chars());
+                continue;
+            }
+            // Keep every side on lines of its own.
+            let line = |side: &str| if side.is_empty() || side.ends_with('\n') { side.to_string() } else { format!("{side}\n") };
+            let resolved = match self.merge_resolution {
+                MergeResolution::Markers => format!("<<<<<<< ours\n{}=======\n{}>>>>>>> theirs\n", line(&ours_text), line(&theirs_text)),
+                MergeResolution::Ours => ours_text.clone(),
+                MergeResolution::Theirs => theirs_text.clone(),
+                MergeResolution::Union => line(&ours_text) + theirs_text.as_str()
+            };
+            let start = text.len();
+            text.extend(resolved.chars());
+            conflicts.push(MergeConflict {
+                base: base[cluster.start..cluster.end].iter().collect(),
+                ours: ours_text,
+                theirs: theirs_text,
+                range: start..text.len()
+            });
+        }
+        text.extend(&base[position..]);
+        MergeResult { text: text.into_iter().collect(), conflicts }
+    }
+}
@@ -0,0 +1,89 @@
+/*Move detection.
+
+A block of text moved elsewhere shows up in a diff as a deletion in one
+place and an insertion in another.  Deletions are paired with the most
+similar insertion of another change, where similarity is one minus their
+Levenshtein distance over the length of the longer text.  A deletion and an
+insertion of the same change are a replacement, not a move.
+*/
+
+use super::dmp::{Diff, Dmp, Operation};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffMove {
+    // index in diffs of the deletion and of the insertion it moved to.
+    pub from: usize,
+    pub to: usize,
+    // 1.0 when the moved text is unchanged.
+    pub similarity: f32,
+    // changes made to the text on the way, if asked for.
+    pub diffs: Option<Vec<Diff>>
+}
+
+pub(crate) fn similarity_bound(length1: usize, length2: usize) -> f32 {
+    // the highest similarity two texts of these lengths can have, their
+    // distance being at least the difference in length.
+    length1.min(length2) as f32 / length1.max(length2) as f32
+}
+
+impl Dmp {
+    pub fn diff_moves(&self, diffs: &[Diff], nested: bool) -> Vec<DiffMove> {
+        /*
+        Find the deletions of a diff that were inserted again elsewhere.
+        Texts shorter than move_min_length or less alike than move_threshold
+        are left alone.
+
+        Args:
+            diffs: Vector of Diff objects.
+            nested: Whether to diff the text of every move.
+
+        Returns:
+            Vector of DiffMove, in the order of the deletions.
+        */
+        // Number the changes, moves go from one change to another.
+        let mut changes: Vec<usize> = vec![];
+        let mut change = 0;
+        for diff in diffs {
+            if diff.operation == Operation::Equal {
+                change += 1;
+            }
+            changes.push(change);
+        }
+        let lengths: Vec<usize> = diffs.iter().map(|diff| diff.text.chars().count()).collect();
+        let candidates = |operation: Operation| -> Vec<usize> {
+            (0..diffs.len()).filter(|&i| diffs[i].operation == operation && lengths[i] as i32 >= self.move_min_length.max(1)).collect()
+        };
+        let mut insertions = candidates(Operation::Insert);
+
+        let mut moves: Vec<DiffMove> = vec![];
+        for from in candidates(Operation::Delete) {
+            let mut best: Option<(usize, f32, Vec<Diff>)> = None;
+            for &to in &insertions {
+                if changes[to] == changes[from] {
+                    continue;
+                }
+                let bound = similarity_bound(lengths[from], lengths[to]);
+                if bound < self.move_threshold || best.as_ref().is_some_and(|best| bound <= best.1) {
+                    continue;
+                }
+                let move_diffs = self.diff_exact(&diffs[from].text, &diffs[to].text, false);
+                let similarity = 1.0 - self.diff_levenshtein(&move_diffs) as f32 / lengths[from].max(lengths[to]) as f32;
+                if similarity >= self.move_threshold && best.as_ref().is_none_or(|best| similarity > best.1) {
+                    best = Some((to, similarity, move_diffs));
+                }
+            }
+            if let Some((to, similarity, mut move_diffs)) = best {
+                insertions.retain(|&i| i != to);
+                let diffs = if nested {
+                    self.diff_cleanup_semantic(&mut move_diffs);
+                    Some(move_diffs)
+                }
+                else {
+                    None
+                };
+                moves.push(DiffMove { from, to, similarity, diffs });
+            }
+        }
+        moves
+    }
+}
@@ -0,0 +1,195 @@
+/*Diffs ignoring whitespace, blank lines or case.
+
+Each text is cut into tokens: a normalized key to compare, and the span of
+original chars it stands for.  Ignored chars are folded into the span of
+the token before them, so the spans still cover the whole text.  The keys
+are diffed, and the diff is rebuilt from the spans: deletions and
+equalities hold chars of text1, insertions chars of text2.  diff_text1
+gives text1 back exactly, diff_text2 gives text2 up to what was ignored.
+
+Only diff_main and diff_linemode normalize.  Patches, deltas, merges, sync
+and the diffs inside moves and review lines must rebuild text2 exactly, so
+they go through diff_exact.
+*/
+
+use std::ops::Range;
+use std::time::Instant;
+
+use super::dmp::{Diff, DiffGranularity, Dmp, Operation};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WhitespaceMode {
+    // Compare whitespace like any other char.
+    Exact,
+    // Any run of whitespace equals any other, trailing whitespace is ignored (diff -b).
+    IgnoreAmount,
+    // Ignore whitespace altogether (diff -w).
+    IgnoreAll
+}
+
+#[derive(Debug, Clone)]
+struct NormalizedToken {
+    // text compared, and the original chars it stands for.
+    key: String,
+    span: Range<usize>
+}
+
+fn normalized_push(diffs: &mut Vec<Diff>, operation: Operation, text: &[char]) {
+    // append text to diffs, merging it into the last diff when the operation is the same.
+    if text.is_empty() {
+        return;
+    }
+    if let Some(last) = diffs.last_mut() {
+        if last.operation == operation {
+            last.text.extend(text);
+            return;
+        }
+    }
+    diffs.push(Diff::new(operation, text.iter().collect()));
+}
+
+impl Dmp {
+    pub(crate) fn diff_ignores_anything(&self) -> bool {
+        self.whitespace_mode != WhitespaceMode::Exact || self.ignore_blank_lines || self.ignore_case
+    }
+
+    fn normalized_chars(&self, text: &[char]) -> Vec<NormalizedToken> {
+        /*
+        Cut text into one token per char that counts.
+
+        Args:
+            text: Chars to normalize.
+
+        Returns:
+            Vector of tokens, starting with an empty one for leading ignored chars.
+        */
+        // Chars of lines holding nothing but whitespace.
+        let mut blank = vec![false; text.len()];
+        if self.ignore_blank_lines {
+            let mut line_start = 0;
+            for i in 0..text.len() {
+                if text[i] == '\n' || i + 1 == text.len() {
+                    if text[line_start..=i].iter().all(|c| c.is_whitespace()) {
+                        blank[line_start..=i].iter_mut().for_each(|b| *b = true);
+                    }
+                    line_start = i + 1;
+                }
+            }
+        }
+        let is_space = |i: usize| text[i] != '\n' && text[i].is_whitespace() && !blank[i];
+
+        let mut tokens = vec![NormalizedToken { key: String::new(), span: 0..0 }];
+        let mut i = 0;
+        while i < text.len() {
+            let ignored = blank[i] || match self.whitespace_mode {
+                WhitespaceMode::Exact => false,
+                WhitespaceMode::IgnoreAll => is_space(i),
+                WhitespaceMode::IgnoreAmount => {
+                    let mut end = i;
+                    while end < text.len() && is_space(end) {
+                        end += 1;
+                    }
+                    if end > i && end < text.len() && text[end] != '\n' {
+                        // One space stands for the whole run.
+                        tokens.push(NormalizedToken { key: " ".to_string(), span: i..end });
+                        i = end;
+                        continue;
+                    }
+                    // Trailing whitespace.
+                    end > i
+                }
+            };
+            if ignored {
+                tokens.last_mut().unwrap().span.end = i + 1;
+            }
+            else {
+                let key = if self.ignore_case { text[i].to_lowercase().collect() } else { text[i].to_string() };
+                tokens.push(NormalizedToken { key, span: i..i + 1 });
+            }
+            i += 1;
+        }
+        tokens
+    }
+
+    fn normalized_tokens(&self, text: &[char], granularity: DiffGranularity) -> Vec<NormalizedToken> {
+        // the char tokens of text, grouped into words or lines.
+        let chars = self.normalized_chars(text);
+        if granularity == DiffGranularity::Char {
+            return chars;
+        }
+        let starts = self.diff_token_starts(text, &*self.diff_tokenizer(granularity));
+        let mut tokens: Vec<NormalizedToken> = vec![];
+        let mut group = usize::MAX;
+        for token in chars {
+            let token_group = starts.partition_point(|&start| start <= token.span.start);
+            match tokens.last_mut() {
+                Some(last) if token_group == group => {
+                    last.key += token.key.as_str();
+                    last.span.end = token.span.end;
+                },
+                _ => tokens.push(token)
+            }
+            group = token_group;
+        }
+        tokens
+    }
+
+    fn diff_normalized_tokens(&self, text1: &[char], text2: &[char], tokens1: &[NormalizedToken], tokens2: &[NormalizedToken], start_time: Instant) -> Vec<(Operation, Range<usize>)> {
+        // diff the keys of the tokens, returning the spans of each operation.
+        let keys1: Vec<&str> = tokens1.iter().map(|token| token.key.as_str()).collect();
+        let keys2: Vec<&str> = tokens2.iter().map(|token| token.key.as_str()).collect();
+        let mut spans: Vec<(Operation, Range<usize>)> = vec![];
+        let mut i1 = 0;
+        let mut i2 = 0;
+        for op in self.diff_tokens_internal(&keys1, &keys2, start_time) {
+            let count = op.tokens.len();
+            let (tokens, i, length) = match op.operation {
+                Operation::Insert => (tokens2, &mut i2, text2.len()),
+                _ => (tokens1, &mut i1, text1.len())
+            };
+            let span = tokens[*i].span.start..tokens.get(*i + count).map_or(length, |token| token.span.start);
+            *i += count;
+            if op.operation == Operation::Equal {
+                i2 += count;
+            }
+            spans.push((op.operation, span));
+        }
+        spans
+    }
+
+    pub(crate) fn diff_normalized(&self, text1: &[char], text2: &[char], granularity: DiffGranularity, rediff: bool, start_time: Instant) -> Vec<Diff> {
+        /*
+        Diff two texts under whitespace_mode, ignore_blank_lines and
+        ignore_case.
+
+        Args:
+            text1: Old chars to be diffed.
+            text2: New chars to be diffed.
+            granularity: Tokens to diff.
+            rediff: Rediff replaced tokens char by char, as diff_linemode does.
+            start_time: Start of the diff, for diff_timeout.
+
+        Returns:
+            Vector of diffs as changes.
+        */
+        let text = |operation: Operation, span: &Range<usize>| if operation == Operation::Insert { &text2[span.clone()] } else { &text1[span.clone()] };
+        let tokens1 = self.normalized_tokens(text1, granularity);
+        let tokens2 = self.normalized_tokens(text2, granularity);
+        let spans = self.diff_normalized_tokens(text1, text2, &tokens1, &tokens2, start_time);
+        let mut diffs: Vec<Diff> = vec![];
+        let mut i = 0;
+        while i < spans.len() {
+            if let (true, [(Operation::Delete, deleted), (Operation::Insert, inserted), ..]) = (rediff, &spans[i..]) {
+                for diff in self.diff_normalized(&text1[deleted.clone()], &text2[inserted.clone()], DiffGranularity::Char, false, start_time) {
+                    normalized_push(&mut diffs, diff.operation, &diff.text.chars().collect::<Vec<char>>());
+                }
+                i += 2;
+                continue;
+            }
+            let (operation, span) = &spans[i];
+            normalized_push(&mut diffs, *operation, text(*operation, span));
+            i += 1;
+        }
+        diffs
+    }
+}
@@ -0,0 +1,96 @@
+/*Patience diff.
+
+Tokens that occur exactly once in both sequences are paired up, and the
+longest run of pairs appearing in the same order in both becomes a set of
+anchors.  The gaps between anchors are diffed recursively; a gap with no
+unique tokens left falls back to the Myers bisect.  This tends to keep
+structural lines such as braces from being matched across unrelated blocks.
+*/
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::Instant;
+
+use super::dmp::{Dmp, Operation};
+use super::tokens::DiffOp;
+
+fn patience_unique_pairs<T: Eq + Hash>(text1: &[T], text2: &[T]) -> Vec<(usize, usize)> {
+    // (count in text1, count in text2, index in text2) of every token.
+    let mut counts: HashMap<&T, (usize, usize, usize)> = HashMap::new();
+    for token in text1 {
+        counts.entry(token).or_insert((0, 0, 0)).0 += 1;
+    }
+    for (j, token) in text2.iter().enumerate() {
+        if let Some(count) = counts.get_mut(token) {
+            count.1 += 1;
+            count.2 = j;
+        }
+    }
+    text1.iter().enumerate().filter_map(|(i, token)| {
+        match counts[token] {
+            (1, 1, j) => Some((i, j)),
+            _ => None
+        }
+    }).collect()
+}
+
+fn patience_longest_increasing(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // Patience sorting: each pile keeps the index of its top pair, and every
+    // pair remembers the top of the pile to its left when it was placed.
+    let mut piles: Vec<usize> = vec![];
+    let mut back: Vec<Option<usize>> = vec![None; pairs.len()];
+    for (k, pair) in pairs.iter().enumerate() {
+        let pile = piles.partition_point(|top| pairs[*top].1 < pair.1);
+        if pile > 0 {
+            back[k] = Some(piles[pile - 1]);
+        }
+        if pile == piles.len() {
+            piles.push(k);
+        }
+        else {
+            piles[pile] = k;
+        }
+    }
+    let mut anchors: Vec<(usize, usize)> = vec![];
+    let mut k = piles.last().copied();
+    while let Some(index) = k {
+        anchors.push(pairs[index]);
+        k = back[index];
+    }
+    anchors.reverse();
+    anchors
+}
+
+impl Dmp {
+    pub(crate) fn diff_patience_tokens<T: Eq + Hash + Clone>(&self, text1: &[T], text2: &[T], start_time: Instant) -> Vec<DiffOp<T>> {
+        /*
+        Find the differences between two token sequences using patience diff.
+        Assumes that they do not have any common prefix or suffix.
+
+        Args:
+            text1: Old tokens to be diffed.
+            text2: New tokens to be diffed.
+            start_time: Time at which the diff started, for the bisect fallback.
+
+        Returns:
+            Vector of token diffs as changes.
+        */
+        let anchors = patience_longest_increasing(&patience_unique_pairs(text1, text2));
+        if anchors.is_empty() {
+            // Nothing unique to anchor on.
+            return self.diff_bisect_tokens(text1, text2, start_time);
+        }
+        let mut diffs: Vec<DiffOp<T>> = vec![];
+        let mut x = 0;
+        let mut y = 0;
+        for (i, j) in anchors {
+            // Recurse into the gap before the anchor.
+            diffs.extend(self.diff_tokens_internal(&text1[x..i], &text2[y..j], start_time));
+            diffs.push(DiffOp::new(Operation::Equal, vec![text1[i].clone()]));
+            x = i + 1;
+            y = j + 1;
+        }
+        diffs.extend(self.diff_tokens_internal(&text1[x..], &text2[y..], start_time));
+        diffs
+    }
+}
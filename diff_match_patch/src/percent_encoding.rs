@@ -1,16 +1,24 @@
 use std::fmt;
 
- #[derive(Debug, Clone)]
- pub struct DecodeError;
+ #[derive(Debug, Clone, PartialEq)]
+ pub enum DecodeError {
+     // a '%' not followed by two hex digits
+     MalformedEscape,
+     // escapes that don't form a valid UTF-8 sequence
+     MalformedString
+ }
  impl fmt::Display for DecodeError {
      fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-         write!(f, "Malformed string")
+         match self {
+             DecodeError::MalformedEscape => write!(f, "URI malformed"),
+             DecodeError::MalformedString => write!(f, "Malformed string")
+         }
      }
  }
  impl std::error::Error for DecodeError {}
 
 
- pub fn percent_decode_u16(input: &[u8]) -> Result<Vec<u16>, Box<dyn std::error::Error>> {
+ pub fn percent_decode_u16(input: &[u8]) -> Result<Vec<u16>, DecodeError> {
      let mut input_iter = input.iter();
      let mut result: Vec<u16> = Vec::new();
 
@@ -20,16 +28,16 @@ use std::fmt;
              continue;
          }
 
-         let byte1 = next_percent_encoded_byte(&mut input_iter, true);
+         let byte1 = next_percent_encoded_byte(&mut input_iter, true)?;
          if (byte1 & 0x80) == 0 {
              result.push(byte1);
              continue;
          }
 
-         let mut byte2 = next_percent_encoded_byte(&mut input_iter, false);
+         let mut byte2 = next_percent_encoded_byte(&mut input_iter, false)?;
          // continuation bytes have bitmask 10xx xxxx
          if (byte2 & 0xC0) != 0x80 {
-             return Err(DecodeError.into());
+             return Err(DecodeError::MalformedString);
          }
 
          // continuation bytes thus only contribute six bits each
@@ -45,9 +53,9 @@ use std::fmt;
              continue;
          }
 
-         let mut byte3 = next_percent_encoded_byte(&mut input_iter, false);
+         let mut byte3 = next_percent_encoded_byte(&mut input_iter, false)?;
          if (byte3 & 0xC0) != 0x80 {
-             return Err(DecodeError.into());
+             return Err(DecodeError::MalformedString);
          }
 
          byte3 &= 0x3F;
@@ -62,9 +70,9 @@ use std::fmt;
              continue;
          }
 
-         let mut byte4 = next_percent_encoded_byte(&mut input_iter, false);
+         let mut byte4 = next_percent_encoded_byte(&mut input_iter, false)?;
          if (byte4 & 0xC0) != 0x80 {
-             return Err(DecodeError.into());
+             return Err(DecodeError::MalformedString);
          }
 
          byte4 &= 0x3F;
@@ -86,21 +94,21 @@ use std::fmt;
              }
          }
 
-         return Err(DecodeError.into());
+         return Err(DecodeError::MalformedString);
      }
 
      Ok(result)
  }
 
- fn next_percent_encoded_byte(iter: &mut std::slice::Iter<u8>, skip_percent: bool) -> u16 {
+ fn next_percent_encoded_byte(iter: &mut std::slice::Iter<u8>, skip_percent: bool) -> Result<u16, DecodeError> {
      if !skip_percent && iter.next() != Some(&b'%') {
-         panic!("URI malformed");
+         return Err(DecodeError::MalformedEscape);
      }
 
-     let h = iter.next().and_then(|&b| (b as char).to_digit(16)).unwrap();
-     let l = iter.next().and_then(|&b| (b as char).to_digit(16)).unwrap();
+     let h = iter.next().and_then(|&b| (b as char).to_digit(16)).ok_or(DecodeError::MalformedEscape)?;
+     let l = iter.next().and_then(|&b| (b as char).to_digit(16)).ok_or(DecodeError::MalformedEscape)?;
 
-     (h as u8 * 0x10 + l as u8) as u16
+     Ok((h as u8 * 0x10 + l as u8) as u16)
  }
 
  #[cfg(test)]
@@ -124,5 +132,12 @@ use std::fmt;
          let result = percent_decode_u16("123%ED%B5%B1".as_bytes());
          assert_eq!(result.unwrap(), vec![49, 50, 51, 56689]);
      }
+
+     #[test]
+     fn test_decode_malformed_escape() {
+         assert_eq!(percent_decode_u16("%E".as_bytes()), Err(DecodeError::MalformedEscape));
+         assert_eq!(percent_decode_u16("%C3A9".as_bytes()), Err(DecodeError::MalformedEscape));
+         assert_eq!(percent_decode_u16("%C3%41".as_bytes()), Err(DecodeError::MalformedString));
+     }
  }
  
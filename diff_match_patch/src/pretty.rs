@@ -0,0 +1,188 @@
+/*Renderers for a list of diffs.
+
+Every renderer walks the diffs in order and writes each one through a
+DiffFormatter, so new output styles only need to say how a single
+insertion, deletion or equality looks.
+*/
+
+use super::dmp::{Diff, Operation};
+
+pub trait DiffFormatter {
+    // Append the rendering of a single diff to out.
+    fn write_diff(&self, out: &mut String, operation: Operation, text: &str);
+
+    fn format(&self, diffs: &[Diff]) -> String {
+        /*
+        Render a whole diff list.
+
+        Args:
+            diffs: Vector of diff object.
+
+        Returns:
+            Rendered text.
+        */
+        let mut out = String::new();
+        for diff in diffs {
+            self.write_diff(&mut out, diff.operation, &diff.text);
+        }
+        out
+    }
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    // escape the characters that are special in HTML text and attributes.
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped += "&amp;",
+            '<' => escaped += "&lt;",
+            '>' => escaped += "&gt;",
+            '"' => escaped += "&quot;",
+            _ => escaped.push(ch)
+        }
+    }
+    escaped
+}
+
+pub struct HtmlFormatter {
+    // Class names for <ins>, <del> and <span>.  None uses the inline styles of
+    // the reference diff_prettyHtml.
+    pub insert_class: Option<String>,
+    pub delete_class: Option<String>,
+    pub equal_class: Option<String>,
+    // Show line breaks as a pilcrow followed by <br>.
+    pub show_paragraphs: bool
+}
+
+impl HtmlFormatter {
+    pub fn new() -> Self {
+        // it will give the formatter used by diff_pretty_html.
+        HtmlFormatter { insert_class: None, delete_class: None, equal_class: None, show_paragraphs: true }
+    }
+
+    pub fn with_classes(insert_class: &str, delete_class: &str, equal_class: &str) -> Self {
+        // it will give a formatter tagging each diff with a css class instead of a style.
+        HtmlFormatter {
+            insert_class: Some(insert_class.to_string()),
+            delete_class: Some(delete_class.to_string()),
+            equal_class: Some(equal_class.to_string()),
+            show_paragraphs: true
+        }
+    }
+}
+
+impl Default for HtmlFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DiffFormatter for HtmlFormatter {
+    fn write_diff(&self, out: &mut String, operation: Operation, text: &str) {
+        let mut text = escape_html(text);
+        if self.show_paragraphs {
+            text = text.replace('\n', "&para;<br>");
+        }
+        let (tag, class, style) = match operation {
+            Operation::Insert => ("ins", &self.insert_class, " style=\"background:#e6ffe6;\""),
+            Operation::Delete => ("del", &self.delete_class, " style=\"background:#ffe6e6;\""),
+            Operation::Equal => ("span", &self.equal_class, "")
+        };
+        let attributes = match class {
+            Some(class) => format!(" class=\"{}\"", escape_html(class)),
+            None => style.to_string()
+        };
+        *out += format!("<{tag}{attributes}>{text}</{tag}>").as_str();
+    }
+}
+
+pub struct AnsiFormatter {
+    // SGR escape sequences wrapped around insertions and deletions.
+    pub insert_style: String,
+    pub delete_style: String,
+    pub reset: String
+}
+
+impl AnsiFormatter {
+    pub fn new() -> Self {
+        // it will give a formatter with green insertions and red deletions.
+        AnsiFormatter {
+            insert_style: "\x1b[32m".to_string(),
+            delete_style: "\x1b[31m".to_string(),
+            reset: "\x1b[0m".to_string()
+        }
+    }
+}
+
+impl Default for AnsiFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DiffFormatter for AnsiFormatter {
+    fn write_diff(&self, out: &mut String, operation: Operation, text: &str) {
+        let style = match operation {
+            Operation::Insert => &self.insert_style,
+            Operation::Delete => &self.delete_style,
+            Operation::Equal => {
+                *out += text;
+                return;
+            }
+        };
+        // Close the style before each line break so colours don't bleed into
+        // the next line of the terminal.
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                out.push('\n');
+            }
+            if !line.is_empty() {
+                *out += style.as_str();
+                *out += line;
+                *out += self.reset.as_str();
+            }
+        }
+    }
+}
+
+pub struct WordDiffFormatter {
+    // Markers around deletions and insertions, like git diff --word-diff=plain.
+    pub delete_start: String,
+    pub delete_end: String,
+    pub insert_start: String,
+    pub insert_end: String
+}
+
+impl WordDiffFormatter {
+    pub fn new() -> Self {
+        // it will give the [-old-]{+new+} markup.
+        WordDiffFormatter {
+            delete_start: "[-".to_string(),
+            delete_end: "-]".to_string(),
+            insert_start: "{+".to_string(),
+            insert_end: "+}".to_string()
+        }
+    }
+}
+
+impl Default for WordDiffFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DiffFormatter for WordDiffFormatter {
+    fn write_diff(&self, out: &mut String, operation: Operation, text: &str) {
+        let (start, end) = match operation {
+            Operation::Insert => (&self.insert_start, &self.insert_end),
+            Operation::Delete => (&self.delete_start, &self.delete_end),
+            Operation::Equal => {
+                *out += text;
+                return;
+            }
+        };
+        *out += start.as_str();
+        *out += text;
+        *out += end.as_str();
+    }
+}
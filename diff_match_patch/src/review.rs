@@ -0,0 +1,202 @@
+/*Two-level diffs, for code review.
+
+Texts are diffed line by line first, as diff_linemode does, and grouped
+into hunks like a unified diff.  Within every change, deleted and inserted
+lines are then paired by similarity, keeping their order, and every pair is
+diffed again inside the line, exactly, the way diff_granularity says, char
+diffs going through diff_cleanup_semantic.
+Similarity is one minus the Levenshtein distance of the two lines over the
+length of the longer one, pairs less alike than review_threshold stay a
+plain deletion and insertion.  Line numbers are 0-based.
+*/
+
+use std::collections::HashMap;
+
+use super::dmp::{Diff, DiffGranularity, Dmp, Operation};
+use super::moves::similarity_bound;
+use super::unified::{diff_change_lines, diff_lines_from_chars, hunk_ranges};
+
+// Largest change, deleted lines times inserted lines, whose lines are all
+// compared with each other.  Past it only the lines facing each other are.
+const REVIEW_PAIR_LIMIT: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReviewLine {
+    // line found in both texts.
+    Context { line1: usize, line2: usize, text: String },
+    // line of text1 only.
+    Deleted { line1: usize, text: String },
+    // line of text2 only.
+    Inserted { line2: usize, text: String },
+    // line of text1 changed into a line of text2, diffs turn one into the other.
+    Modified { line1: usize, line2: usize, similarity: f32, diffs: Vec<Diff> }
+}
+
+impl ReviewLine {
+    pub fn line1(&self) -> Option<usize> {
+        match self {
+            ReviewLine::Context { line1, .. } | ReviewLine::Deleted { line1, .. } | ReviewLine::Modified { line1, .. } => Some(*line1),
+            ReviewLine::Inserted { .. } => None
+        }
+    }
+
+    pub fn line2(&self) -> Option<usize> {
+        match self {
+            ReviewLine::Context { line2, .. } | ReviewLine::Inserted { line2, .. } | ReviewLine::Modified { line2, .. } => Some(*line2),
+            ReviewLine::Deleted { .. } => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReviewHunk {
+    // first line and number of lines of the hunk in text1 and text2.
+    pub start1: usize,
+    pub start2: usize,
+    pub length1: usize,
+    pub length2: usize,
+    pub lines: Vec<ReviewLine>
+}
+
+impl Dmp {
+    pub fn diff_review(&self, text1: &str, text2: &str) -> Vec<ReviewHunk> {
+        /*
+        Diff two texts line by line, then inside every changed line.
+
+        Args:
+            text1: Old text to be diffed.
+            text2: New text to be diffed.
+
+        Returns:
+            Vector of ReviewHunk objects, empty if the texts are equal.
+        */
+        let chars1: Vec<char> = text1.chars().collect();
+        let chars2: Vec<char> = text2.chars().collect();
+        let mut diffs = self.diff_linemode(&chars1, &chars2);
+        self.diff_cleanup_semantic(&mut diffs);
+        self.diff_review_hunks(&diffs)
+    }
+
+    pub fn diff_review_hunks(&self, diffs: &[Diff]) -> Vec<ReviewHunk> {
+        /*
+        Group a diff into hunks with unified_context lines of context, and
+        pair the deleted and inserted lines of every change.
+
+        Args:
+            diffs: Vector of diff object.
+
+        Returns:
+            Vector of ReviewHunk objects, empty if the texts are equal.
+        */
+        let line_diffs = diff_lines_from_chars(diffs);
+        let mut lines: Vec<ReviewLine> = vec![];
+        let mut line1 = 0;
+        let mut line2 = 0;
+        let mut k = 0;
+        while k < line_diffs.len() {
+            if line_diffs[k].operation == Operation::Equal {
+                lines.push(ReviewLine::Context { line1, line2, text: line_diffs[k].text.clone() });
+                line1 += 1;
+                line2 += 1;
+                k += 1;
+                continue;
+            }
+            let (deleted, inserted) = diff_change_lines(&line_diffs, &mut k);
+            // Unpaired lines go before the pair after them, deletions first.
+            let mut i = 0;
+            let mut j = 0;
+            for (pair1, pair2, similarity, diffs) in self.review_pairs(&deleted, &inserted).into_iter().chain(std::iter::once((deleted.len(), inserted.len(), 0.0, vec![]))) {
+                for text in &deleted[i..pair1] {
+                    lines.push(ReviewLine::Deleted { line1, text: text.to_string() });
+                    line1 += 1;
+                }
+                for text in &inserted[j..pair2] {
+                    lines.push(ReviewLine::Inserted { line2, text: text.to_string() });
+                    line2 += 1;
+                }
+                if pair1 < deleted.len() {
+                    lines.push(ReviewLine::Modified { line1, line2, similarity, diffs });
+                    line1 += 1;
+                    line2 += 1;
+                }
+                i = pair1 + 1;
+                j = pair2 + 1;
+            }
+        }
+
+        let changed: Vec<bool> = lines.iter().map(|line| !matches!(line, ReviewLine::Context { .. })).collect();
+        let count = |lines: &[ReviewLine], side: fn(&ReviewLine) -> Option<usize>| lines.iter().filter(|line| side(line).is_some()).count();
+        hunk_ranges(&changed, self.unified_context.max(0) as usize).into_iter().map(|range| ReviewHunk {
+            start1: count(&lines[..range.start], ReviewLine::line1),
+            start2: count(&lines[..range.start], ReviewLine::line2),
+            length1: count(&lines[range.clone()], ReviewLine::line1),
+            length2: count(&lines[range.clone()], ReviewLine::line2),
+            lines: lines[range].to_vec()
+        }).collect()
+    }
+
+    fn review_pairs(&self, deleted: &[&str], inserted: &[&str]) -> Vec<(usize, usize, f32, Vec<Diff>)> {
+        /*
+        Pair deleted and inserted lines, in order, for the highest total
+        similarity.
+
+        Args:
+            deleted: Lines deleted by a change.
+            inserted: Lines inserted by it.
+
+        Returns:
+            Vector of (deleted index, inserted index, similarity, diffs), in order.
+        */
+        let n = deleted.len();
+        let m = inserted.len();
+        let lengths1: Vec<usize> = deleted.iter().map(|line| line.chars().count()).collect();
+        let lengths2: Vec<usize> = inserted.iter().map(|line| line.chars().count()).collect();
+        // Similarity and diffs of every pair alike enough.
+        let mut similar: HashMap<(usize, usize), (f32, Vec<Diff>)> = HashMap::new();
+        for i in 0..n {
+            for j in 0..m {
+                if n * m > REVIEW_PAIR_LIMIT && i != j {
+                    continue;
+                }
+                if similarity_bound(lengths1[i], lengths2[j]) < self.review_threshold {
+                    continue;
+                }
+                let mut diffs = self.diff_exact(deleted[i], inserted[j], false);
+                let similarity = 1.0 - self.diff_levenshtein(&diffs) as f32 / lengths1[i].max(lengths2[j]) as f32;
+                if similarity >= self.review_threshold {
+                    // Words and lines are already whole, chars are made readable.
+                    if self.diff_granularity == DiffGranularity::Char {
+                        self.diff_cleanup_semantic(&mut diffs);
+                    }
+                    similar.insert((i, j), (similarity, diffs));
+                }
+            }
+        }
+
+        // best[i][j] is the highest total similarity of deleted[i..] and inserted[j..].
+        let mut best = vec![vec![0.0f32; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                let paired = similar.get(&(i, j)).map_or(f32::MIN, |(similarity, _)| similarity + best[i + 1][j + 1]);
+                best[i][j] = paired.max(best[i + 1][j]).max(best[i][j + 1]);
+            }
+        }
+        let mut pairs = vec![];
+        let (mut i, mut j) = (0, 0);
+        while i < n && j < m {
+            if best[i][j] == best[i + 1][j] {
+                i += 1;
+            }
+            else if best[i][j] == best[i][j + 1] {
+                j += 1;
+            }
+            else {
+                let (similarity, diffs) = similar.remove(&(i, j)).unwrap();
+                pairs.push((i, j, similarity, diffs));
+                i += 1;
+                j += 1;
+            }
+        }
+        pairs
+    }
+}
@@ -0,0 +1,222 @@
+/*Side-by-side diffs.
+
+A line diff is laid out as rows of two columns, text1 on the left and text2
+on the right.  Equal lines share a row; within a change the deleted and
+inserted lines share rows in order, and the longer side gets rows of its
+own.  Lines are kept without their line break and numbered from 0, the
+renderers show numbers from 1.
+*/
+
+use super::dmp::{Diff, Dmp, Operation};
+use super::pretty::escape_html;
+use super::unified::{diff_change_lines, diff_lines_from_chars};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowKind {
+    // same line on both sides.
+    Equal,
+    // left line only.
+    Delete,
+    // right line only.
+    Insert,
+    // left line replaced by the right one.
+    Change
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SideLine {
+    // line number within its text, and the line without its line break.
+    pub number: usize,
+    pub text: String
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SideBySideRow {
+    pub left: Option<SideLine>,
+    pub right: Option<SideLine>,
+    pub kind: RowKind
+}
+
+fn side_line(number: usize, text: &str) -> SideLine {
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    SideLine { number, text: text.strip_suffix('\r').unwrap_or(text).to_string() }
+}
+
+pub trait RowFormatter {
+    // Append the rendering of a single row to out.
+    fn write_row(&self, out: &mut String, row: &SideBySideRow);
+
+    // Append what goes before and after the rows.
+    fn write_start(&self, _out: &mut String) {}
+    fn write_end(&self, _out: &mut String) {}
+
+    fn format(&self, rows: &[SideBySideRow]) -> String {
+        /*
+        Render a whole list of rows.
+
+        Args:
+            rows: Vector of SideBySideRow.
+
+        Returns:
+            Rendered text.
+        */
+        let mut out = String::new();
+        self.write_start(&mut out);
+        for row in rows {
+            self.write_row(&mut out, row);
+        }
+        self.write_end(&mut out);
+        out
+    }
+}
+
+pub struct TerminalRowFormatter {
+    // Chars of text per column, longer lines are cut.
+    pub width: usize,
+    // Columns for the line numbers, 0 leaves them out.
+    pub number_width: usize,
+    // Columns a tab stops at a multiple of.
+    pub tab_width: usize
+}
+
+impl TerminalRowFormatter {
+    pub fn new(width: usize) -> Self {
+        // it will give a layout like diff -y, marking rows with |, < and >.
+        TerminalRowFormatter { width, number_width: 4, tab_width: 8 }
+    }
+
+    fn cell(&self, line: &Option<SideLine>) -> (String, String) {
+        // the number and the text of one side, the text fitted to width.
+        let line = match line {
+            Some(line) => line,
+            None => return (" ".repeat(self.number_width), String::new())
+        };
+        let mut text = String::new();
+        let mut column = 0;
+        for ch in line.text.chars() {
+            let stop = if ch == '\t' { (column / self.tab_width.max(1) + 1) * self.tab_width.max(1) } else { column + 1 };
+            if stop > self.width {
+                break;
+            }
+            if ch == '\t' {
+                text += " ".repeat(stop - column).as_str();
+            }
+            else {
+                text.push(ch);
+            }
+            column = stop;
+        }
+        let number = if self.number_width > 0 { format!("{:>width$}", line.number + 1, width = self.number_width) } else { String::new() };
+        (number, text)
+    }
+}
+
+impl RowFormatter for TerminalRowFormatter {
+    fn write_row(&self, out: &mut String, row: &SideBySideRow) {
+        let marker = match row.kind {
+            RowKind::Equal => ' ',
+            RowKind::Delete => '<',
+            RowKind::Insert => '>',
+            RowKind::Change => '|'
+        };
+        let (number1, text1) = self.cell(&row.left);
+        let (number2, text2) = self.cell(&row.right);
+        let gutter = if self.number_width > 0 { " " } else { "" };
+        let line = format!("{number1}{gutter}{:<width$} {marker} {number2}{gutter}{text2}", text1, width = self.width);
+        *out += line.trim_end();
+        out.push('\n');
+    }
+}
+
+pub struct HtmlRowFormatter {
+    // Class of the <table>; rows are tagged equal, delete, insert or change.
+    pub table_class: String
+}
+
+impl HtmlRowFormatter {
+    pub fn new() -> Self {
+        // it will give a four column table: number and text of either side.
+        HtmlRowFormatter { table_class: "diff".to_string() }
+    }
+
+    fn write_cells(&self, out: &mut String, line: &Option<SideLine>, side: &str) {
+        match line {
+            Some(line) => *out += format!("<td class=\"num\">{}</td><td class=\"{side}\">{}</td>", line.number + 1, escape_html(&line.text)).as_str(),
+            None => *out += format!("<td class=\"num\"></td><td class=\"{side}\"></td>").as_str()
+        }
+    }
+}
+
+impl Default for HtmlRowFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RowFormatter for HtmlRowFormatter {
+    fn write_start(&self, out: &mut String) {
+        *out += format!("<table class=\"{}\">\n", escape_html(&self.table_class)).as_str();
+    }
+
+    fn write_end(&self, out: &mut String) {
+        *out += "</table>\n";
+    }
+
+    fn write_row(&self, out: &mut String, row: &SideBySideRow) {
+        let class = match row.kind {
+            RowKind::Equal => "equal",
+            RowKind::Delete => "delete",
+            RowKind::Insert => "insert",
+            RowKind::Change => "change"
+        };
+        *out += format!("<tr class=\"{class}\">").as_str();
+        self.write_cells(out, &row.left, "left");
+        self.write_cells(out, &row.right, "right");
+        *out += "</tr>\n";
+    }
+}
+
+impl Dmp {
+    pub fn diff_side_by_side(&self, diffs: &[Diff]) -> Vec<SideBySideRow> {
+        /*
+        Align the lines of a diff into rows of two columns.  Works on the
+        output of diff_linemode as well as on a char diff, a line counting as
+        equal only when an equality covers it whole.
+
+        Args:
+            diffs: Vector of diff object.
+
+        Returns:
+            Vector of SideBySideRow, from the first line to the last.
+        */
+        let line_diffs = diff_lines_from_chars(diffs);
+        let mut rows: Vec<SideBySideRow> = vec![];
+        let mut line1 = 0;
+        let mut line2 = 0;
+        let mut k = 0;
+        while k < line_diffs.len() {
+            if line_diffs[k].operation == Operation::Equal {
+                let text = &line_diffs[k].text;
+                rows.push(SideBySideRow { left: Some(side_line(line1, text)), right: Some(side_line(line2, text)), kind: RowKind::Equal });
+                line1 += 1;
+                line2 += 1;
+                k += 1;
+                continue;
+            }
+            let (deleted, inserted) = diff_change_lines(&line_diffs, &mut k);
+            for i in 0..deleted.len().max(inserted.len()) {
+                let left = deleted.get(i).map(|text| side_line(line1 + i, text));
+                let right = inserted.get(i).map(|text| side_line(line2 + i, text));
+                let kind = match (&left, &right) {
+                    (Some(_), Some(_)) => RowKind::Change,
+                    (Some(_), None) => RowKind::Delete,
+                    _ => RowKind::Insert
+                };
+                rows.push(SideBySideRow { left, right, kind });
+            }
+            line1 += deleted.len();
+            line2 += inserted.len();
+        }
+        rows
+    }
+}
@@ -0,0 +1,267 @@
+/*Differential synchronization.
+
+Neil Fraser's guaranteed delivery variant.  Each peer keeps, per peer it
+talks to, a shadow of the text both sides last agreed on and a backup of it.
+Local changes are diffed against the shadow and stacked until the peer
+acknowledges them, remote changes are applied exactly to the shadow and
+fuzzily to the text.  Version numbers find duplicate and lost messages, and
+a checksum of the shadow catches peers that drift apart anyway.
+
+Peers take turns: each message received is answered by one message sent.
+Nothing here touches the network, messages go through whatever transport
+the caller likes as SyncMessage values or as text.
+*/
+
+use std::fmt;
+
+use super::dmp::{Dmp, delta_escape, percent_decode_checked};
+use super::errors::DmpError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncEdit {
+    // diff_todelta of a change to the shadow at version.
+    Delta { version: u32, delta: String },
+    // whole text, replacing the shadow and the text of the peer.
+    Raw { version: u32, text: String }
+}
+
+impl SyncEdit {
+    pub fn version(&self) -> u32 {
+        match self {
+            SyncEdit::Delta { version, .. } | SyncEdit::Raw { version, .. } => *version
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncMessage {
+    // the receiver's version the sender has seen edits up to.
+    pub ack: u32,
+    // every edit the receiver has not acknowledged yet.
+    pub edits: Vec<SyncEdit>,
+    // sync_checksum of the sender's shadow, after the edits.
+    pub checksum: u32
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncState {
+    // text both peers last agreed on.
+    pub shadow: String,
+    // versions of the edits sent and received so far.
+    pub local_version: u32,
+    pub remote_version: u32,
+    // shadow as of the last message received, for when our answer got lost.
+    pub backup: String,
+    pub backup_version: u32,
+    // edits sent but not acknowledged.
+    pub edits: Vec<SyncEdit>
+}
+
+impl SyncState {
+    pub fn new(text: &str) -> SyncState {
+        SyncState {
+            shadow: text.to_string(),
+            local_version: 0,
+            remote_version: 0,
+            backup: text.to_string(),
+            backup_version: 0,
+            edits: vec![]
+        }
+    }
+}
+
+impl fmt::Display for SyncMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        /*
+        Emulate the text format of MobWrite: a header with the ack and the
+        checksum, then one line per edit.  Raw text is escaped like the
+        insertions of a delta.
+        */
+        writeln!(f, "v:{}:{:08x}", self.ack, self.checksum)?;
+        for edit in &self.edits {
+            match edit {
+                SyncEdit::Delta { version, delta } => writeln!(f, "d:{version}:{delta}")?,
+                SyncEdit::Raw { version, text } => {
+                    writeln!(f, "r:{version}:{}", delta_escape(text))?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn sync_checksum(text: &str) -> u32 {
+    // 32-bit FNV-1a of the UTF-8 bytes of text.
+    text.bytes().fold(0x811c9dc5, |hash, byte| (hash ^ byte as u32).wrapping_mul(0x01000193))
+}
+
+impl Dmp {
+    pub fn sync_make_message(&self, state: &mut SyncState, text: &str) -> SyncMessage {
+        /*
+        Stack the changes made to text since the last message, and build the
+        message carrying every edit the peer has not acknowledged.
+
+        Args:
+            state: Shadow kept for the peer.
+            text: Current local text.
+
+        Returns:
+            SyncMessage for the peer.
+        */
+        if state.shadow != text {
+            let mut diffs = self.diff_exact(&state.shadow, text, true);
+            self.diff_cleanup_efficiency(&mut diffs);
+            state.edits.push(SyncEdit::Delta { version: state.local_version, delta: self.diff_todelta(&diffs) });
+            state.local_version += 1;
+            state.shadow = text.to_string();
+        }
+        SyncMessage { ack: state.remote_version, edits: state.edits.clone(), checksum: sync_checksum(&state.shadow) }
+    }
+
+    pub fn sync_make_raw_message(&self, state: &mut SyncState, text: &str) -> SyncMessage {
+        /*
+        Build a message overwriting the peer with text, to recover from a
+        version or checksum mismatch.  Unacknowledged edits are dropped.
+
+        Args:
+            state: Shadow kept for the peer.
+            text: Current local text.
+
+        Returns:
+            SyncMessage for the peer.
+        */
+        state.edits = vec![SyncEdit::Raw { version: state.local_version, text: text.to_string() }];
+        state.local_version += 1;
+        state.shadow = text.to_string();
+        SyncMessage { ack: state.remote_version, edits: state.edits.clone(), checksum: sync_checksum(&state.shadow) }
+    }
+
+    pub fn sync_receive_message(&self, state: &mut SyncState, message: &SyncMessage, text: &mut String) -> Result<(), DmpError> {
+        /*
+        Apply a message from the peer to the shadow and to the local text.
+
+        Args:
+            state: Shadow kept for the peer.
+            message: SyncMessage from the peer.
+            text: Local text, patched in place.
+
+        Errors:
+            DmpError: VersionMismatch if the message does not follow on from
+            the shadow, ChecksumMismatch if the shadows differ afterwards, or a
+            delta parse error.  The peers are out of sync and one of them
+            should send a raw message.
+        */
+        // Forget the edits the peer has received.
+        state.edits.retain(|edit| edit.version() >= message.ack);
+        if message.ack != state.local_version {
+            if message.edits.iter().any(|edit| matches!(edit, SyncEdit::Raw { .. })) {
+                // The peer resets us anyway.
+                state.local_version = message.ack;
+                state.edits.clear();
+            }
+            else if message.ack == state.backup_version {
+                // Our last message was lost, the peer still has the backup.
+                state.shadow = state.backup.clone();
+                state.local_version = state.backup_version;
+                state.edits.clear();
+            }
+            else {
+                return Err(DmpError::VersionMismatch { expected: state.local_version, actual: message.ack });
+            }
+        }
+
+        for edit in &message.edits {
+            if edit.version() < state.remote_version {
+                // Received before, the peer did not get our ack.
+                continue;
+            }
+            match edit {
+                SyncEdit::Delta { version, .. } if *version != state.remote_version => {
+                    return Err(DmpError::VersionMismatch { expected: state.remote_version, actual: *version });
+                },
+                SyncEdit::Delta { delta, .. } => {
+                    let diffs = self.diff_from_delta(&state.shadow, delta)?;
+                    let patches = self.patch_make4(&state.shadow, &diffs);
+                    *text = self.patch_apply(&patches, text).0;
+                    state.shadow = self.diff_text2(&diffs);
+                },
+                SyncEdit::Raw { text: raw, .. } => {
+                    state.shadow = raw.clone();
+                    *text = raw.clone();
+                }
+            }
+            state.remote_version = edit.version() + 1;
+        }
+
+        let checksum = sync_checksum(&state.shadow);
+        if checksum != message.checksum {
+            return Err(DmpError::ChecksumMismatch { expected: message.checksum, actual: checksum });
+        }
+        state.backup = state.shadow.clone();
+        state.backup_version = state.local_version;
+        Ok(())
+    }
+
+    pub fn sync_message_to_text(&self, message: &SyncMessage) -> String {
+        /*
+        Take a sync message and return a textual representation.
+
+        Args:
+            message: SyncMessage object.
+
+        Returns:
+            Text representation of message.
+        */
+        message.to_string()
+    }
+
+    pub fn sync_message_from_text(&self, textline: &str) -> Result<SyncMessage, DmpError> {
+        /*
+        Parse a textual representation of a sync message.
+
+        Args:
+            textline: Text representation of a sync message.
+
+        Returns:
+            SyncMessage object.
+
+        Errors:
+            DmpError: If invalid input, with the offending line and column.
+        */
+        let mut lines = textline.lines().enumerate();
+        let header = lines.next().map(|(_, line)| line).unwrap_or("");
+        let fields: Vec<&str> = header.splitn(3, ':').collect();
+        let (ack, checksum) = match fields.as_slice() {
+            ["v", ack, checksum] => (ack.parse::<u32>().ok(), u32::from_str_radix(checksum, 16).ok()),
+            _ => (None, None)
+        };
+        let (ack, checksum) = match (ack, checksum) {
+            (Some(ack), Some(checksum)) => (ack, checksum),
+            _ => return Err(DmpError::BadHeader { line: 1, column: 1 })
+        };
+
+        let mut edits: Vec<SyncEdit> = vec![];
+        for (i, line) in lines {
+            if line.is_empty() {
+                continue;
+            }
+            let mut fields = line.splitn(3, ':');
+            let kind = fields.next().unwrap_or("");
+            let version = fields.next().and_then(|version| version.parse::<u32>().ok());
+            let delta = fields.next();
+            let (version, delta) = match (kind, version, delta) {
+                ("d", Some(version), Some(delta)) | ("r", Some(version), Some(delta)) => (version, delta),
+                ("d", _, _) | ("r", _, _) => return Err(DmpError::BadNumber { line: i + 1, column: 3 }),
+                _ => return Err(DmpError::BadLinePrefix { line: i + 1, column: 1, prefix: line.chars().next().unwrap_or(' ') })
+            };
+            if kind == "d" {
+                edits.push(SyncEdit::Delta { version, delta: delta.to_string() });
+            }
+            else {
+                let column = line.len() - delta.len() + 1;
+                edits.push(SyncEdit::Raw { version, text: percent_decode_checked(delta, i + 1, column)? });
+            }
+        }
+        Ok(SyncMessage { ack, edits, checksum })
+    }
+}
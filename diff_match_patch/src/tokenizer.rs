@@ -0,0 +1,127 @@
+/*Tokenizers.
+
+A tokenizer cuts a text into the units diff_main compares when
+diff_granularity asks for them.  The built-in ones split chars, lines and
+words; others are registered on the Dmp under a name and picked with
+DiffGranularity::Custom.
+*/
+
+use std::ops::Range;
+use std::sync::{Arc, OnceLock};
+
+use regex::Regex;
+
+use super::dmp::{DiffGranularity, Dmp};
+
+pub trait Tokenizer: Send + Sync {
+    // char ranges of the tokens of text, in order.  Chars left out of every
+    // range belong to the token before them.
+    fn tokenize(&self, text: &[char]) -> Vec<Range<usize>>;
+}
+
+pub struct CharTokenizer;
+
+impl Tokenizer for CharTokenizer {
+    fn tokenize(&self, text: &[char]) -> Vec<Range<usize>> {
+        (0..text.len()).map(|i| i..i + 1).collect()
+    }
+}
+
+pub struct LineTokenizer;
+
+impl Tokenizer for LineTokenizer {
+    fn tokenize(&self, text: &[char]) -> Vec<Range<usize>> {
+        // every line with its line break, the last one may have none.
+        let mut spans: Vec<Range<usize>> = vec![];
+        let mut line_start = 0;
+        for (i, ch) in text.iter().enumerate() {
+            if *ch == '\n' || i + 1 == text.len() {
+                spans.push(line_start..i + 1);
+                line_start = i + 1;
+            }
+        }
+        spans
+    }
+}
+
+pub struct WordTokenizer {
+    // every match is a token of its own, words run between them.
+    pub boundary: Regex
+}
+
+impl WordTokenizer {
+    pub fn new(boundary: Regex) -> WordTokenizer {
+        WordTokenizer { boundary }
+    }
+}
+
+impl Default for WordTokenizer {
+    fn default() -> Self {
+        WordTokenizer::new(default_word_boundary().clone())
+    }
+}
+
+impl Tokenizer for WordTokenizer {
+    fn tokenize(&self, text: &[char]) -> Vec<Range<usize>> {
+        WordBoundary(&self.boundary).tokenize(text)
+    }
+}
+
+pub(crate) fn default_word_boundary() -> &'static Regex {
+    // whitespace, compiled once for every Dmp.
+    static WORD_BOUNDARY: OnceLock<Regex> = OnceLock::new();
+    WORD_BOUNDARY.get_or_init(|| Regex::new(r"[\s\n\r]").unwrap())
+}
+
+// WordTokenizer borrowing the boundary of a Dmp.
+pub(crate) struct WordBoundary<'a>(pub(crate) &'a Regex);
+
+impl Tokenizer for WordBoundary<'_> {
+    fn tokenize(&self, text: &[char]) -> Vec<Range<usize>> {
+        let string: String = text.iter().collect();
+        let offsets: Vec<usize> = string.char_indices().map(|(offset, _)| offset).collect();
+        let char_offset = |offset: usize| offsets.partition_point(|&start| start < offset);
+        let mut spans: Vec<Range<usize>> = vec![];
+        let mut prev_end = 0;
+        for part in self.0.find_iter(&string) {
+            let (start, end) = (char_offset(part.start()), char_offset(part.end()));
+            if prev_end < start {
+                spans.push(prev_end..start);
+            }
+            if start < end {
+                spans.push(start..end);
+            }
+            prev_end = end;
+        }
+        if prev_end < text.len() {
+            spans.push(prev_end..text.len());
+        }
+        spans
+    }
+}
+
+impl Dmp {
+    pub fn register_tokenizer(&mut self, name: &'static str, tokenizer: impl Tokenizer + 'static) {
+        /*
+        Make a tokenizer available as DiffGranularity::Custom(name).
+
+        Args:
+            name: Name to pick the tokenizer by.
+            tokenizer: The tokenizer, replacing any registered under name.
+        */
+        self.tokenizers.insert(name, Arc::new(tokenizer));
+    }
+
+    pub(crate) fn diff_tokenizer(&self, granularity: DiffGranularity) -> Arc<dyn Tokenizer + '_> {
+        // the tokenizer behind granularity, chars for a name nothing was registered under.
+        match granularity {
+            DiffGranularity::Char => Arc::new(CharTokenizer),
+            DiffGranularity::Word => Arc::new(WordBoundary(&self.word_boundary)),
+            DiffGranularity::Line => Arc::new(LineTokenizer),
+            DiffGranularity::Custom(name) => match self.tokenizers.get(name) {
+                Some(tokenizer) => tokenizer.clone(),
+                None => Arc::new(CharTokenizer)
+            }
+        }
+    }
+}
@@ -0,0 +1,650 @@
+/*Diff engine over arbitrary token sequences.
+
+The char based API in dmp.rs is a thin layer on top of these functions, and
+line mode diffs line ids with them instead of packing lines into chars.
+*/
+
+use std::hash::Hash;
+use std::ops::Range;
+use std::time::Instant;
+
+use super::dmp::{Diff, DiffAlgorithm, Dmp, Operation};
+use super::tokenizer::Tokenizer;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffOp<T> {
+    // diff object over tokens
+    pub operation: Operation,
+    pub tokens: Vec<T>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffRange {
+    // Token ranges touched by one diff.  The range of the side a diff doesn't
+    // belong to is empty and marks where it happens in that sequence.
+    pub operation: Operation,
+    pub range1: Range<usize>,
+    pub range2: Range<usize>,
+}
+
+// Diff of a block found some other way than by the diff algorithm, if any.
+pub(crate) type Shortcut<'a, T> = dyn Fn(&[T], &[T], Instant) -> Option<Vec<DiffOp<T>>> + 'a;
+
+pub(crate) struct HalfMatch<T> {
+    // prefix and suffix of text1 and text2 around their common middle.
+    pub text1_a: Vec<T>,
+    pub text1_b: Vec<T>,
+    pub text2_a: Vec<T>,
+    pub text2_b: Vec<T>,
+    pub mid_common: Vec<T>,
+}
+
+impl<T> DiffOp<T> {
+    // A new token diff object created.
+    pub fn new(operation: Operation, tokens: Vec<T>) -> DiffOp<T> {
+        DiffOp {
+            operation,
+            tokens
+        }
+    }
+}
+
+impl From<DiffOp<char>> for Diff {
+    fn from(op: DiffOp<char>) -> Diff {
+        Diff::new(op.operation, op.tokens.into_iter().collect())
+    }
+}
+
+impl From<&Diff> for DiffOp<char> {
+    fn from(diff: &Diff) -> DiffOp<char> {
+        DiffOp::new(diff.operation, diff.text.chars().collect())
+    }
+}
+
+impl Dmp {
+    pub fn diff_tokens<T: Eq + Hash + Clone>(&self, tokens1: &[T], tokens2: &[T]) -> Vec<DiffOp<T>> {
+        /*
+        Find the differences between two token sequences.  Works like
+        diff_main without the line mode speedup.
+
+        Args:
+            tokens1: Old tokens to be diffed.
+            tokens2: New tokens to be diffed.
+
+        Returns:
+            Vector of token diffs as changes.
+        */
+        self.diff_tokens_internal(tokens1, tokens2, Instant::now())
+    }
+
+    pub fn diff_token_ranges<T: Eq + Hash + Clone>(&self, tokens1: &[T], tokens2: &[T]) -> Vec<DiffRange> {
+        /*
+        Find the differences between two token sequences as index ranges
+        instead of copied tokens.
+
+        Args:
+            tokens1: Old tokens to be diffed.
+            tokens2: New tokens to be diffed.
+
+        Returns:
+            Vector of ranges into tokens1 and tokens2.
+        */
+        let mut ranges: Vec<DiffRange> = vec![];
+        let mut index1 = 0;
+        let mut index2 = 0;
+        for op in self.diff_tokens(tokens1, tokens2) {
+            let len = op.tokens.len();
+            let len1 = if op.operation == Operation::Insert { 0 } else { len };
+            let len2 = if op.operation == Operation::Delete { 0 } else { len };
+            ranges.push(DiffRange { operation: op.operation, range1: index1..index1 + len1, range2: index2..index2 + len2 });
+            index1 += len1;
+            index2 += len2;
+        }
+        ranges
+    }
+
+    pub(crate) fn diff_token_starts(&self, text: &[char], tokenizer: &dyn Tokenizer) -> Vec<usize> {
+        // char offsets where the tokens of text start.
+        let mut starts: Vec<usize> = tokenizer.tokenize(text).into_iter().map(|span| span.start).filter(|&start| start < text.len()).collect();
+        if !text.is_empty() && starts.first() != Some(&0) {
+            // Leading chars no token claims.
+            starts.insert(0, 0);
+        }
+        starts.dedup();
+        starts
+    }
+
+    pub fn diff_tokenized(&self, text1: &str, text2: &str, tokenizer: &dyn Tokenizer) -> Vec<Diff> {
+        /*
+        Find the differences between two texts, comparing whole tokens.
+
+        Args:
+            text1: Old string to be diffed.
+            text2: New string to be diffed.
+            tokenizer: Cuts both texts into tokens.
+
+        Returns:
+            Vector of diffs as changes.
+        */
+        let chars1: Vec<char> = text1.chars().collect();
+        let chars2: Vec<char> = text2.chars().collect();
+        self.diff_tokenized_internal(&chars1, &chars2, tokenizer, Instant::now())
+    }
+
+    pub(crate) fn diff_tokenized_internal(&self, text1: &[char], text2: &[char], tokenizer: &dyn Tokenizer, start_time: Instant) -> Vec<Diff> {
+        // diff_tokenized over chars, with a deadline.
+        let split = |text: &'_ [char]| -> Vec<Vec<char>> {
+            let starts = self.diff_token_starts(text, tokenizer);
+            starts.iter().enumerate().map(|(i, &start)| text[start..starts.get(i + 1).copied().unwrap_or(text.len())].to_vec()).collect()
+        };
+        self.diff_tokens_internal(&split(text1), &split(text2), start_time).into_iter().map(|op| {
+            Diff::new(op.operation, op.tokens.concat().into_iter().collect())
+        }).collect()
+    }
+
+    pub(crate) fn diff_tokens_internal<T: Eq + Hash + Clone>(&self, text1: &[T], text2: &[T], start_time: Instant) -> Vec<DiffOp<T>> {
+        self.diff_tokens_shortcut(text1, text2, start_time, &|_, _, _| None)
+    }
+
+    pub(crate) fn diff_tokens_shortcut<T: Eq + Hash + Clone>(&self, text1: &[T], text2: &[T], start_time: Instant, shortcut: &Shortcut<T>) -> Vec<DiffOp<T>> {
+        // diff_tokens_internal, trying shortcut on every block left once the
+        // speedups are exhausted before the diff algorithm.  Char diffs use it
+        // for the checklines line mode.
+
+        // check for empty text
+        if text1.is_empty() && text2.is_empty() {
+            return vec![];
+        }
+        else if text1.is_empty() {
+            return vec![DiffOp::new(Operation::Insert, text2.to_vec())];
+        }
+        else if text2.is_empty() {
+            return vec![DiffOp::new(Operation::Delete, text1.to_vec())];
+        }
+
+        // check for equality
+        if text1 == text2 {
+            return vec![DiffOp::new(Operation::Equal, text1.to_vec())];
+        }
+
+        // Trim off common prefix (speedup).
+        let prefix_length = self.diff_common_prefix(text1, text2) as usize;
+        let commonprefix = &text1[..prefix_length];
+        let text1 = &text1[prefix_length..];
+        let text2 = &text2[prefix_length..];
+
+        // Trim off common suffix (speedup).
+        let suffix_length = self.diff_common_suffix(text1, text2) as usize;
+        let commonsuffix = &text1[(text1.len() - suffix_length)..];
+        let text1 = &text1[..(text1.len() - suffix_length)];
+        let text2 = &text2[..(text2.len() - suffix_length)];
+        let mut diffs: Vec<DiffOp<T>> = Vec::new();
+
+        // Restore the prefix
+        if !commonprefix.is_empty() {
+            diffs.push(DiffOp::new(Operation::Equal, commonprefix.to_vec()));
+        }
+
+        // Compute the diff on the middle block.
+        diffs.extend(self.diff_tokens_compute(text1, text2, start_time, shortcut));
+
+        // Restore the suffix
+        if !commonsuffix.is_empty() {
+            diffs.push(DiffOp::new(Operation::Equal, commonsuffix.to_vec()));
+        }
+        self.diff_cleanup_merge_tokens(&mut diffs);
+        diffs
+    }
+
+    fn diff_tokens_compute<T: Eq + Hash + Clone>(&self, text1: &[T], text2: &[T], start_time: Instant, shortcut: &Shortcut<T>) -> Vec<DiffOp<T>> {
+        /*
+        Find the differences between two token sequences.  Assumes that they
+        do not have any common prefix or suffix.
+        */
+        if text1.is_empty() {
+            // Just add some text (speedup).
+            return vec![DiffOp::new(Operation::Insert, text2.to_vec())];
+        }
+        else if text2.is_empty() {
+            // Just delete some text (speedup).
+            return vec![DiffOp::new(Operation::Delete, text1.to_vec())];
+        }
+        let (longtext, shorttext) = if text1.len() >= text2.len() {
+            (text1, text2)
+        }
+        else {
+            (text2, text1)
+        };
+        let i = self.kmp(longtext, shorttext, 0);
+        if i != -1 {
+            // Shorter text is inside the longer text (speedup).
+            let i = i as usize;
+            let operation = if text1.len() > text2.len() { Operation::Delete } else { Operation::Insert };
+            let mut diffs: Vec<DiffOp<T>> = vec![];
+            if i != 0 {
+                diffs.push(DiffOp::new(operation, longtext[..i].to_vec()));
+            }
+            diffs.push(DiffOp::new(Operation::Equal, shorttext.to_vec()));
+            if i + shorttext.len() != longtext.len() {
+                diffs.push(DiffOp::new(operation, longtext[(i + shorttext.len())..].to_vec()));
+            }
+            return diffs;
+        }
+        if shorttext.len() == 1 {
+            // Single token.
+            // After the previous speedup, the token can't be an equality.
+            return vec![DiffOp::new(Operation::Delete, text1.to_vec()), DiffOp::new(Operation::Insert, text2.to_vec())];
+        }
+
+        // Check to see if the problem can be split in two.
+        if let Some(hm) = self.diff_half_match_tokens(text1, text2) {
+            // Send both pairs off for separate processing.
+            let mut diffs = self.diff_tokens_shortcut(&hm.text1_a, &hm.text2_a, start_time, shortcut);
+            diffs.push(DiffOp::new(Operation::Equal, hm.mid_common));
+            diffs.extend(self.diff_tokens_shortcut(&hm.text1_b, &hm.text2_b, start_time, shortcut));
+            return diffs;
+        }
+        if let Some(diffs) = shortcut(text1, text2, start_time) {
+            return diffs;
+        }
+        self.diff_algorithm_tokens(text1, text2, start_time)
+    }
+
+    pub(crate) fn diff_algorithm_tokens<T: Eq + Hash + Clone>(&self, text1: &[T], text2: &[T], start_time: Instant) -> Vec<DiffOp<T>> {
+        // Run the configured diff algorithm once the speedups have been exhausted.
+        match self.diff_algorithm {
+            DiffAlgorithm::Myers => self.diff_bisect_tokens(text1, text2, start_time),
+            DiffAlgorithm::Patience => self.diff_patience_tokens(text1, text2, start_time),
+            DiffAlgorithm::Histogram => self.diff_histogram_tokens(text1, text2, start_time)
+        }
+    }
+
+    pub(crate) fn diff_bisect_tokens<T: Eq + Hash + Clone>(&self, char1: &[T], char2: &[T], start_time: Instant) -> Vec<DiffOp<T>> {
+        /*
+        Find the 'middle snake' of a diff, split the problem in two
+        and return the recursively constructed diff.
+        See Myers 1986 paper: An O(ND) Difference Algorithm and Its Variations.
+        */
+        let text1_length = char1.len() as i32;
+        let text2_length = char2.len() as i32;
+        let max_d: i32 = (text1_length + text2_length + 1)/2;
+        let v_offset: i32 = max_d;
+        let v_length: i32 = 2 * max_d;
+        let mut v1: Vec<i32> = vec![-1; v_length as usize];
+        let mut v2: Vec<i32> = vec![-1; v_length as usize];
+        v1[v_offset as usize + 1] = 0;
+        v2[v_offset as usize + 1] = 0;
+        let delta: i32 = text1_length - text2_length;
+        // If the total number of tokens is odd, then the front path will
+        // collide with the reverse path.
+        let front: i32 = (delta%2 != 0) as i32;
+        // Offsets for start and end of k loop.
+        // Prevents mapping of space beyond the grid.
+        let mut k1start: i32 = 0;
+        let mut k1end: i32 = 0;
+        let mut k2start: i32 = 0;
+        let mut k2end: i32 = 0;
+        for d in 0..max_d {
+            if self.diff_timeout.is_some() && start_time.elapsed().as_secs_f32() >= self.diff_timeout.unwrap() {
+                break;
+            }
+
+            let d1 = d;
+            let mut k1 = -d1 + k1start;
+            let mut x1: i32;
+            let mut k1_offset: i32;
+            let mut k2_offset;
+            let mut x2;
+            let mut y1;
+            // Walk the front path one step.
+            while k1 < d1 + 1 - k1end {
+                k1_offset = v_offset + k1;
+                if k1 == -d1 || (k1 != d1 && v1[k1_offset as usize - 1] < v1[k1_offset as usize + 1]) {
+                    x1 = v1[k1_offset as usize + 1];
+                }
+                else {
+                    x1 = v1[k1_offset as usize - 1] + 1;
+                }
+                y1 = x1 - k1;
+                while x1 < text1_length && y1 < text2_length {
+                    let i1 = if x1 < 0 {
+                        text1_length + x1
+                    }
+                    else {
+                        x1
+                    };
+                    let i2 = if y1 < 0 {
+                        text2_length + y1
+                    }
+                    else {
+                        y1
+                    };
+                    if char1[i1 as usize] != char2[i2 as usize] {
+                        break;
+                    }
+                    x1 += 1;
+                    y1 += 1;
+                }
+                v1[k1_offset as usize] = x1;
+                if x1 > text1_length {
+                    // Ran off the right of the graph.
+                    k1end += 2;
+                }
+                else if y1 > text2_length {
+                    // Ran off the bottom of the graph.
+                    k1start += 2;
+                }
+                else if front != 0 {
+                    k2_offset = v_offset + delta - k1;
+                    if k2_offset >= 0 && k2_offset < v_length && v2[k2_offset as usize] != -1 {
+                        // Mirror x2 onto top-left coordinate system.
+                        x2 = text1_length - v2[k2_offset as usize];
+                        if x1 >= x2 {
+                            // Overlap detected.
+                            return self.diff_bisect_tokens_split(char1, char2, x1, y1, start_time);
+                        }
+                    }
+                }
+                k1 += 2;
+            }
+            let mut k2 = -d1 + k2start;
+            let mut y2;
+            // Walk the reverse path one step.
+            while k2 < d1 + 1 - k2end {
+                k2_offset = v_offset + k2;
+                if k2 == -d1 || (k2 != d1 && v2[k2_offset as usize - 1] < v2[k2_offset as usize + 1]) {
+                    x2 = v2[k2_offset as usize + 1];
+                }
+                else {
+                    x2 = v2[k2_offset as usize - 1] + 1;
+                }
+                y2 = x2 - k2;
+                while x2 < text1_length && y2 < text2_length {
+                    let i1 = if text1_length - x2  > 0 {
+                        text1_length - x2 - 1
+                    }
+                    else {
+                        x2 + 1
+                    };
+                    let i2 = if text2_length - y2  > 0 {
+                        text2_length -y2 - 1
+                    }
+                    else {
+                        y2 + 1
+                    };
+                    if char1[i1 as usize] != char2[i2 as usize] {
+                        break;
+                    }
+                    x2 += 1;
+                    y2 += 1;
+                }
+                v2[k2_offset as usize] = x2;
+                if x2 > text1_length {
+                    // Ran off the left of the graph.
+                    k2end += 2;
+                }
+                else if y2 > text2_length {
+                    // Ran off the top of the graph.
+                    k2start += 2;
+                }
+                else if front == 0 {
+                    k1_offset = v_offset + delta - k2;
+                    if k1_offset >= 0 && k1_offset < v_length && v1[k1_offset as usize] != -1 {
+                        x1 = v1[k1_offset as usize];
+                        y1 = v_offset + x1 - k1_offset;
+                        // Mirror x2 onto top-left coordinate system.
+                        x2 = text1_length - x2;
+                        if x1 >= x2 {
+                            // Overlap detected.
+                            return self.diff_bisect_tokens_split(char1, char2, x1, y1, start_time);
+                        }
+                    }
+                }
+                k2 += 2;
+            }
+        }
+        // number of diffs equals number of tokens, no commonality at all.
+        vec![DiffOp::new(Operation::Delete, char1.to_vec()), DiffOp::new(Operation::Insert, char2.to_vec())]
+    }
+
+    fn diff_bisect_tokens_split<T: Eq + Hash + Clone>(&self, text1: &[T], text2: &[T], x: i32, y: i32, start_time: Instant) -> Vec<DiffOp<T>> {
+        /*
+        Given the location of the 'middle snake', split the diff in two parts
+        and recurse.
+
+        Args:
+            text1: Old tokens to be diffed.
+            text2: New tokens to be diffed.
+            x: Index of split point in text1.
+            y: Index of split point in text2.
+        */
+        let (text1a, text1b) = text1.split_at(x as usize);
+        let (text2a, text2b) = text2.split_at(y as usize);
+
+        // Compute both diffs serially.
+        let mut diffs = self.diff_tokens_internal(text1a, text2a, start_time);
+        diffs.extend(self.diff_tokens_internal(text1b, text2b, start_time));
+        diffs
+    }
+
+    pub(crate) fn diff_half_match_tokens<T: PartialEq + Clone>(&self, text1: &[T], text2: &[T]) -> Option<HalfMatch<T>> {
+        /*
+        Do the two sequences share a run which is at least half the length of
+        the longer one?
+        This speedup can produce non-minimal diffs.
+        */
+
+        // Don't risk returning a non-optimal diff if we have unlimited time.
+        self.diff_timeout?;
+
+        let (long_text, short_text) = if text1.len() > text2.len() {
+            (text1, text2)
+        }
+        else {
+            (text2, text1)
+        };
+        let len1 = short_text.len();
+        let len2 = long_text.len();
+        if len2 < 4 || len1*2 < len2 {
+            return None;
+        }
+
+        // First check if the second quarter is the seed for a half-match.
+        let hm1 = self.diff_half_matchi_tokens(long_text, short_text, len2.div_ceil(4));
+        // Check again based on the third quarter.
+        let hm2 = self.diff_half_matchi_tokens(long_text, short_text, len2.div_ceil(2));
+        let hm = match (hm1, hm2) {
+            (None, None) => return None,
+            (Some(hm1), None) => hm1,
+            (None, Some(hm2)) => hm2,
+            // Both matched.  Select the longest.
+            (Some(hm1), Some(hm2)) => if hm1.mid_common.len() > hm2.mid_common.len() { hm1 } else { hm2 }
+        };
+        if text1.len() > text2.len() {
+            return Some(hm);
+        }
+        // text2 was the long text, swap the halves back.
+        Some(HalfMatch {
+            text1_a: hm.text2_a,
+            text1_b: hm.text2_b,
+            text2_a: hm.text1_a,
+            text2_b: hm.text1_b,
+            mid_common: hm.mid_common
+        })
+    }
+
+    fn diff_half_matchi_tokens<T: PartialEq + Clone>(&self, long_text: &[T], short_text: &[T], i: usize) -> Option<HalfMatch<T>> {
+        /*
+        Does a run of shorttext exist within longtext such that the run is at
+        least half the length of longtext?
+
+        Args:
+            longtext: Longer tokens.
+            shorttext: Shorter tokens.
+            i: Start index of quarter length run within longtext.
+
+        Returns:
+            Half match with longtext as text1 and shorttext as text2, or None.
+        */
+        let seed = &long_text[i..(i + long_text.len() / 4)];
+        let mut best: Option<HalfMatch<T>> = None;
+        let mut best_length = 0;
+        let mut j: i32 = self.kmp(short_text, seed, 0);
+        while j != -1 {
+            let ju = j as usize;
+            let prefix_length = self.diff_common_prefix(&long_text[i..], &short_text[ju..]) as usize;
+            let suffix_length = self.diff_common_suffix(&long_text[..i], &short_text[..ju]) as usize;
+            if best_length < suffix_length + prefix_length {
+                best_length = suffix_length + prefix_length;
+                best = Some(HalfMatch {
+                    text1_a: long_text[..(i - suffix_length)].to_vec(),
+                    text1_b: long_text[(i + prefix_length)..].to_vec(),
+                    text2_a: short_text[..(ju - suffix_length)].to_vec(),
+                    text2_b: short_text[(ju + prefix_length)..].to_vec(),
+                    mid_common: short_text[(ju - suffix_length)..(ju + prefix_length)].to_vec()
+                });
+            }
+            j = self.kmp(short_text, seed, ju + 1);
+        }
+        if best_length * 2 >= long_text.len() {
+            return best;
+        }
+        None
+    }
+
+    pub fn diff_cleanup_merge_tokens<T: PartialEq + Clone>(&self, diffs: &mut Vec<DiffOp<T>>) {
+        /*
+        Reorder and merge like edit sections.  Merge equalities.
+        Any edit section can move as long as it doesn't cross an equality.
+
+        Args:
+            diffs: vectors of token diff object.
+      */
+        if diffs.is_empty() {
+            return;
+        }
+        diffs.push(DiffOp::new(Operation::Equal, vec![]));
+        let mut text_insert: Vec<T> = vec![];
+        let mut text_delete: Vec<T> = vec![];
+        let mut i: usize = 0;
+        let mut count_insert = 0;
+        let mut count_delete = 0;
+        while i < diffs.len() {
+            match diffs[i].operation {
+                Operation::Delete => {
+                    text_delete.extend_from_slice(&diffs[i].tokens);
+                    count_delete += 1;
+                    i += 1;
+                },
+                Operation::Insert => {
+                    text_insert.extend_from_slice(&diffs[i].tokens);
+                    count_insert += 1;
+                    i += 1;
+                },
+                Operation::Equal => {
+                    // Upon reaching an equality, check for prior redundancies.
+                    if count_delete + count_insert > 1 {
+                        if count_delete > 0 && count_insert > 0 {
+                            // Factor out any common prefixies.
+                            let mut commonlength = self.diff_common_prefix(&text_insert, &text_delete) as usize;
+                            if commonlength != 0 {
+                                let prefix: Vec<T> = text_insert.drain(..commonlength).collect();
+                                text_delete.drain(..commonlength);
+                                let x = i - count_delete - count_insert;
+                                if x > 0 && diffs[x - 1].operation == Operation::Equal {
+                                    diffs[x - 1].tokens.extend(prefix);
+                                }
+                                else {
+                                    diffs.insert(0, DiffOp::new(Operation::Equal, prefix));
+                                    i += 1;
+                                }
+                            }
+
+                            // Factor out any common suffixies.
+                            commonlength = self.diff_common_suffix(&text_insert, &text_delete) as usize;
+                            if commonlength != 0 {
+                                let mut suffix: Vec<T> = text_insert.split_off(text_insert.len() - commonlength);
+                                text_delete.truncate(text_delete.len() - commonlength);
+                                suffix.append(&mut diffs[i].tokens);
+                                diffs[i].tokens = suffix;
+                            }
+                        }
+
+                        // Delete the offending records and add the merged ones.
+                        i -= count_delete + count_insert;
+                        diffs.drain(i..(i + count_delete + count_insert));
+                        if !text_delete.is_empty() {
+                            diffs.insert(i, DiffOp::new(Operation::Delete, std::mem::take(&mut text_delete)));
+                            i += 1;
+                        }
+                        if !text_insert.is_empty() {
+                            diffs.insert(i, DiffOp::new(Operation::Insert, std::mem::take(&mut text_insert)));
+                            i += 1;
+                        }
+                        i += 1;
+                    }
+                    else if i != 0 && diffs[i - 1].operation == Operation::Equal {
+                        // Merge this equality with the previous one.
+                        let tokens = diffs.remove(i).tokens;
+                        diffs[i - 1].tokens.extend(tokens);
+                    }
+                    else {
+                        i += 1;
+                    }
+                    count_delete = 0;
+                    count_insert = 0;
+                    text_delete.clear();
+                    text_insert.clear();
+                }
+            }
+        }
+        // Remove the dummy entry at the end.
+        if diffs.last().is_some_and(|diff| diff.tokens.is_empty()) {
+            diffs.pop();
+        }
+
+        /*
+        Second pass: look for single edits surrounded on both sides by equalities
+        which can be shifted sideways to eliminate an equality.
+        e.g: A<ins>BA</ins>C -> <ins>AB</ins>AC
+        */
+        let mut changes = false;
+        i = 1;
+        // Intentionally ignore the first and last element (don't need checking).
+        while i + 1 < diffs.len() {
+            if diffs[i - 1].operation == Operation::Equal && diffs[i + 1].operation == Operation::Equal {
+                // This is a single edit surrounded by equalities.
+                if diffs[i].tokens.ends_with(&diffs[i - 1].tokens) {
+                    // Shift the edit over the previous equality.
+                    if !diffs[i - 1].tokens.is_empty() {
+                        let previous = diffs[i - 1].tokens.clone();
+                        let edit_len = diffs[i].tokens.len() - previous.len();
+                        let mut edit = previous.clone();
+                        edit.extend_from_slice(&diffs[i].tokens[..edit_len]);
+                        diffs[i].tokens = edit;
+                        let mut next = previous;
+                        next.append(&mut diffs[i + 1].tokens);
+                        diffs[i + 1].tokens = next;
+                    }
+                    diffs.remove(i - 1);
+                    changes = true;
+                }
+                else if diffs[i].tokens.starts_with(&diffs[i + 1].tokens) {
+                    // Shift the edit over the next equality.
+                    let next = diffs[i + 1].tokens.clone();
+                    diffs[i - 1].tokens.extend_from_slice(&next);
+                    let mut edit = diffs[i].tokens[next.len()..].to_vec();
+                    edit.extend(next);
+                    diffs[i].tokens = edit;
+                    diffs.remove(i + 1);
+                    changes = true;
+                }
+            }
+            i += 1;
+        }
+        // If shifts were made, the diff needs reordering and another shift sweep.
+        if changes {
+            self.diff_cleanup_merge_tokens(diffs);
+        }
+    }
+}
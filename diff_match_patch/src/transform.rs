@@ -0,0 +1,97 @@
+/*Operational transform of concurrent diffs.
+
+Two diffs made from the same base are walked side by side over that base.
+Each is rewritten to apply on top of the other: the text the other side
+inserted becomes an equality, and whatever both sides deleted is dropped.
+Applying a then b' gives the same text as applying b then a'.
+*/
+
+use std::collections::VecDeque;
+
+use super::dmp::{Diff, Dmp, Operation};
+
+fn transform_ops(diffs: &[Diff]) -> VecDeque<(Operation, Vec<char>)> {
+    // the edits of a diff, as a queue that can be consumed a few chars at a time.
+    diffs.iter()
+        .filter(|diff| !diff.text.is_empty())
+        .map(|diff| (diff.operation, diff.text.chars().collect()))
+        .collect()
+}
+
+fn transform_push(diffs: &mut Vec<Diff>, operation: Operation, text: &[char]) {
+    // append text to diffs, merging it into the last diff when the operation is the same.
+    if let Some(last) = diffs.last_mut() {
+        if last.operation == operation {
+            last.text.extend(text);
+            return;
+        }
+    }
+    diffs.push(Diff::new(operation, text.iter().collect()));
+}
+
+impl Dmp {
+    pub fn diff_transform(&self, a: &[Diff], b: &[Diff]) -> (Vec<Diff>, Vec<Diff>) {
+        /*
+        Transform two concurrent diffs of the same text against each other.
+        When both insert at the same position, the insertion of a goes first.
+
+        Args:
+            a: Vector of Diff objects for base to text_a.
+            b: Vector of Diff objects for base to text_b.
+
+        Returns:
+            Tuple of a' (text_b to the merged text) and b' (text_a to the
+            merged text).
+        */
+        let mut ops_a = transform_ops(a);
+        let mut ops_b = transform_ops(b);
+        let mut a_prime: Vec<Diff> = vec![];
+        let mut b_prime: Vec<Diff> = vec![];
+        loop {
+            // Insertions do not consume base, they go through first.
+            if let Some((_, text)) = ops_a.front().filter(|(operation, _)| *operation == Operation::Insert) {
+                transform_push(&mut a_prime, Operation::Insert, text);
+                transform_push(&mut b_prime, Operation::Equal, text);
+                ops_a.pop_front();
+                continue;
+            }
+            if let Some((_, text)) = ops_b.front().filter(|(operation, _)| *operation == Operation::Insert) {
+                transform_push(&mut a_prime, Operation::Equal, text);
+                transform_push(&mut b_prime, Operation::Insert, text);
+                ops_b.pop_front();
+                continue;
+            }
+            // Both sides keep or delete the same chars of base.
+            let (operation_a, operation_b, text) = match (ops_a.front_mut(), ops_b.front_mut()) {
+                (Some((operation_a, text_a)), Some((operation_b, text_b))) => {
+                    let length = text_a.len().min(text_b.len());
+                    text_b.drain(..length);
+                    (*operation_a, *operation_b, text_a.drain(..length).collect::<Vec<char>>())
+                },
+                // One diff is longer than the other, keep the rest as it is.
+                (Some((operation, text)), None) => (*operation, Operation::Equal, std::mem::take(text)),
+                (None, Some((operation, text))) => (Operation::Equal, *operation, std::mem::take(text)),
+                (None, None) => break
+            };
+            match (operation_a, operation_b) {
+                (Operation::Equal, Operation::Equal) => {
+                    transform_push(&mut a_prime, Operation::Equal, &text);
+                    transform_push(&mut b_prime, Operation::Equal, &text);
+                },
+                (Operation::Delete, Operation::Equal) => transform_push(&mut a_prime, Operation::Delete, &text),
+                (Operation::Equal, Operation::Delete) => transform_push(&mut b_prime, Operation::Delete, &text),
+                // Deleted by both sides.
+                _ => {}
+            }
+            if ops_a.front().is_some_and(|(_, text)| text.is_empty()) {
+                ops_a.pop_front();
+            }
+            if ops_b.front().is_some_and(|(_, text)| text.is_empty()) {
+                ops_b.pop_front();
+            }
+        }
+        self.diff_cleanup_merge(&mut a_prime);
+        self.diff_cleanup_merge(&mut b_prime);
+        (a_prime, b_prime)
+    }
+}
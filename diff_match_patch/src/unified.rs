@@ -0,0 +1,445 @@
+/*Unified diffs, as produced by GNU diff -u and git diff.
+
+Char diffs are first turned into line diffs: a line is context only when a
+single equality covers it from start to end in both texts, every other line
+shows up as deleted from text1 and inserted into text2.  Context is counted
+in lines, see Dmp::unified_context.
+
+Parsed hunks are applied through patch_apply, so a hunk whose line numbers
+drifted is relocated with the same bitap matching as any other patch.
+*/
+
+use std::fmt::{self, Display};
+use std::ops::Range;
+use std::sync::OnceLock;
+use regex::Regex;
+
+use super::dmp::{Diff, Dmp, Operation, Patch};
+use super::errors::DmpError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnifiedHunk {
+    // hunk of a unified diff, one diff per line.  Starts are 0-based line
+    // numbers and lengths are counted in lines.
+    pub diffs: Vec<Diff>,
+    pub start1: i32,
+    pub start2: i32,
+    pub length1: i32,
+    pub length2: i32
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilePatch {
+    // changes to one file of a unified or git diff.  A path is None for
+    // /dev/null, i.e. when the file is created or deleted.
+    pub old_path: Option<String>,
+    pub new_path: Option<String>,
+    // git reported the file as renamed from old_path to new_path.
+    pub renamed: bool,
+    pub hunks: Vec<UnifiedHunk>
+}
+
+impl FilePatch {
+    fn new(old_path: Option<String>, new_path: Option<String>) -> FilePatch {
+        FilePatch { old_path, new_path, renamed: false, hunks: vec![] }
+    }
+}
+
+fn unified_path(path: &str, git: bool) -> Option<String> {
+    // drop the timestamp diff -u puts after a tab, and the a/ and b/ prefixes of git.
+    let path = path.split('\t').next().unwrap_or("").trim_end();
+    if path == "/dev/null" {
+        return None;
+    }
+    if git {
+        if let Some(stripped) = path.strip_prefix("a/").or_else(|| path.strip_prefix("b/")) {
+            return Some(stripped.to_string());
+        }
+    }
+    Some(path.to_string())
+}
+
+fn unified_range(start: i32, length: i32) -> String {
+    // GNU diff leaves out a length of 1, and an empty range names the line before it.
+    match length {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, length)
+    }
+}
+
+impl Display for UnifiedHunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "@@ -{} +{} @@", unified_range(self.start1, self.length1), unified_range(self.start2, self.length2))?;
+        for diff in &self.diffs {
+            let prefix = match diff.operation {
+                Operation::Equal => ' ',
+                Operation::Delete => '-',
+                Operation::Insert => '+'
+            };
+            write!(f, "{prefix}{}", diff.text)?;
+            if !diff.text.ends_with('\n') {
+                write!(f, "\n\\ No newline at end of file\n")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl UnifiedHunk {
+    fn to_patch(&self, start: i32) -> Patch {
+        // the hunk as a char patch at char offset start, merging runs of lines.
+        let mut diffs: Vec<Diff> = vec![];
+        for line in &self.diffs {
+            match diffs.last_mut() {
+                Some(diff) if diff.operation == line.operation => diff.text += line.text.as_str(),
+                _ => diffs.push(line.clone())
+            }
+        }
+        let length1 = diffs.iter().filter(|diff| diff.operation != Operation::Insert).map(|diff| diff.text.chars().count() as i32).sum();
+        let length2 = diffs.iter().filter(|diff| diff.operation != Operation::Delete).map(|diff| diff.text.chars().count() as i32).sum();
+        Patch::new(diffs, start, start, length1, length2)
+    }
+}
+
+fn line_starts(text: &str) -> Vec<usize> {
+    // byte offset of the start of every line.
+    let mut starts = vec![];
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        starts.push(offset);
+        offset += line.len();
+    }
+    starts
+}
+
+pub(crate) fn diff_lines_from_chars(diffs: &[Diff]) -> Vec<Diff> {
+    /*
+    Turn a char diff into a line diff, one diff per line.
+
+    Args:
+        diffs: Vector of diff object.
+
+    Returns:
+        Vector of single line diffs.
+    */
+    let text1: String = diffs.iter().filter(|diff| diff.operation != Operation::Insert).map(|diff| diff.text.as_str()).collect();
+    let text2: String = diffs.iter().filter(|diff| diff.operation != Operation::Delete).map(|diff| diff.text.as_str()).collect();
+    let lines1: Vec<&str> = text1.split_inclusive('\n').collect();
+    let lines2: Vec<&str> = text2.split_inclusive('\n').collect();
+    let starts1 = line_starts(&text1);
+    let starts2 = line_starts(&text2);
+
+    // Pairs of line numbers an equality covers completely.
+    let mut matches: Vec<(usize, usize)> = vec![];
+    let mut offset1 = 0;
+    let mut offset2 = 0;
+    for diff in diffs {
+        match diff.operation {
+            Operation::Insert => offset2 += diff.text.len(),
+            Operation::Delete => offset1 += diff.text.len(),
+            Operation::Equal => {
+                let mut k = 0;
+                for piece in diff.text.split_inclusive('\n') {
+                    let complete = piece.ends_with('\n') || (offset1 + k + piece.len() == text1.len() && offset2 + k + piece.len() == text2.len());
+                    if let (Ok(i), Ok(j), true) = (starts1.binary_search(&(offset1 + k)), starts2.binary_search(&(offset2 + k)), complete) {
+                        matches.push((i, j));
+                    }
+                    k += piece.len();
+                }
+                offset1 += diff.text.len();
+                offset2 += diff.text.len();
+            }
+        }
+    }
+
+    let mut line_diffs: Vec<Diff> = vec![];
+    let mut i = 0;
+    let mut j = 0;
+    for (match1, match2) in matches.into_iter().chain(std::iter::once((lines1.len(), lines2.len()))) {
+        for line in &lines1[i..match1] {
+            line_diffs.push(Diff::new(Operation::Delete, line.to_string()));
+        }
+        for line in &lines2[j..match2] {
+            line_diffs.push(Diff::new(Operation::Insert, line.to_string()));
+        }
+        if match1 < lines1.len() {
+            line_diffs.push(Diff::new(Operation::Equal, lines1[match1].to_string()));
+        }
+        i = match1 + 1;
+        j = match2 + 1;
+    }
+    line_diffs
+}
+
+pub(crate) fn diff_change_lines<'a>(line_diffs: &'a [Diff], k: &mut usize) -> (Vec<&'a str>, Vec<&'a str>) {
+    // the lines deleted and inserted by the change starting at line_diffs[k],
+    // moving k past it.
+    let mut deleted: Vec<&str> = vec![];
+    let mut inserted: Vec<&str> = vec![];
+    while let Some(diff) = line_diffs.get(*k).filter(|diff| diff.operation != Operation::Equal) {
+        if diff.operation == Operation::Delete {
+            deleted.push(&diff.text);
+        }
+        else {
+            inserted.push(&diff.text);
+        }
+        *k += 1;
+    }
+    (deleted, inserted)
+}
+
+pub(crate) fn hunk_ranges(changed: &[bool], context: usize) -> Vec<Range<usize>> {
+    // the lines of every hunk, context lines around the changed ones.
+    // Changes no more than twice context lines apart share a hunk.
+    let changes: Vec<usize> = (0..changed.len()).filter(|i| changed[*i]).collect();
+    let mut ranges: Vec<Range<usize>> = vec![];
+    let mut k = 0;
+    while k < changes.len() {
+        let first = changes[k];
+        while k + 1 < changes.len() && changes[k + 1] - changes[k] - 1 <= 2 * context {
+            k += 1;
+        }
+        ranges.push(first.saturating_sub(context)..(changes[k] + context + 1).min(changed.len()));
+        k += 1;
+    }
+    ranges
+}
+
+impl Dmp {
+    pub fn diff_unified_hunks(&self, diffs: &[Diff]) -> Vec<UnifiedHunk> {
+        /*
+        Group a diff into the hunks of a unified diff, with unified_context
+        lines of context around every change.  Changes separated by no more
+        than twice that many lines share a hunk.
+
+        Args:
+            diffs: Vector of diff object.
+
+        Returns:
+            Vector of UnifiedHunk objects, empty if the texts are equal.
+        */
+        let context = self.unified_context.max(0) as usize;
+        let line_diffs = diff_lines_from_chars(diffs);
+        // Line numbers in text1 and text2 at the start of every line diff.
+        let mut line1 = vec![0];
+        let mut line2 = vec![0];
+        for diff in &line_diffs {
+            line1.push(line1[line1.len() - 1] + (diff.operation != Operation::Insert) as i32);
+            line2.push(line2[line2.len() - 1] + (diff.operation != Operation::Delete) as i32);
+        }
+
+        let changed: Vec<bool> = line_diffs.iter().map(|diff| diff.operation != Operation::Equal).collect();
+        hunk_ranges(&changed, context).into_iter().map(|lines| UnifiedHunk {
+            diffs: line_diffs[lines.clone()].to_vec(),
+            start1: line1[lines.start],
+            start2: line2[lines.start],
+            length1: line1[lines.end] - line1[lines.start],
+            length2: line2[lines.end] - line2[lines.start]
+        }).collect()
+    }
+
+    pub fn diff_unified(&self, diffs: &[Diff], name1: &str, name2: &str) -> String {
+        /*
+        Render a diff as a unified diff.
+
+        Args:
+            diffs: Vector of diff object.
+            name1: Name of the old file, for the --- header.
+            name2: Name of the new file, for the +++ header.
+
+        Returns:
+            Unified diff text, empty if the texts are equal.
+        */
+        let hunks = self.diff_unified_hunks(diffs);
+        if hunks.is_empty() {
+            return String::new();
+        }
+        let mut text = format!("--- {name1}\n+++ {name2}\n");
+        for hunk in hunks {
+            text += hunk.to_string().as_str();
+        }
+        text
+    }
+
+    pub fn diff_unified_text(&self, text1: &str, text2: &str, name1: &str, name2: &str) -> String {
+        /*
+        Diff two texts line by line and render the result as a unified diff.
+
+        Args:
+            text1: Old text to be diffed.
+            text2: New text to be diffed.
+            name1: Name of the old file, for the --- header.
+            name2: Name of the new file, for the +++ header.
+
+        Returns:
+            Unified diff text, empty if the texts are equal.
+        */
+        let lines1: Vec<&str> = text1.split_inclusive('\n').collect();
+        let lines2: Vec<&str> = text2.split_inclusive('\n').collect();
+        let diffs: Vec<Diff> = self.diff_tokens(&lines1, &lines2).into_iter().map(|diff| Diff::new(diff.operation, diff.tokens.concat())).collect();
+        self.diff_unified(&diffs, name1, name2)
+    }
+
+    pub fn patch_from_unified(&self, text: &str) -> Result<Vec<FilePatch>, DmpError> {
+        /*
+        Parse a unified diff, as written by diff -u or git diff, into one
+        FilePatch per file.  Lines outside of file headers and hunks, such as
+        commit messages or index lines, are ignored.
+
+        Args:
+            text: Text representation of the diff.
+
+        Returns:
+            Vector of FilePatch objects.
+
+        Errors:
+            DmpError: If a hunk is malformed or cut short.
+        */
+        static HEADER: OnceLock<Regex> = OnceLock::new();
+        let header = HEADER.get_or_init(|| Regex::new(r"^@@ -(\d+)(?:,(\d+))? \+(\d+)(?:,(\d+))? @@").unwrap());
+        let lines: Vec<&str> = text.split_inclusive('\n').collect();
+        let mut files: Vec<FilePatch> = vec![];
+        // The last file came from a "diff --git" line, and its ---/+++ lines are still to come.
+        let mut git = false;
+        let mut awaiting_paths = false;
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i].trim_end_matches(['\n', '\r']);
+            let number = i + 1;
+            if let Some(rest) = line.strip_prefix("diff --git ") {
+                let (old_path, new_path) = match rest.find(" b/") {
+                    Some(k) => (&rest[..k], &rest[(k + 1)..]),
+                    None => (rest, rest)
+                };
+                files.push(FilePatch::new(unified_path(old_path, true), unified_path(new_path, true)));
+                git = true;
+                awaiting_paths = true;
+            }
+            else if line.starts_with("--- ") && i + 1 < lines.len() && lines[i + 1].starts_with("+++ ") {
+                let new_line = lines[i + 1].trim_end_matches(['\n', '\r']);
+                if !awaiting_paths {
+                    git = false;
+                    files.push(FilePatch::new(None, None));
+                }
+                if let Some(file) = files.last_mut() {
+                    file.old_path = unified_path(&line[4..], git);
+                    file.new_path = unified_path(&new_line[4..], git);
+                }
+                awaiting_paths = false;
+                i += 1;
+            }
+            else if line.starts_with("@@ ") {
+                let bad_header = DmpError::BadHeader { line: number, column: 4 };
+                let file = files.last_mut().ok_or_else(|| bad_header.clone())?;
+                let captures = header.captures(line).ok_or_else(|| bad_header.clone())?;
+                let mut numbers: Vec<i32> = vec![];
+                for group in 1..5 {
+                    numbers.push(match captures.get(group) {
+                        Some(number) => number.as_str().parse::<i32>().map_err(|_| bad_header.clone())?,
+                        None => 1
+                    });
+                }
+                let mut hunk = UnifiedHunk {
+                    diffs: vec![],
+                    start1: if numbers[1] == 0 { numbers[0] } else { numbers[0] - 1 },
+                    start2: if numbers[3] == 0 { numbers[2] } else { numbers[2] - 1 },
+                    length1: numbers[1],
+                    length2: numbers[3]
+                };
+                // Lines of text1 and text2 still expected in the hunk.
+                let mut remaining1 = hunk.length1;
+                let mut remaining2 = hunk.length2;
+                while remaining1 > 0 || remaining2 > 0 || (i + 1 < lines.len() && lines[i + 1].starts_with('\\')) {
+                    i += 1;
+                    if i >= lines.len() {
+                        return Err(DmpError::LengthMismatch { line: number, column: 1, expected: hunk.length1 as usize, actual: (hunk.length1 - remaining1) as usize });
+                    }
+                    let body = lines[i];
+                    let (operation, content) = match body.chars().next() {
+                        Some(' ') => (Operation::Equal, &body[1..]),
+                        Some('-') => (Operation::Delete, &body[1..]),
+                        Some('+') => (Operation::Insert, &body[1..]),
+                        // "\ No newline at end of file" belongs to the line before.
+                        Some('\\') => {
+                            if let Some(diff) = hunk.diffs.last_mut() {
+                                if diff.text.ends_with('\n') {
+                                    diff.text.pop();
+                                }
+                            }
+                            continue;
+                        },
+                        // Some editors strip the space off empty context lines.
+                        Some('\n') | Some('\r') => (Operation::Equal, body),
+                        Some(prefix) => return Err(DmpError::BadLinePrefix { line: i + 1, column: 1, prefix }),
+                        None => continue
+                    };
+                    if operation != Operation::Insert {
+                        remaining1 -= 1;
+                    }
+                    if operation != Operation::Delete {
+                        remaining2 -= 1;
+                    }
+                    if remaining1 < 0 || remaining2 < 0 {
+                        let (expected, actual) = if remaining1 < 0 { (hunk.length1, hunk.length1 - remaining1) } else { (hunk.length2, hunk.length2 - remaining2) };
+                        return Err(DmpError::LengthMismatch { line: i + 1, column: 1, expected: expected as usize, actual: actual as usize });
+                    }
+                    hunk.diffs.push(Diff::new(operation, content.to_string()));
+                }
+                file.hunks.push(hunk);
+                awaiting_paths = false;
+            }
+            else if let (true, Some(file)) = (git, files.last_mut()) {
+                // Extended header lines of git.
+                if line.starts_with("new file mode") {
+                    file.old_path = None;
+                }
+                else if line.starts_with("deleted file mode") {
+                    file.new_path = None;
+                }
+                else if let Some(path) = line.strip_prefix("rename from ") {
+                    file.old_path = Some(path.to_string());
+                    file.renamed = true;
+                }
+                else if let Some(path) = line.strip_prefix("rename to ") {
+                    file.new_path = Some(path.to_string());
+                    file.renamed = true;
+                }
+            }
+            i += 1;
+        }
+        Ok(files)
+    }
+
+    pub fn patch_apply_unified(&self, patch: &FilePatch, text: &str) -> (String, Vec<bool>) {
+        /*
+        Apply the hunks of a parsed file diff to a text.  Each hunk is looked
+        for at its line number, shifted by the lines the hunks before it
+        added or removed, and relocated with match_main when the text moved.
+        A hunk is either applied as a whole or not at all.
+
+        Args:
+            patch: FilePatch object.
+            text: Old text.
+
+        Returns:
+            The new text and one boolean per hunk.
+        */
+        let mut text = text.to_string();
+        let mut results: Vec<bool> = vec![];
+        // Lines added minus lines removed by the hunks applied so far.
+        let mut delta = 0;
+        for hunk in &patch.hunks {
+            let line = (hunk.start1 + delta).max(0) as usize;
+            let start: usize = text.split_inclusive('\n').take(line).map(|line| line.chars().count()).sum();
+            let (patched, applied) = self.patch_apply(&[hunk.to_patch(start as i32)], &text);
+            let applied = applied.iter().all(|applied| *applied);
+            if applied {
+                text = patched;
+                delta += hunk.length2 - hunk.length1;
+            }
+            results.push(applied);
+        }
+        (text, results)
+    }
+}
@@ -1,22 +1,36 @@
-use diff_match_patch;
+use diff_match_patch::{DiffFormatter, Operation};
 use std::collections::HashMap;
 use core::char;
+use std::convert::TryFrom;
 
 pub fn diff_rebuildtexts( diffs: Vec<diff_match_patch::Diff>) -> Vec<String> {
     let mut text1: String = "".to_string();
     let mut text2: String = "".to_string();
-    for x in 0..diffs.len() {
-        if diffs[x].operation != 1 {
-            text1 += diffs[x].text.as_str();
+    for diff in &diffs {
+        if diff.operation != Operation::Insert {
+            text1 += diff.text.as_str();
         }
-        if diffs[x].operation != -1 {
-            text2 += diffs[x].text.as_str();
+        if diff.operation != Operation::Delete {
+            text2 += diff.text.as_str();
         }
     }
     
     vec![text1, text2]
 }
 
+#[test]
+pub fn test_diff_operation() {
+    // Legacy integer operations round-trip through the enum.
+    assert_eq!(Some(Operation::Delete), Operation::from_i32(-1));
+    assert_eq!(Some(Operation::Equal), Operation::from_i32(0));
+    assert_eq!(Some(Operation::Insert), Operation::from_i32(1));
+    assert_eq!(None, Operation::from_i32(2));
+    assert_eq!(-1, i32::from(Operation::Delete));
+    assert_eq!(0, Operation::Equal.to_i32());
+    assert_eq!(Ok(Operation::Insert), Operation::try_from(1));
+    assert_eq!(Err(-2), Operation::try_from(-2));
+}
+
 #[test]
 pub fn test_diff_common_prefix() {
     let dmp = diff_match_patch::Dmp::new();
@@ -56,16 +70,16 @@ pub fn test_diff_half_match() {
     let mut dmp = diff_match_patch::Dmp::new();
     dmp.diff_timeout = Some(1.0);
     let temp: Vec<String> = vec![];
-    assert_eq!(temp, dmp.diff_half_match(&("1234567890".to_string().chars().collect()), &("abcdef".to_string().chars().collect())));
-    assert_eq!(temp, dmp.diff_half_match(&("12345".to_string().chars().collect()), &("23".to_string().chars().collect())));
-    assert_eq!(dmp.split_by_char("12,90,a,z,345678", ','), dmp.diff_half_match(&("1234567890".to_string().chars().collect()), &("a345678z".to_string().chars().collect())));
-    assert_eq!(dmp.split_by_char("a,z,12,90,345678", ','), dmp.diff_half_match(&("a345678z".to_string().chars().collect()), &("1234567890".to_string().chars().collect())));
-    assert_eq!(dmp.split_by_char("abc,z,1234,0,56789", ','), dmp.diff_half_match(&("abc56789z".to_string().chars().collect()), &("1234567890".to_string().chars().collect())));
-    assert_eq!(dmp.split_by_char("a,xyz,1,7890,23456", ','), dmp.diff_half_match(&("a23456xyz".to_string().chars().collect()), &("1234567890".to_string().chars().collect())));
-    assert_eq!(dmp.split_by_char("12123,123121,a,z,1234123451234", ','), dmp.diff_half_match(&("121231234123451234123121".to_string().chars().collect()), &("a1234123451234z".to_string().chars().collect())));
-    assert_eq!(dmp.split_by_char(",-=-=-=-=-=,x,,x-=-=-=-=-=-=-=", ','), dmp.diff_half_match(&("x-=-=-=-=-=-=-=-=-=-=-=-=".to_string().chars().collect()), &("xx-=-=-=-=-=-=-=".to_string().chars().collect())));
-    assert_eq!(dmp.split_by_char("-=-=-=-=-=,,,y,-=-=-=-=-=-=-=y", ','), dmp.diff_half_match(&("-=-=-=-=-=-=-=-=-=-=-=-=y".to_string().chars().collect()), &("-=-=-=-=-=-=-=yy".to_string().chars().collect())));
-    assert_eq!(dmp.split_by_char("qHillo,w,x,Hulloy,HelloHe", ','), dmp.diff_half_match(&("qHilloHelloHew".to_string().chars().collect()), &("xHelloHeHulloy".to_string().chars().collect())));
+    assert_eq!(temp, dmp.diff_half_match(&("1234567890".to_string().chars().collect::<Vec<_>>()), &("abcdef".to_string().chars().collect::<Vec<_>>())));
+    assert_eq!(temp, dmp.diff_half_match(&("12345".to_string().chars().collect::<Vec<_>>()), &("23".to_string().chars().collect::<Vec<_>>())));
+    assert_eq!(dmp.split_by_char("12,90,a,z,345678", ','), dmp.diff_half_match(&("1234567890".to_string().chars().collect::<Vec<_>>()), &("a345678z".to_string().chars().collect::<Vec<_>>())));
+    assert_eq!(dmp.split_by_char("a,z,12,90,345678", ','), dmp.diff_half_match(&("a345678z".to_string().chars().collect::<Vec<_>>()), &("1234567890".to_string().chars().collect::<Vec<_>>())));
+    assert_eq!(dmp.split_by_char("abc,z,1234,0,56789", ','), dmp.diff_half_match(&("abc56789z".to_string().chars().collect::<Vec<_>>()), &("1234567890".to_string().chars().collect::<Vec<_>>())));
+    assert_eq!(dmp.split_by_char("a,xyz,1,7890,23456", ','), dmp.diff_half_match(&("a23456xyz".to_string().chars().collect::<Vec<_>>()), &("1234567890".to_string().chars().collect::<Vec<_>>())));
+    assert_eq!(dmp.split_by_char("12123,123121,a,z,1234123451234", ','), dmp.diff_half_match(&("121231234123451234123121".to_string().chars().collect::<Vec<_>>()), &("a1234123451234z".to_string().chars().collect::<Vec<_>>())));
+    assert_eq!(dmp.split_by_char(",-=-=-=-=-=,x,,x-=-=-=-=-=-=-=", ','), dmp.diff_half_match(&("x-=-=-=-=-=-=-=-=-=-=-=-=".to_string().chars().collect::<Vec<_>>()), &("xx-=-=-=-=-=-=-=".to_string().chars().collect::<Vec<_>>())));
+    assert_eq!(dmp.split_by_char("-=-=-=-=-=,,,y,-=-=-=-=-=-=-=y", ','), dmp.diff_half_match(&("-=-=-=-=-=-=-=-=-=-=-=-=y".to_string().chars().collect::<Vec<_>>()), &("-=-=-=-=-=-=-=yy".to_string().chars().collect::<Vec<_>>())));
+    assert_eq!(dmp.split_by_char("qHillo,w,x,Hulloy,HelloHe", ','), dmp.diff_half_match(&("qHilloHelloHew".to_string().chars().collect::<Vec<_>>()), &("xHelloHeHulloy".to_string().chars().collect::<Vec<_>>())));
 }
 
 #[test]
@@ -73,7 +87,7 @@ pub fn test_diff_half_match_no_timeout() {
     let mut dmp = diff_match_patch::Dmp::new();
     dmp.diff_timeout = None;
     let empty_vec: Vec<String> = vec![];
-    assert_eq!(empty_vec, dmp.diff_half_match(&("qHilloHelloHew".to_string().chars().collect()), &("xHelloHeHulloy".to_string().chars().collect())));
+    assert_eq!(empty_vec, dmp.diff_half_match(&("qHilloHelloHew".to_string().chars().collect::<Vec<_>>()), &("xHelloHeHulloy".to_string().chars().collect::<Vec<_>>())));
 }
 
 #[test]
@@ -88,11 +102,8 @@ pub fn test_diff_lines_tochars() {
     let mut char_list: Vec<char> = vec![];
     for i in 1..n+1{
         line_list.push(i.to_string() + "\n");
-        match char::from_u32(i) {
-            Some(ch) => {
-                char_list.push(ch);
-            },
-            None => {}
+        if let Some(ch) = char::from_u32(i) {
+            char_list.push(ch);
         }
     }
     let chars: String = char_list.into_iter().collect();
@@ -101,36 +112,36 @@ pub fn test_diff_lines_tochars() {
     let lines_vec: Vec<char> = lines.chars().collect();
     assert_eq!(n as usize, chars.chars().count());
     line_list.insert(0, "".to_string());
-    assert_eq!((chars, "".to_string(), line_list), dmp.diff_lines_tochars(&lines_vec, &vec![]))
+    assert_eq!((chars, "".to_string(), line_list), dmp.diff_lines_tochars(&lines_vec, &[]))
 }
 
 #[test]
 pub fn test_diff_words_tochars() {
-    let mut dmp = diff_match_patch::Dmp::new();
+    let dmp = diff_match_patch::Dmp::new();
     assert_eq!(("\x01\x02\x03\x02\x01".to_string(), "\x03\x02\x01\x02\x03".to_string(), vec!["".to_string(), "alpha".to_string(), " ".to_string(), "beta".to_string()]),
-                dmp.diff_words_tochars(&"alpha beta alpha".to_string(), &"beta alpha beta".to_string())
+                dmp.diff_words_tochars("alpha beta alpha", "beta alpha beta")
                );               
     assert_eq!(("\x01\x02".to_string(), "\x03\x02\x01".to_string(), vec!["".to_string(), "alpha".to_string(), "\n".to_string(), "beta".to_string()]),
-                dmp.diff_words_tochars(&"alpha\n".to_string(), &"beta\nalpha".to_string())
+                dmp.diff_words_tochars("alpha\n", "beta\nalpha")
                );
     let old_string = "betty bought some butter ".to_string();
     let new_string = "betty sought some butter".to_string();
     let mut diff_arr = vec![
-        diff_match_patch::Diff::new(0, "betty ".to_string()),
-        diff_match_patch::Diff::new(-1, "b".to_string()),
-        diff_match_patch::Diff::new(1, "s".to_string()),
-        diff_match_patch::Diff::new(0, "ought some butter".to_string()),
-        diff_match_patch::Diff::new(-1, " ".to_string())
+        diff_match_patch::Diff::new(Operation::Equal, "betty ".to_string()),
+        diff_match_patch::Diff::new(Operation::Delete, "b".to_string()),
+        diff_match_patch::Diff::new(Operation::Insert, "s".to_string()),
+        diff_match_patch::Diff::new(Operation::Equal, "ought some butter".to_string()),
+        diff_match_patch::Diff::new(Operation::Delete, " ".to_string())
     ];
     println!("{:?}", diff_arr);
     assert_eq!(diff_arr, dmp.diff_main(&old_string, &new_string, true));
 
     diff_arr = vec![
-        diff_match_patch::Diff::new(0, "betty ".to_string()),
-        diff_match_patch::Diff::new(-1, "bought".to_string()),
-        diff_match_patch::Diff::new(1, "sought".to_string()),
-        diff_match_patch::Diff::new(0, " some butter".to_string()),
-        diff_match_patch::Diff::new(-1, " ".to_string())
+        diff_match_patch::Diff::new(Operation::Equal, "betty ".to_string()),
+        diff_match_patch::Diff::new(Operation::Delete, "bought".to_string()),
+        diff_match_patch::Diff::new(Operation::Insert, "sought".to_string()),
+        diff_match_patch::Diff::new(Operation::Equal, " some butter".to_string()),
+        diff_match_patch::Diff::new(Operation::Delete, " ".to_string())
     ];
 
     let (chars1, chars2, hash_arr) = dmp.diff_words_tochars(&old_string, &new_string);
@@ -144,9 +155,9 @@ pub fn test_diff_words_tochars() {
 #[test]
 pub fn test_diff_chars_tolines() {
     let dmp = diff_match_patch::Dmp::new();
-    let mut diffs = vec![diff_match_patch::Diff::new(0, "\x01\x02\x01".to_string()), diff_match_patch::Diff::new(1, "\x02\x01\x02".to_string())];
-    dmp.diff_chars_tolines(&mut diffs, &vec!["".to_string(), "alpha\n".to_string(), "beta\n".to_string()]);
-    assert_eq!(vec![diff_match_patch::Diff::new(0, "alpha\nbeta\nalpha\n".to_string()), diff_match_patch::Diff::new(1, "beta\nalpha\nbeta\n".to_string())], diffs);
+    let mut diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "\x01\x02\x01".to_string()), diff_match_patch::Diff::new(Operation::Insert, "\x02\x01\x02".to_string())];
+    dmp.diff_chars_tolines(&mut diffs, &["".to_string(), "alpha\n".to_string(), "beta\n".to_string()]);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "alpha\nbeta\nalpha\n".to_string()), diff_match_patch::Diff::new(Operation::Insert, "beta\nalpha\nbeta\n".to_string())], diffs);
 
     let n: u32 = 300;
     let mut line_list: Vec<String> = vec![];
@@ -160,9 +171,9 @@ pub fn test_diff_chars_tolines() {
     let lines = line_list.join("");
     assert_eq!(n as usize, chars.chars().count());
     line_list.insert(0, "".to_string());
-    let mut diffs = vec![diff_match_patch::Diff::new(-1, chars)];
+    let mut diffs = vec![diff_match_patch::Diff::new(Operation::Delete, chars)];
     dmp.diff_chars_tolines(&mut diffs, &line_list);
-    assert_eq!(diffs, vec![diff_match_patch::Diff::new(-1, lines)]);
+    assert_eq!(diffs, vec![diff_match_patch::Diff::new(Operation::Delete, lines)]);
 }
 
 #[test]
@@ -198,71 +209,71 @@ pub fn test_diff_cleanup_merge() {
     assert_eq!(temp, diffs);
 
     // No change case.
-    diffs = vec![diff_match_patch::Diff::new(0, "a".to_string()), diff_match_patch::Diff::new(-1, "b".to_string()), diff_match_patch::Diff::new(1, "c".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "a".to_string()), diff_match_patch::Diff::new(Operation::Delete, "b".to_string()), diff_match_patch::Diff::new(Operation::Insert, "c".to_string())];
     dmp.diff_cleanup_merge(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(0, "a".to_string()), diff_match_patch::Diff::new(-1, "b".to_string()), diff_match_patch::Diff::new(1, "c".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "a".to_string()), diff_match_patch::Diff::new(Operation::Delete, "b".to_string()), diff_match_patch::Diff::new(Operation::Insert, "c".to_string())], diffs);
 
     // Merge equalities.
-    diffs = vec![diff_match_patch::Diff::new(0, "a".to_string()), diff_match_patch::Diff::new(0, "b".to_string()), diff_match_patch::Diff::new(0, "c".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "a".to_string()), diff_match_patch::Diff::new(Operation::Equal, "b".to_string()), diff_match_patch::Diff::new(Operation::Equal, "c".to_string())];
     dmp.diff_cleanup_merge(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(0, "abc".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "abc".to_string())], diffs);
 
     // Merge deletions.
-    diffs = vec![diff_match_patch::Diff::new(-1, "a".to_string()), diff_match_patch::Diff::new(-1, "b".to_string()), diff_match_patch::Diff::new(-1, "c".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Delete, "a".to_string()), diff_match_patch::Diff::new(Operation::Delete, "b".to_string()), diff_match_patch::Diff::new(Operation::Delete, "c".to_string())];
     dmp.diff_cleanup_merge(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "abc".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "abc".to_string())], diffs);
 
     // Merge insertions.
-    diffs = vec![diff_match_patch::Diff::new(1, "a".to_string()), diff_match_patch::Diff::new(1, "b".to_string()), diff_match_patch::Diff::new(1, "c".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Insert, "a".to_string()), diff_match_patch::Diff::new(Operation::Insert, "b".to_string()), diff_match_patch::Diff::new(Operation::Insert, "c".to_string())];
     dmp.diff_cleanup_merge(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(1, "abc".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Insert, "abc".to_string())], diffs);
 
     // Merge interweave.
-    diffs = vec![diff_match_patch::Diff::new(-1, "a".to_string()), diff_match_patch::Diff::new(1, "b".to_string()), diff_match_patch::Diff::new(-1, "c".to_string()), diff_match_patch::Diff::new(1, "d".to_string()), diff_match_patch::Diff::new(0, "e".to_string()), diff_match_patch::Diff::new(0, "f".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Delete, "a".to_string()), diff_match_patch::Diff::new(Operation::Insert, "b".to_string()), diff_match_patch::Diff::new(Operation::Delete, "c".to_string()), diff_match_patch::Diff::new(Operation::Insert, "d".to_string()), diff_match_patch::Diff::new(Operation::Equal, "e".to_string()), diff_match_patch::Diff::new(Operation::Equal, "f".to_string())];
     dmp.diff_cleanup_merge(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "ac".to_string()), diff_match_patch::Diff::new(1, "bd".to_string()), diff_match_patch::Diff::new(0, "ef".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "ac".to_string()), diff_match_patch::Diff::new(Operation::Insert, "bd".to_string()), diff_match_patch::Diff::new(Operation::Equal, "ef".to_string())], diffs);
 
 
     // Prefix and suffix detection.
-    diffs = vec![diff_match_patch::Diff::new(-1, "a".to_string()), diff_match_patch::Diff::new(1, "abc".to_string()), diff_match_patch::Diff::new(-1, "dc".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Delete, "a".to_string()), diff_match_patch::Diff::new(Operation::Insert, "abc".to_string()), diff_match_patch::Diff::new(Operation::Delete, "dc".to_string())];
     dmp.diff_cleanup_merge(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(0, "a".to_string()), diff_match_patch::Diff::new(-1, "d".to_string()), diff_match_patch::Diff::new(1, "b".to_string()), diff_match_patch::Diff::new(0, "c".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "a".to_string()), diff_match_patch::Diff::new(Operation::Delete, "d".to_string()), diff_match_patch::Diff::new(Operation::Insert, "b".to_string()), diff_match_patch::Diff::new(Operation::Equal, "c".to_string())], diffs);
 
 
     // Prefix and suffix detection with equalities.
-    diffs = vec![diff_match_patch::Diff::new(0, "x".to_string()), diff_match_patch::Diff::new(-1, "a".to_string()), diff_match_patch::Diff::new(1, "abc".to_string()), diff_match_patch::Diff::new(-1, "dc".to_string()), diff_match_patch::Diff::new(0, "y".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "x".to_string()), diff_match_patch::Diff::new(Operation::Delete, "a".to_string()), diff_match_patch::Diff::new(Operation::Insert, "abc".to_string()), diff_match_patch::Diff::new(Operation::Delete, "dc".to_string()), diff_match_patch::Diff::new(Operation::Equal, "y".to_string())];
     dmp.diff_cleanup_merge(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(0, "xa".to_string()), diff_match_patch::Diff::new(-1, "d".to_string()), diff_match_patch::Diff::new(1, "b".to_string()), diff_match_patch::Diff::new(0, "cy".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "xa".to_string()), diff_match_patch::Diff::new(Operation::Delete, "d".to_string()), diff_match_patch::Diff::new(Operation::Insert, "b".to_string()), diff_match_patch::Diff::new(Operation::Equal, "cy".to_string())], diffs);
 
     // Slide edit left.
-    diffs = vec![diff_match_patch::Diff::new(0, "a".to_string()), diff_match_patch::Diff::new(1, "ba".to_string()), diff_match_patch::Diff::new(0, "c".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "a".to_string()), diff_match_patch::Diff::new(Operation::Insert, "ba".to_string()), diff_match_patch::Diff::new(Operation::Equal, "c".to_string())];
     dmp.diff_cleanup_merge(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(1, "ab".to_string()), diff_match_patch::Diff::new(0, "ac".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Insert, "ab".to_string()), diff_match_patch::Diff::new(Operation::Equal, "ac".to_string())], diffs);
 
     // Slide edit right.
-    diffs = vec![diff_match_patch::Diff::new(0, "c".to_string()), diff_match_patch::Diff::new(1, "ab".to_string()), diff_match_patch::Diff::new(0, "a".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "c".to_string()), diff_match_patch::Diff::new(Operation::Insert, "ab".to_string()), diff_match_patch::Diff::new(Operation::Equal, "a".to_string())];
     dmp.diff_cleanup_merge(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(0, "ca".to_string()), diff_match_patch::Diff::new(1, "ba".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "ca".to_string()), diff_match_patch::Diff::new(Operation::Insert, "ba".to_string())], diffs);
 
     // # Slide edit left recursive.
-    diffs = vec![diff_match_patch::Diff::new(0, "a".to_string()), diff_match_patch::Diff::new(-1, "b".to_string()), diff_match_patch::Diff::new(0, "c".to_string()), diff_match_patch::Diff::new(-1, "ac".to_string()), diff_match_patch::Diff::new(0, "x".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "a".to_string()), diff_match_patch::Diff::new(Operation::Delete, "b".to_string()), diff_match_patch::Diff::new(Operation::Equal, "c".to_string()), diff_match_patch::Diff::new(Operation::Delete, "ac".to_string()), diff_match_patch::Diff::new(Operation::Equal, "x".to_string())];
     dmp.diff_cleanup_merge(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "abc".to_string()), diff_match_patch::Diff::new(0, "acx".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "abc".to_string()), diff_match_patch::Diff::new(Operation::Equal, "acx".to_string())], diffs);
 
     // # Slide edit right recursive.
-    diffs = vec![diff_match_patch::Diff::new(0, "x".to_string()), diff_match_patch::Diff::new(-1, "ca".to_string()), diff_match_patch::Diff::new(0, "c".to_string()), diff_match_patch::Diff::new(-1, "b".to_string()), diff_match_patch::Diff::new(0, "a".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "x".to_string()), diff_match_patch::Diff::new(Operation::Delete, "ca".to_string()), diff_match_patch::Diff::new(Operation::Equal, "c".to_string()), diff_match_patch::Diff::new(Operation::Delete, "b".to_string()), diff_match_patch::Diff::new(Operation::Equal, "a".to_string())];
     dmp.diff_cleanup_merge(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(0, "xca".to_string()), diff_match_patch::Diff::new(-1, "cba".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "xca".to_string()), diff_match_patch::Diff::new(Operation::Delete, "cba".to_string())], diffs);
 
     // # Empty merge.
-    diffs = vec![diff_match_patch::Diff::new(-1, "b".to_string()), diff_match_patch::Diff::new(1, "ab".to_string()), diff_match_patch::Diff::new(0, "c".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Delete, "b".to_string()), diff_match_patch::Diff::new(Operation::Insert, "ab".to_string()), diff_match_patch::Diff::new(Operation::Equal, "c".to_string())];
     dmp.diff_cleanup_merge(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(1, "a".to_string()), diff_match_patch::Diff::new(0, "bc".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Insert, "a".to_string()), diff_match_patch::Diff::new(Operation::Equal, "bc".to_string())], diffs);
 
     // # Empty equality.
-    diffs = vec![diff_match_patch::Diff::new(0, "".to_string()), diff_match_patch::Diff::new(1, "a".to_string()), diff_match_patch::Diff::new(0, "b".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "".to_string()), diff_match_patch::Diff::new(Operation::Insert, "a".to_string()), diff_match_patch::Diff::new(Operation::Equal, "b".to_string())];
     dmp.diff_cleanup_merge(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(1, "a".to_string()), diff_match_patch::Diff::new(0, "b".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Insert, "a".to_string()), diff_match_patch::Diff::new(Operation::Equal, "b".to_string())], diffs);
 }
 
 
@@ -277,39 +288,39 @@ pub fn test_diff_cleanup_semantic_lossless() {
     assert_eq!(temp, diffs);
 
     // Blank lines.
-    diffs = vec![diff_match_patch::Diff::new(0, "AAA\r\n\r\nBBB".to_string()), diff_match_patch::Diff::new(1, "\r\nDDD\r\n\r\nBBB".to_string()), diff_match_patch::Diff::new(0, "\r\nEEE".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "AAA\r\n\r\nBBB".to_string()), diff_match_patch::Diff::new(Operation::Insert, "\r\nDDD\r\n\r\nBBB".to_string()), diff_match_patch::Diff::new(Operation::Equal, "\r\nEEE".to_string())];
     dmp.diff_cleanup_semantic_lossless(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(0, "AAA\r\n\r\n".to_string()), diff_match_patch::Diff::new(1, "BBB\r\nDDD\r\n\r\n".to_string()), diff_match_patch::Diff::new(0, "BBB\r\nEEE".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "AAA\r\n\r\n".to_string()), diff_match_patch::Diff::new(Operation::Insert, "BBB\r\nDDD\r\n\r\n".to_string()), diff_match_patch::Diff::new(Operation::Equal, "BBB\r\nEEE".to_string())], diffs);
 
     // # Line boundaries.
-    diffs = vec![diff_match_patch::Diff::new(0, "AAA\r\nBBB".to_string()), diff_match_patch::Diff::new(1, " DDD\r\nBBB".to_string()), diff_match_patch::Diff::new(0, " EEE".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "AAA\r\nBBB".to_string()), diff_match_patch::Diff::new(Operation::Insert, " DDD\r\nBBB".to_string()), diff_match_patch::Diff::new(Operation::Equal, " EEE".to_string())];
     dmp.diff_cleanup_semantic_lossless(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(0, "AAA\r\n".to_string()), diff_match_patch::Diff::new(1, "BBB DDD\r\n".to_string()), diff_match_patch::Diff::new(0, "BBB EEE".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "AAA\r\n".to_string()), diff_match_patch::Diff::new(Operation::Insert, "BBB DDD\r\n".to_string()), diff_match_patch::Diff::new(Operation::Equal, "BBB EEE".to_string())], diffs);
 
     // # Word boundaries.
-    diffs = vec![diff_match_patch::Diff::new(0, "The c".to_string()), diff_match_patch::Diff::new(1, "ow and the c".to_string()), diff_match_patch::Diff::new(0, "at.".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "The c".to_string()), diff_match_patch::Diff::new(Operation::Insert, "ow and the c".to_string()), diff_match_patch::Diff::new(Operation::Equal, "at.".to_string())];
     dmp.diff_cleanup_semantic_lossless(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(0, "The ".to_string()), diff_match_patch::Diff::new(1, "cow and the ".to_string()), diff_match_patch::Diff::new(0, "cat.".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "The ".to_string()), diff_match_patch::Diff::new(Operation::Insert, "cow and the ".to_string()), diff_match_patch::Diff::new(Operation::Equal, "cat.".to_string())], diffs);
 
     // # Alphanumeric boundaries.
-    diffs = vec![diff_match_patch::Diff::new(0, "The-c".to_string()), diff_match_patch::Diff::new(1, "ow-and-the-c".to_string()), diff_match_patch::Diff::new(0, "at.".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "The-c".to_string()), diff_match_patch::Diff::new(Operation::Insert, "ow-and-the-c".to_string()), diff_match_patch::Diff::new(Operation::Equal, "at.".to_string())];
     dmp.diff_cleanup_semantic_lossless(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(0, "The-".to_string()), diff_match_patch::Diff::new(1, "cow-and-the-".to_string()), diff_match_patch::Diff::new(0, "cat.".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "The-".to_string()), diff_match_patch::Diff::new(Operation::Insert, "cow-and-the-".to_string()), diff_match_patch::Diff::new(Operation::Equal, "cat.".to_string())], diffs);
 
     // # Hitting the start.
-    diffs = vec![diff_match_patch::Diff::new(0, "a".to_string()), diff_match_patch::Diff::new(-1, "a".to_string()), diff_match_patch::Diff::new(0, "ax".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "a".to_string()), diff_match_patch::Diff::new(Operation::Delete, "a".to_string()), diff_match_patch::Diff::new(Operation::Equal, "ax".to_string())];
     dmp.diff_cleanup_semantic_lossless(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "a".to_string()), diff_match_patch::Diff::new(0, "aax".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "a".to_string()), diff_match_patch::Diff::new(Operation::Equal, "aax".to_string())], diffs);
 
     // # Hitting the end.
-    diffs = vec![diff_match_patch::Diff::new(0, "xa".to_string()), diff_match_patch::Diff::new(-1, "a".to_string()), diff_match_patch::Diff::new(0, "a".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "xa".to_string()), diff_match_patch::Diff::new(Operation::Delete, "a".to_string()), diff_match_patch::Diff::new(Operation::Equal, "a".to_string())];
     dmp.diff_cleanup_semantic_lossless(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(0, "xaa".to_string()), diff_match_patch::Diff::new(-1, "a".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "xaa".to_string()), diff_match_patch::Diff::new(Operation::Delete, "a".to_string())], diffs);
 
     // # Sentence boundaries.
-    diffs = vec![diff_match_patch::Diff::new(0, "The xxx. The ".to_string()), diff_match_patch::Diff::new(1, "zzz. The ".to_string()), diff_match_patch::Diff::new(0, "yyy.".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "The xxx. The ".to_string()), diff_match_patch::Diff::new(Operation::Insert, "zzz. The ".to_string()), diff_match_patch::Diff::new(Operation::Equal, "yyy.".to_string())];
     dmp.diff_cleanup_semantic_lossless(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(0, "The xxx.".to_string()), diff_match_patch::Diff::new(1, " The zzz.".to_string()), diff_match_patch::Diff::new(0, " The yyy.".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "The xxx.".to_string()), diff_match_patch::Diff::new(Operation::Insert, " The zzz.".to_string()), diff_match_patch::Diff::new(Operation::Equal, " The yyy.".to_string())], diffs);
 
 }
 
@@ -325,54 +336,54 @@ pub fn test_diff_cleanup_semantic() {
     assert_eq!(diffs, temp);
 
     // No elimination #1.
-    diffs = vec![diff_match_patch::Diff::new(-1, "ab".to_string()), diff_match_patch::Diff::new(1, "cd".to_string()), diff_match_patch::Diff::new(0, "c12".to_string()), diff_match_patch::Diff::new(-1, "e".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Delete, "ab".to_string()), diff_match_patch::Diff::new(Operation::Insert, "cd".to_string()), diff_match_patch::Diff::new(Operation::Equal, "c12".to_string()), diff_match_patch::Diff::new(Operation::Delete, "e".to_string())];
     dmp.diff_cleanup_semantic(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "ab".to_string()), diff_match_patch::Diff::new(1, "cd".to_string()), diff_match_patch::Diff::new(0, "c12".to_string()), diff_match_patch::Diff::new(-1, "e".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "ab".to_string()), diff_match_patch::Diff::new(Operation::Insert, "cd".to_string()), diff_match_patch::Diff::new(Operation::Equal, "c12".to_string()), diff_match_patch::Diff::new(Operation::Delete, "e".to_string())], diffs);
 
     // No elimination #2.
-    diffs = vec![diff_match_patch::Diff::new(-1, "abc".to_string()), diff_match_patch::Diff::new(1, "ABC".to_string()), diff_match_patch::Diff::new(0, "1234".to_string()), diff_match_patch::Diff::new(-1, "wxyz".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Delete, "abc".to_string()), diff_match_patch::Diff::new(Operation::Insert, "ABC".to_string()), diff_match_patch::Diff::new(Operation::Equal, "1234".to_string()), diff_match_patch::Diff::new(Operation::Delete, "wxyz".to_string())];
     dmp.diff_cleanup_semantic(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "abc".to_string()), diff_match_patch::Diff::new(1, "ABC".to_string()), diff_match_patch::Diff::new(0, "1234".to_string()), diff_match_patch::Diff::new(-1, "wxyz".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "abc".to_string()), diff_match_patch::Diff::new(Operation::Insert, "ABC".to_string()), diff_match_patch::Diff::new(Operation::Equal, "1234".to_string()), diff_match_patch::Diff::new(Operation::Delete, "wxyz".to_string())], diffs);
 
     // Simple elimination.
-    diffs = vec![diff_match_patch::Diff::new(-1, "a".to_string()), diff_match_patch::Diff::new(0, "b".to_string()), diff_match_patch::Diff::new(-1, "c".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Delete, "a".to_string()), diff_match_patch::Diff::new(Operation::Equal, "b".to_string()), diff_match_patch::Diff::new(Operation::Delete, "c".to_string())];
     dmp.diff_cleanup_semantic(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "abc".to_string()), diff_match_patch::Diff::new(1, "b".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "abc".to_string()), diff_match_patch::Diff::new(Operation::Insert, "b".to_string())], diffs);
 
     // Backpass elimination.
-    diffs = vec![diff_match_patch::Diff::new(-1, "ab".to_string()), diff_match_patch::Diff::new(0, "cd".to_string()), diff_match_patch::Diff::new(-1, "e".to_string()), diff_match_patch::Diff::new(0, "f".to_string()), diff_match_patch::Diff::new(1, "g".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Delete, "ab".to_string()), diff_match_patch::Diff::new(Operation::Equal, "cd".to_string()), diff_match_patch::Diff::new(Operation::Delete, "e".to_string()), diff_match_patch::Diff::new(Operation::Equal, "f".to_string()), diff_match_patch::Diff::new(Operation::Insert, "g".to_string())];
     dmp.diff_cleanup_semantic(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "abcdef".to_string()), diff_match_patch::Diff::new(1, "cdfg".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "abcdef".to_string()), diff_match_patch::Diff::new(Operation::Insert, "cdfg".to_string())], diffs);
 
     // Multiple eliminations.
-    diffs = vec![diff_match_patch::Diff::new(1, "1".to_string()), diff_match_patch::Diff::new(0, "A".to_string()), diff_match_patch::Diff::new(-1, "B".to_string()), diff_match_patch::Diff::new(1, "2".to_string()), diff_match_patch::Diff::new(0, "_".to_string()),  diff_match_patch::Diff::new(1, "1".to_string()),  diff_match_patch::Diff::new(0, "A".to_string()),  diff_match_patch::Diff::new(-1, "B".to_string()),  diff_match_patch::Diff::new(1, "2".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Insert, "1".to_string()), diff_match_patch::Diff::new(Operation::Equal, "A".to_string()), diff_match_patch::Diff::new(Operation::Delete, "B".to_string()), diff_match_patch::Diff::new(Operation::Insert, "2".to_string()), diff_match_patch::Diff::new(Operation::Equal, "_".to_string()),  diff_match_patch::Diff::new(Operation::Insert, "1".to_string()),  diff_match_patch::Diff::new(Operation::Equal, "A".to_string()),  diff_match_patch::Diff::new(Operation::Delete, "B".to_string()),  diff_match_patch::Diff::new(Operation::Insert, "2".to_string())];
     dmp.diff_cleanup_semantic(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "AB_AB".to_string()), diff_match_patch::Diff::new(1, "1A2_1A2".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "AB_AB".to_string()), diff_match_patch::Diff::new(Operation::Insert, "1A2_1A2".to_string())], diffs);
 
     // Word boundaries.
-    diffs = vec![diff_match_patch::Diff::new(0, "The c".to_string()), diff_match_patch::Diff::new(-1, "ow and the c".to_string()), diff_match_patch::Diff::new(0, "at.".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "The c".to_string()), diff_match_patch::Diff::new(Operation::Delete, "ow and the c".to_string()), diff_match_patch::Diff::new(Operation::Equal, "at.".to_string())];
     dmp.diff_cleanup_semantic(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(0, "The ".to_string()), diff_match_patch::Diff::new(-1, "cow and the ".to_string()), diff_match_patch::Diff::new(0, "cat.".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "The ".to_string()), diff_match_patch::Diff::new(Operation::Delete, "cow and the ".to_string()), diff_match_patch::Diff::new(Operation::Equal, "cat.".to_string())], diffs);
 
     // No overlap elimination.
-    diffs = vec![diff_match_patch::Diff::new(-1, "abcxx".to_string()), diff_match_patch::Diff::new(1, "xxdef".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Delete, "abcxx".to_string()), diff_match_patch::Diff::new(Operation::Insert, "xxdef".to_string())];
     dmp.diff_cleanup_semantic(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "abcxx".to_string()), diff_match_patch::Diff::new(1, "xxdef".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "abcxx".to_string()), diff_match_patch::Diff::new(Operation::Insert, "xxdef".to_string())], diffs);
 
     // Overlap elimination.
-    diffs = vec![diff_match_patch::Diff::new(-1, "abcxxx".to_string()), diff_match_patch::Diff::new(1, "xxxdef".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Delete, "abcxxx".to_string()), diff_match_patch::Diff::new(Operation::Insert, "xxxdef".to_string())];
     dmp.diff_cleanup_semantic(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "abc".to_string()), diff_match_patch::Diff::new(0, "xxx".to_string()), diff_match_patch::Diff::new(1, "def".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "abc".to_string()), diff_match_patch::Diff::new(Operation::Equal, "xxx".to_string()), diff_match_patch::Diff::new(Operation::Insert, "def".to_string())], diffs);
 
     // Reverse overlap elimination.
-    diffs = vec![diff_match_patch::Diff::new(-1, "xxxabc".to_string()), diff_match_patch::Diff::new(1, "defxxx".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Delete, "xxxabc".to_string()), diff_match_patch::Diff::new(Operation::Insert, "defxxx".to_string())];
     dmp.diff_cleanup_semantic(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(1, "def".to_string()), diff_match_patch::Diff::new(0, "xxx".to_string()), diff_match_patch::Diff::new(-1, "abc".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Insert, "def".to_string()), diff_match_patch::Diff::new(Operation::Equal, "xxx".to_string()), diff_match_patch::Diff::new(Operation::Delete, "abc".to_string())], diffs);
 
     // Two overlap eliminations.
-    diffs = vec![diff_match_patch::Diff::new(-1, "abcd1212".to_string()), diff_match_patch::Diff::new(1, "1212efghi".to_string()), diff_match_patch::Diff::new(0, "----".to_string()), diff_match_patch::Diff::new(-1, "A3".to_string()), diff_match_patch::Diff::new(1, "3BC".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Delete, "abcd1212".to_string()), diff_match_patch::Diff::new(Operation::Insert, "1212efghi".to_string()), diff_match_patch::Diff::new(Operation::Equal, "----".to_string()), diff_match_patch::Diff::new(Operation::Delete, "A3".to_string()), diff_match_patch::Diff::new(Operation::Insert, "3BC".to_string())];
     dmp.diff_cleanup_semantic(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "abcd".to_string()), diff_match_patch::Diff::new(0, "1212".to_string()), diff_match_patch::Diff::new(1, "efghi".to_string()), diff_match_patch::Diff::new(0, "----".to_string()), diff_match_patch::Diff::new(-1, "A".to_string()), diff_match_patch::Diff::new(0, "3".to_string()), diff_match_patch::Diff::new(1, "BC".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "abcd".to_string()), diff_match_patch::Diff::new(Operation::Equal, "1212".to_string()), diff_match_patch::Diff::new(Operation::Insert, "efghi".to_string()), diff_match_patch::Diff::new(Operation::Equal, "----".to_string()), diff_match_patch::Diff::new(Operation::Delete, "A".to_string()), diff_match_patch::Diff::new(Operation::Equal, "3".to_string()), diff_match_patch::Diff::new(Operation::Insert, "BC".to_string())], diffs);
 }
 
 #[test]
@@ -386,67 +397,81 @@ pub fn test_diff_cleanup_efficiency() {
     assert_eq!(temp, diffs);
 
     // No elimination.
-    diffs = vec![diff_match_patch::Diff::new(-1, "ab".to_string()), diff_match_patch::Diff::new(1, "12".to_string()), diff_match_patch::Diff::new(0, "wxyz".to_string()), diff_match_patch::Diff::new(-1, "cd".to_string()), diff_match_patch::Diff::new(1, "34".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Delete, "ab".to_string()), diff_match_patch::Diff::new(Operation::Insert, "12".to_string()), diff_match_patch::Diff::new(Operation::Equal, "wxyz".to_string()), diff_match_patch::Diff::new(Operation::Delete, "cd".to_string()), diff_match_patch::Diff::new(Operation::Insert, "34".to_string())];
     dmp.diff_cleanup_efficiency(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "ab".to_string()), diff_match_patch::Diff::new(1, "12".to_string()), diff_match_patch::Diff::new(0, "wxyz".to_string()), diff_match_patch::Diff::new(-1, "cd".to_string()), diff_match_patch::Diff::new(1, "34".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "ab".to_string()), diff_match_patch::Diff::new(Operation::Insert, "12".to_string()), diff_match_patch::Diff::new(Operation::Equal, "wxyz".to_string()), diff_match_patch::Diff::new(Operation::Delete, "cd".to_string()), diff_match_patch::Diff::new(Operation::Insert, "34".to_string())], diffs);
 
     // Four-edit elimination.
-    diffs = vec![diff_match_patch::Diff::new(-1, "ab".to_string()), diff_match_patch::Diff::new(1, "12".to_string()), diff_match_patch::Diff::new(0, "xyz".to_string()), diff_match_patch::Diff::new(-1, "cd".to_string()), diff_match_patch::Diff::new(1, "34".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Delete, "ab".to_string()), diff_match_patch::Diff::new(Operation::Insert, "12".to_string()), diff_match_patch::Diff::new(Operation::Equal, "xyz".to_string()), diff_match_patch::Diff::new(Operation::Delete, "cd".to_string()), diff_match_patch::Diff::new(Operation::Insert, "34".to_string())];
     dmp.diff_cleanup_efficiency(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "abxyzcd".to_string()), diff_match_patch::Diff::new(1, "12xyz34".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "abxyzcd".to_string()), diff_match_patch::Diff::new(Operation::Insert, "12xyz34".to_string())], diffs);
 
     // Three-edit elimination.
-    diffs = vec![diff_match_patch::Diff::new(1, "12".to_string()), diff_match_patch::Diff::new(0, "x".to_string()), diff_match_patch::Diff::new(-1, "cd".to_string()), diff_match_patch::Diff::new(1, "34".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Insert, "12".to_string()), diff_match_patch::Diff::new(Operation::Equal, "x".to_string()), diff_match_patch::Diff::new(Operation::Delete, "cd".to_string()), diff_match_patch::Diff::new(Operation::Insert, "34".to_string())];
     dmp.diff_cleanup_efficiency(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "xcd".to_string()), diff_match_patch::Diff::new(1, "12x34".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "xcd".to_string()), diff_match_patch::Diff::new(Operation::Insert, "12x34".to_string())], diffs);
 
     // Backpass elimination.
-    diffs = vec![diff_match_patch::Diff::new(-1, "ab".to_string()), diff_match_patch::Diff::new(1, "12".to_string()), diff_match_patch::Diff::new(0, "xy".to_string()), diff_match_patch::Diff::new(1, "34".to_string()), diff_match_patch::Diff::new(0, "z".to_string()), diff_match_patch::Diff::new(-1, "cd".to_string()), diff_match_patch::Diff::new(1, "56".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Delete, "ab".to_string()), diff_match_patch::Diff::new(Operation::Insert, "12".to_string()), diff_match_patch::Diff::new(Operation::Equal, "xy".to_string()), diff_match_patch::Diff::new(Operation::Insert, "34".to_string()), diff_match_patch::Diff::new(Operation::Equal, "z".to_string()), diff_match_patch::Diff::new(Operation::Delete, "cd".to_string()), diff_match_patch::Diff::new(Operation::Insert, "56".to_string())];
     dmp.diff_cleanup_efficiency(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "abxyzcd".to_string()), diff_match_patch::Diff::new(1, "12xy34z56".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "abxyzcd".to_string()), diff_match_patch::Diff::new(Operation::Insert, "12xy34z56".to_string())], diffs);
 
     // High cost elimination.
     dmp.edit_cost = 5;
-    diffs = vec![diff_match_patch::Diff::new(-1, "ab".to_string()), diff_match_patch::Diff::new(1, "12".to_string()), diff_match_patch::Diff::new(0, "wxyz".to_string()), diff_match_patch::Diff::new(-1, "cd".to_string()), diff_match_patch::Diff::new(1, "34".to_string())];
+    diffs = vec![diff_match_patch::Diff::new(Operation::Delete, "ab".to_string()), diff_match_patch::Diff::new(Operation::Insert, "12".to_string()), diff_match_patch::Diff::new(Operation::Equal, "wxyz".to_string()), diff_match_patch::Diff::new(Operation::Delete, "cd".to_string()), diff_match_patch::Diff::new(Operation::Insert, "34".to_string())];
     dmp.diff_cleanup_efficiency(&mut diffs);
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "abwxyzcd".to_string()), diff_match_patch::Diff::new(1, "12wxyz34".to_string())], diffs);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "abwxyzcd".to_string()), diff_match_patch::Diff::new(Operation::Insert, "12wxyz34".to_string())], diffs);
 }
 
 
 #[test]
 pub fn test_diff_text() {
-    let mut dmp = diff_match_patch::Dmp::new();
-    let mut diffs: Vec<diff_match_patch::Diff> = vec![diff_match_patch::Diff::new(0, "jump".to_string()), diff_match_patch::Diff::new(-1, "s".to_string()), diff_match_patch::Diff::new(1, "ed".to_string()), diff_match_patch::Diff::new(0, " over ".to_string()), diff_match_patch::Diff::new(-1, "the".to_string()), diff_match_patch::Diff::new(1, "a".to_string()), diff_match_patch::Diff::new(0, " lazy".to_string())];
-    assert_eq!("jumps over the lazy".to_string(), dmp.diff_text1(&mut diffs));
-    assert_eq!("jumped over a lazy".to_string(), dmp.diff_text2(&mut diffs));
+    let dmp = diff_match_patch::Dmp::new();
+    let diffs: Vec<diff_match_patch::Diff> = vec![diff_match_patch::Diff::new(Operation::Equal, "jump".to_string()), diff_match_patch::Diff::new(Operation::Delete, "s".to_string()), diff_match_patch::Diff::new(Operation::Insert, "ed".to_string()), diff_match_patch::Diff::new(Operation::Equal, " over ".to_string()), diff_match_patch::Diff::new(Operation::Delete, "the".to_string()), diff_match_patch::Diff::new(Operation::Insert, "a".to_string()), diff_match_patch::Diff::new(Operation::Equal, " lazy".to_string())];
+    assert_eq!("jumps over the lazy".to_string(), dmp.diff_text1(&diffs));
+    assert_eq!("jumped over a lazy".to_string(), dmp.diff_text2(&diffs));
 }
 
 #[test]
  pub fn test_diff_text2_u16() {
      let dmp = diff_match_patch::Dmp::new();
      assert_eq!(
-         dmp.diff_text2_from_delta_u16("🅰", "-2\t+%F0%9F%85%B1"), 
-         dmp.diff_text2_from_delta_u16("🅰", "=1\t-1\t+%ED%B5%B1")
+         dmp.diff_text2_from_delta_u16("🅰", "-2\t+%F0%9F%85%B1").unwrap(), 
+         dmp.diff_text2_from_delta_u16("🅰", "=1\t-1\t+%ED%B5%B1").unwrap()
      );
  }
 
 #[test]
 pub fn test_diff_delta() {
 
-    let mut dmp = diff_match_patch::Dmp::new();
-    let mut diffs = vec![diff_match_patch::Diff::new(0, "jump".to_string()), diff_match_patch::Diff::new(-1, "s".to_string()), diff_match_patch::Diff::new(1, "ed".to_string()), diff_match_patch::Diff::new(0, " over ".to_string()), diff_match_patch::Diff::new(-1, "the".to_string()), diff_match_patch::Diff::new(1, "a".to_string()), diff_match_patch::Diff::new(0, " lazy".to_string()), diff_match_patch::Diff::new(1, "old dog".to_string())];
-    let mut text1 = dmp.diff_text1(&mut diffs);
+    let dmp = diff_match_patch::Dmp::new();
+    let mut diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "jump".to_string()), diff_match_patch::Diff::new(Operation::Delete, "s".to_string()), diff_match_patch::Diff::new(Operation::Insert, "ed".to_string()), diff_match_patch::Diff::new(Operation::Equal, " over ".to_string()), diff_match_patch::Diff::new(Operation::Delete, "the".to_string()), diff_match_patch::Diff::new(Operation::Insert, "a".to_string()), diff_match_patch::Diff::new(Operation::Equal, " lazy".to_string()), diff_match_patch::Diff::new(Operation::Insert, "old dog".to_string())];
+    let mut text1 = dmp.diff_text1(&diffs);
     assert_eq!("jumps over the lazy".to_string(), text1);
-    let mut delta = dmp.diff_todelta(&mut diffs);
+    let mut delta = dmp.diff_todelta(&diffs);
     assert_eq!("=4\t-1\t+ed\t=6\t-3\t+a\t=5\t+old dog".to_string(), delta);
 
     // Convert delta string into a diff.
-    assert_eq!(diffs, dmp.diff_from_delta(&text1, &delta));
+    assert_eq!(diffs, dmp.diff_from_delta(&text1, &delta).unwrap());
 
-    // # Generates error (19 != 20).
-    // try:
-    //     self.dmp.diff_fromDelta(text1 + "x", delta)
-    //     self.assertFalse(True)
+    // Generates error (19 != 20).
+    assert_eq!(Err(diff_match_patch::DmpError::LengthMismatch { line: 1, column: 31, expected: 20, actual: 19 }), dmp.diff_from_delta(&(text1.clone() + "x"), &delta));
+
+    // Generates error (19 != 18).
+    assert_eq!(Err(diff_match_patch::DmpError::LengthMismatch { line: 1, column: 20, expected: 18, actual: 19 }), dmp.diff_from_delta(&text1[1..], &delta));
+
+    // Generates error (%c3%xy invalid Unicode).
+    assert_eq!(Err(diff_match_patch::DmpError::BadEscape { line: 1, column: 5 }), dmp.diff_from_delta("", "+%c3%xy"));
+    assert_eq!(Err(diff_match_patch::DmpError::InvalidUtf8 { line: 1, column: 2 }), dmp.diff_from_delta("", "+%c3%28"));
+    assert_eq!(Err(diff_match_patch::DmpError::BadEscape { line: 1, column: 2 }), dmp.diff_text2_from_delta_u16("", "+%c"));
+
+    // Counts too large to add up.
+    assert_eq!(Err(diff_match_patch::DmpError::LengthMismatch { line: 1, column: 4, expected: 2, actual: usize::MAX }), dmp.diff_from_delta("ab", "=1\t=18446744073709551615"));
+    assert_eq!(Err(diff_match_patch::DmpError::LengthMismatch { line: 1, column: 4, expected: 2, actual: usize::MAX }), dmp.diff_text2_from_delta_u16("ab", "=1\t=18446744073709551615"));
+
+    // Unknown operations and lengths.
+    assert_eq!(Err(diff_match_patch::DmpError::BadLinePrefix { line: 1, column: 4, prefix: '*' }), dmp.diff_from_delta("abc", "=3\t*1"));
+    assert_eq!(Err(diff_match_patch::DmpError::BadNumber { line: 1, column: 2 }), dmp.diff_from_delta("abc", "=x"));
     // except ValueError:
     //     # Exception expected.
     //     pass
@@ -469,47 +494,52 @@ pub fn test_diff_delta() {
     // #  pass
 
     // Test deltas with special characters.
-    diffs = vec![diff_match_patch::Diff::new(0, "\u{0680} \x00 \t %".to_string()), diff_match_patch::Diff::new(-1, "\u{0681} \x01 \n ^".to_string()), diff_match_patch::Diff::new(1, "\u{0682} \x02 \\ |".to_string())];
-    text1 = dmp.diff_text1(&mut diffs);
+    diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "\u{0680} \x00 \t %".to_string()), diff_match_patch::Diff::new(Operation::Delete, "\u{0681} \x01 \n ^".to_string()), diff_match_patch::Diff::new(Operation::Insert, "\u{0682} \x02 \\ |".to_string())];
+    text1 = dmp.diff_text1(&diffs);
     assert_eq!("\u{0680} \x00 \t %\u{0681} \x01 \n ^".to_string(), text1);
 
-    delta = dmp.diff_todelta(&mut diffs);
+    delta = dmp.diff_todelta(&diffs);
     assert_eq!("=7\t-7\t+%DA%82 %02 %5C %7C".to_string(), delta);
     // Convert delta string into a diff.
-    assert_eq!(diffs, dmp.diff_from_delta(&text1, &delta));
+    assert_eq!(diffs, dmp.diff_from_delta(&text1, &delta).unwrap());
 
     // Verify pool of unchanged characters.
-    diffs = vec![diff_match_patch::Diff::new(1, "A-Z a-z 0-9 - _ . ! ~ * ' ( ) ; / ? : @ & = + $ , # ".to_string())];
-    let text2 = dmp.diff_text2(&mut diffs);
+    diffs = vec![diff_match_patch::Diff::new(Operation::Insert, "A-Z a-z 0-9 - _ . ! ~ * ' ( ) ; / ? : @ & = + $ , # ".to_string())];
+    let text2 = dmp.diff_text2(&diffs);
     assert_eq!("A-Z a-z 0-9 - _ . ! ~ * \' ( ) ; / ? : @ & = + $ , # ".to_string(), text2);
 
-    delta = dmp.diff_todelta(&mut diffs);
+    delta = dmp.diff_todelta(&diffs);
     assert_eq!("+A-Z a-z 0-9 - _ . ! ~ * \' ( ) ; / ? : @ & = + $ , # ".to_string(), delta);
 
     // Convert delta string into a diff.
-    assert_eq!(diffs, dmp.diff_from_delta(&("".to_string()), &delta));
+    assert_eq!(diffs, dmp.diff_from_delta("", &delta).unwrap());
+
+    // Percent signs are escaped.
+    diffs = vec![diff_match_patch::Diff::new(Operation::Insert, "100% %41".to_string())];
+    delta = dmp.diff_todelta(&diffs);
+    assert_eq!("+100%25 %2541".to_string(), delta);
+    assert_eq!(diffs, dmp.diff_from_delta("", &delta).unwrap());
 
     // 160 kb string.
     let mut a = "abcdefghij".to_string();
     for _i in 0..14 {
         a += a.clone().as_str();
     }
-    diffs = vec![diff_match_patch::Diff::new(1, a.clone())];
-    delta = dmp.diff_todelta(&mut diffs);
+    diffs = vec![diff_match_patch::Diff::new(Operation::Insert, a.clone())];
+    delta = dmp.diff_todelta(&diffs);
     assert_eq!('+'.to_string() + a.as_str(), delta);
 
     // Convert delta string into a diff.
-    assert_eq!(diffs, dmp.diff_from_delta(&"".to_string(), &delta));
+    assert_eq!(diffs, dmp.diff_from_delta("", &delta).unwrap());
 
     // Emoji
     diffs = dmp.diff_main("☺️🖖🏿", "☺️😃🖖🏿", false);
-    delta = dmp.diff_todelta_unit(&mut diffs, diff_match_patch::LengthUnit::UTF16);
+    delta = dmp.diff_todelta_unit(&diffs, diff_match_patch::LengthUnit::UTF16);
     assert_eq!(delta, "=2\t+%F0%9F%98%83\t=4");
 
     diffs = dmp.diff_main("☺️🖖🏿", "☺️😃🖖🏿", false);
-    let mut patches = dmp.patch_make2(&mut diffs);
-    let (patched_text_vec, _) = dmp.patch_apply(&mut patches, "☺️🖖🏿");
-    let patched_text: String = patched_text_vec.into_iter().collect();
+    let patches = dmp.patch_make2(&diffs);
+    let (patched_text, _) = dmp.patch_apply(&patches, "☺️🖖🏿");
     assert_eq!(patched_text, "☺️😃🖖🏿");
 }
 
@@ -520,125 +550,1100 @@ pub fn test_diff_delta() {
      // Inserting similar surrogate pair at beginning
      let mut diffs = dmp.diff_main("🅰🅱", "🅱🅰🅱", false);
      let mut expected_diffs = vec![
-         diff_match_patch::Diff::new(1, "🅱".to_string()),
-         diff_match_patch::Diff::new(0, "🅰🅱".to_string()),
+         diff_match_patch::Diff::new(Operation::Insert, "🅱".to_string()),
+         diff_match_patch::Diff::new(Operation::Equal, "🅰🅱".to_string()),
      ];
      assert_eq!(diffs, expected_diffs);
 
      // Inserting similar surrogate pair in the middle
      diffs = dmp.diff_main("🅱🅱", "🅱🅰🅱", false);
      expected_diffs = vec![
-         diff_match_patch::Diff::new(0, "🅱".to_string()),
-         diff_match_patch::Diff::new(1, "🅰".to_string()),
-         diff_match_patch::Diff::new(0, "🅱".to_string()),
+         diff_match_patch::Diff::new(Operation::Equal, "🅱".to_string()),
+         diff_match_patch::Diff::new(Operation::Insert, "🅰".to_string()),
+         diff_match_patch::Diff::new(Operation::Equal, "🅱".to_string()),
      ];
      assert_eq!(diffs, expected_diffs);
 
      // Deleting similar surrogate pair at the beginning
      diffs = dmp.diff_main("🅱🅰🅱", "🅰🅱", false);
      expected_diffs = vec![
-         diff_match_patch::Diff::new(-1, "🅱".to_string()),
-         diff_match_patch::Diff::new(0, "🅰🅱".to_string()),
+         diff_match_patch::Diff::new(Operation::Delete, "🅱".to_string()),
+         diff_match_patch::Diff::new(Operation::Equal, "🅰🅱".to_string()),
      ];
      assert_eq!(diffs, expected_diffs);
 
      // Deleting similar surrogate pair in the middle
      diffs = dmp.diff_main("🅰🅲🅱", "🅰🅱", false);
      expected_diffs = vec![
-         diff_match_patch::Diff::new(0, "🅰".to_string()),
-         diff_match_patch::Diff::new(-1, "🅲".to_string()),
-         diff_match_patch::Diff::new(0, "🅱".to_string()),
+         diff_match_patch::Diff::new(Operation::Equal, "🅰".to_string()),
+         diff_match_patch::Diff::new(Operation::Delete, "🅲".to_string()),
+         diff_match_patch::Diff::new(Operation::Equal, "🅱".to_string()),
      ];
      assert_eq!(diffs, expected_diffs);
 
      // Swapping surrogate pairs
      diffs = dmp.diff_main("🅰", "🅱", false);
      expected_diffs = vec![
-         diff_match_patch::Diff::new(-1, "🅰".to_string()),
-         diff_match_patch::Diff::new(1, "🅱".to_string()),
+         diff_match_patch::Diff::new(Operation::Delete, "🅰".to_string()),
+         diff_match_patch::Diff::new(Operation::Insert, "🅱".to_string()),
      ];
      assert_eq!(diffs, expected_diffs);
  }
 
  #[test]
  pub fn test_diff_to_delta_unit() {
-     let mut dmp = diff_match_patch::Dmp::new();
+     let dmp = diff_match_patch::Dmp::new();
 
      // UTF16
-     let mut diffs = dmp.diff_main("🅰", "🅱", false);
-     let mut delta = dmp.diff_todelta_unit(&mut diffs, diff_match_patch::LengthUnit::UTF16);
+     let diffs = dmp.diff_main("🅰", "🅱", false);
+     let mut delta = dmp.diff_todelta_unit(&diffs, diff_match_patch::LengthUnit::UTF16);
      assert_eq!(delta, "-2\t+%F0%9F%85%B1");
 
      // Scalar
-     let mut diffs = dmp.diff_main("🅰", "🅱", false);
-     delta = dmp.diff_todelta_unit(&mut diffs, diff_match_patch::LengthUnit::UnicodeScalar);
+     let diffs = dmp.diff_main("🅰", "🅱", false);
+     delta = dmp.diff_todelta_unit(&diffs, diff_match_patch::LengthUnit::UnicodeScalar);
      assert_eq!(delta, "-1\t+%F0%9F%85%B1");
  }
 
  #[test]
  pub fn test_diff_from_delta_unit() {
-     let mut dmp = diff_match_patch::Dmp::new();
+     let dmp = diff_match_patch::Dmp::new();
 
      // UTF16
      let mut delta = "-2\t=2\t+%F0%9F%85%B1";
-     let mut diffs = dmp.diff_from_delta_unit("🅰🅲", delta, diff_match_patch::LengthUnit::UTF16);
-     assert_eq!(dmp.diff_text2(&mut diffs), "🅲🅱");
+     let mut diffs = dmp.diff_from_delta_unit("🅰🅲", delta, diff_match_patch::LengthUnit::UTF16).unwrap();
+     assert_eq!(dmp.diff_text2(&diffs), "🅲🅱");
 
      // Scalar
      delta = "-1\t=1\t+%F0%9F%85%B1";
-     diffs = dmp.diff_from_delta_unit("🅰🅲", delta, diff_match_patch::LengthUnit::UnicodeScalar);
-     assert_eq!(dmp.diff_text2(&mut diffs), "🅲🅱");
+     diffs = dmp.diff_from_delta_unit("🅰🅲", delta, diff_match_patch::LengthUnit::UnicodeScalar).unwrap();
+     assert_eq!(dmp.diff_text2(&diffs), "🅲🅱");
+
+     // Half a surrogate pair escaped in an insertion.
+     diffs = dmp.diff_from_delta_unit("🅰", "=1\t-1\t+%ED%B5%B1", diff_match_patch::LengthUnit::UTF16).unwrap();
+     assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "🅰".to_string()), diff_match_patch::Diff::new(Operation::Insert, "🅱".to_string())], diffs);
+     assert_eq!(Err(diff_match_patch::DmpError::BadEscape { line: 1, column: 2 }), dmp.diff_from_delta_unit("", "+%c", diff_match_patch::LengthUnit::UTF16));
  }
 
  #[test]
  pub fn test_diff_from_delta_split_surrogates() {
-     let mut dmp = diff_match_patch::Dmp::new();
+     let dmp = diff_match_patch::Dmp::new();
 
      assert_eq!(
-         dmp.diff_from_delta_unit("🅰", "-2\t+%F0%9F%85%B1", diff_match_patch::LengthUnit::UTF16), 
-         dmp.diff_from_delta_unit("🅰", "=1\t-1\t+%ED%B5%B1", diff_match_patch::LengthUnit::UTF16)
+         dmp.diff_from_delta_unit("🅰", "-2\t+%F0%9F%85%B1", diff_match_patch::LengthUnit::UTF16).unwrap(), 
+         dmp.diff_from_delta_unit("🅰", "=1\t-1\t+%ED%B5%B1", diff_match_patch::LengthUnit::UTF16).unwrap()
      );
  }
 
 #[test]
 pub fn test_diff_xindex() {
-    let mut dmp = diff_match_patch::Dmp::new();
+    let dmp = diff_match_patch::Dmp::new();
 
     // Translate a location in text1 to text2.
     let mut diffs = vec![
-        diff_match_patch::Diff::new(-1, "a".to_string()),
-        diff_match_patch::Diff::new(1, "1234".to_string()),
-        diff_match_patch::Diff::new(0, "xyz".to_string()),
+        diff_match_patch::Diff::new(Operation::Delete, "a".to_string()),
+        diff_match_patch::Diff::new(Operation::Insert, "1234".to_string()),
+        diff_match_patch::Diff::new(Operation::Equal, "xyz".to_string()),
     ];
     assert_eq!(5, dmp.diff_xindex(&diffs, 2));
     
     // Translation on deletion.
     diffs = vec![
-        diff_match_patch::Diff::new(0, "a".to_string()),
-        diff_match_patch::Diff::new(-1, "1234".to_string()),
-        diff_match_patch::Diff::new(0, "xyz".to_string()),
+        diff_match_patch::Diff::new(Operation::Equal, "a".to_string()),
+        diff_match_patch::Diff::new(Operation::Delete, "1234".to_string()),
+        diff_match_patch::Diff::new(Operation::Equal, "xyz".to_string()),
     ];
     assert_eq!(1, dmp.diff_xindex(&diffs, 3));
 }
 
+#[test]
+pub fn test_diff_pretty_html() {
+    let dmp = diff_match_patch::Dmp::new();
+    let diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "a\n".to_string()), diff_match_patch::Diff::new(Operation::Delete, "<B>b</B>".to_string()), diff_match_patch::Diff::new(Operation::Insert, "c&d".to_string())];
+    assert_eq!("<span>a&para;<br></span><del style=\"background:#ffe6e6;\">&lt;B&gt;b&lt;/B&gt;</del><ins style=\"background:#e6ffe6;\">c&amp;d</ins>", dmp.diff_pretty_html(&diffs));
+
+    // Class names instead of inline styles.
+    let mut html = diff_match_patch::HtmlFormatter::with_classes("added", "removed", "same");
+    html.show_paragraphs = false;
+    assert_eq!("<span class=\"same\">a\n</span><del class=\"removed\">&lt;B&gt;b&lt;/B&gt;</del><ins class=\"added\">c&amp;d</ins>", html.format(&diffs));
+}
+
+#[test]
+pub fn test_diff_formatters() {
+    let diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "The ".to_string()), diff_match_patch::Diff::new(Operation::Delete, "cat".to_string()), diff_match_patch::Diff::new(Operation::Insert, "big\ndog".to_string()), diff_match_patch::Diff::new(Operation::Equal, " ran.".to_string())];
+    assert_eq!("The [-cat-]{+big\ndog+} ran.", diff_match_patch::WordDiffFormatter::new().format(&diffs));
+    assert_eq!("The \x1b[31mcat\x1b[0m\x1b[32mbig\x1b[0m\n\x1b[32mdog\x1b[0m ran.", diff_match_patch::AnsiFormatter::new().format(&diffs));
+
+    // A custom formatter only has to render single diffs.
+    struct Upper;
+    impl DiffFormatter for Upper {
+        fn write_diff(&self, out: &mut String, operation: Operation, text: &str) {
+            if operation != Operation::Delete {
+                *out += text.to_uppercase().as_str();
+            }
+        }
+    }
+    assert_eq!("THE BIG\nDOG RAN.", Upper.format(&diffs));
+}
+
 #[test]
 pub fn test_diff_levenshtein() {
 
-    let mut dmp = diff_match_patch::Dmp::new();
-    assert_eq!(4, dmp.diff_levenshtein(&mut vec![diff_match_patch::Diff::new(-1, "abc".to_string()), diff_match_patch::Diff::new(1, "1234".to_string()), diff_match_patch::Diff::new(0, "xyz".to_string())]));
+    let dmp = diff_match_patch::Dmp::new();
+    assert_eq!(4, dmp.diff_levenshtein(&[diff_match_patch::Diff::new(Operation::Delete, "abc".to_string()), diff_match_patch::Diff::new(Operation::Insert, "1234".to_string()), diff_match_patch::Diff::new(Operation::Equal, "xyz".to_string())]));
     // Levenshtein with leading equality.
-    assert_eq!(4, dmp.diff_levenshtein(&mut vec![diff_match_patch::Diff::new(0, "xyz".to_string()), diff_match_patch::Diff::new(-1, "abc".to_string()), diff_match_patch::Diff::new(1, "1234".to_string())]));
+    assert_eq!(4, dmp.diff_levenshtein(&[diff_match_patch::Diff::new(Operation::Equal, "xyz".to_string()), diff_match_patch::Diff::new(Operation::Delete, "abc".to_string()), diff_match_patch::Diff::new(Operation::Insert, "1234".to_string())]));
     // # Levenshtein with middle equality.
-    assert_eq!(7, dmp.diff_levenshtein(&mut vec![diff_match_patch::Diff::new(-1, "abc".to_string()), diff_match_patch::Diff::new(0, "xyz".to_string()), diff_match_patch::Diff::new(1, "1234".to_string())]));
+    assert_eq!(7, dmp.diff_levenshtein(&[diff_match_patch::Diff::new(Operation::Delete, "abc".to_string()), diff_match_patch::Diff::new(Operation::Equal, "xyz".to_string()), diff_match_patch::Diff::new(Operation::Insert, "1234".to_string())]));
 }
 
 
 #[test]
 pub fn test_diff_bisect() {
-    let mut dmp = diff_match_patch::Dmp::new();
+    let dmp = diff_match_patch::Dmp::new();
     let a = "cat".to_string();
     let b = "map".to_string();
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "c".to_string()), diff_match_patch::Diff::new(1, "m".to_string()), diff_match_patch::Diff::new(0, "a".to_string()), diff_match_patch::Diff::new(-1, "t".to_string()), diff_match_patch::Diff::new(1, "p".to_string())] , dmp.diff_bisect(&a.chars().collect::<Vec<_>>(), &b.chars().collect::<Vec<_>>()));
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "c".to_string()), diff_match_patch::Diff::new(Operation::Insert, "m".to_string()), diff_match_patch::Diff::new(Operation::Equal, "a".to_string()), diff_match_patch::Diff::new(Operation::Delete, "t".to_string()), diff_match_patch::Diff::new(Operation::Insert, "p".to_string())] , dmp.diff_bisect(&a.chars().collect::<Vec<_>>(), &b.chars().collect::<Vec<_>>()));
+}
+
+#[test]
+pub fn test_diff_tokens() {
+    let dmp = diff_match_patch::Dmp::new();
+    // Null case.
+    let empty: Vec<diff_match_patch::DiffOp<u32>> = vec![];
+    assert_eq!(empty, dmp.diff_tokens::<u32>(&[], &[]));
+
+    // Arbitrary ids, well past the range a char could hold.
+    let big = 0x0011_0000;
+    assert_eq!(vec![diff_match_patch::DiffOp::new(Operation::Equal, vec![1]), diff_match_patch::DiffOp::new(Operation::Delete, vec![2]), diff_match_patch::DiffOp::new(Operation::Equal, vec![3, 4]), diff_match_patch::DiffOp::new(Operation::Insert, vec![big])],
+               dmp.diff_tokens(&[1, 2, 3, 4], &[1, 3, 4, big]));
+
+    // Whole words.
+    let words1 = vec!["the", "cat", "sat", "on", "the", "mat"];
+    let words2 = vec!["the", "dog", "sat", "on", "the", "mat"];
+    assert_eq!(vec![diff_match_patch::DiffOp::new(Operation::Equal, vec!["the"]), diff_match_patch::DiffOp::new(Operation::Delete, vec!["cat"]), diff_match_patch::DiffOp::new(Operation::Insert, vec!["dog"]), diff_match_patch::DiffOp::new(Operation::Equal, vec!["sat", "on", "the", "mat"])],
+               dmp.diff_tokens(&words1, &words2));
+
+    // Ranges.
+    assert_eq!(vec![
+        diff_match_patch::DiffRange { operation: Operation::Equal, range1: 0..1, range2: 0..1 },
+        diff_match_patch::DiffRange { operation: Operation::Delete, range1: 1..2, range2: 1..1 },
+        diff_match_patch::DiffRange { operation: Operation::Insert, range1: 2..2, range2: 1..2 },
+        diff_match_patch::DiffRange { operation: Operation::Equal, range1: 2..6, range2: 2..6 }
+    ], dmp.diff_token_ranges(&words1, &words2));
+
+    // The char API agrees with the token engine.
+    let text1: Vec<char> = "The cat sat.".chars().collect();
+    let text2: Vec<char> = "A dog sat down.".chars().collect();
+    let diffs: Vec<diff_match_patch::Diff> = dmp.diff_tokens(&text1, &text2).into_iter().map(diff_match_patch::Diff::from).collect();
+    assert_eq!(dmp.diff_main("The cat sat.", "A dog sat down.", false), diffs);
+}
+
+#[test]
+pub fn test_diff_patience() {
+    let mut dmp = diff_match_patch::Dmp::new();
+    assert_eq!(diff_match_patch::DiffAlgorithm::Myers, dmp.diff_algorithm);
+
+    // Myers keeps the longest run of repeated tokens.
+    let tokens1 = vec!["a", "}", "}", "}", "b"];
+    let tokens2 = vec!["b", "}", "}", "}", "a"];
+    assert_eq!(vec![diff_match_patch::DiffOp::new(Operation::Delete, vec!["a"]), diff_match_patch::DiffOp::new(Operation::Insert, vec!["b"]), diff_match_patch::DiffOp::new(Operation::Equal, vec!["}", "}", "}"]), diff_match_patch::DiffOp::new(Operation::Delete, vec!["b"]), diff_match_patch::DiffOp::new(Operation::Insert, vec!["a"])],
+               dmp.diff_tokens(&tokens1, &tokens2));
+
+    // Patience anchors on a token unique to both sides instead.
+    dmp.diff_algorithm = diff_match_patch::DiffAlgorithm::Patience;
+    assert_eq!(vec![diff_match_patch::DiffOp::new(Operation::Delete, vec!["a", "}", "}", "}"]), diff_match_patch::DiffOp::new(Operation::Equal, vec!["b"]), diff_match_patch::DiffOp::new(Operation::Insert, vec!["}", "}", "}", "a"])],
+               dmp.diff_tokens(&tokens1, &tokens2));
+
+    // No unique tokens, fall back to bisect.
+    let tokens1 = vec!["a", "a", "b", "b"];
+    let tokens2 = vec!["b", "b", "a", "a"];
+    let patience = dmp.diff_tokens(&tokens1, &tokens2);
+    dmp.diff_algorithm = diff_match_patch::DiffAlgorithm::Myers;
+    assert_eq!(dmp.diff_tokens(&tokens1, &tokens2), patience);
+
+    // Char mode.
+    dmp.diff_algorithm = diff_match_patch::DiffAlgorithm::Patience;
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "a".to_string()), diff_match_patch::Diff::new(Operation::Equal, "b".to_string()), diff_match_patch::Diff::new(Operation::Insert, "a".to_string())],
+               dmp.diff_main("ab", "ba", false));
+
+    // Line mode.
+    let text1 = "fn a() {\n    one();\n}\n\nfn b() {\n    two();\n}\n\nfn c() {\n    three();\n}\n".repeat(3);
+    let text2 = text1.replacen("fn b() {\n    two();\n}\n\n", "", 1).replace("three", "four");
+    let diffs = dmp.diff_main(&text1, &text2, true);
+    assert_eq!(text1, dmp.diff_text1(&diffs));
+    assert_eq!(text2, dmp.diff_text2(&diffs));
+}
+
+#[test]
+pub fn test_diff_histogram() {
+    let mut dmp = diff_match_patch::Dmp::new();
+    dmp.diff_algorithm = diff_match_patch::DiffAlgorithm::Histogram;
+
+    // The rarest region anchors the diff, not the longest one.
+    let tokens1 = vec!["a", "}", "}", "}", "b"];
+    let tokens2 = vec!["b", "}", "}", "}", "a"];
+    assert_eq!(vec![diff_match_patch::DiffOp::new(Operation::Delete, vec!["a", "}", "}", "}"]), diff_match_patch::DiffOp::new(Operation::Equal, vec!["b"]), diff_match_patch::DiffOp::new(Operation::Insert, vec!["}", "}", "}", "a"])],
+               dmp.diff_tokens(&tokens1, &tokens2));
+
+    // Unlike patience, tokens don't have to be unique to anchor.
+    let tokens1 = vec!["x", "a", "x", "b", "b", "y"];
+    let tokens2 = vec!["y", "x", "a", "x", "z", "z"];
+    assert_eq!(vec![diff_match_patch::DiffOp::new(Operation::Insert, vec!["y"]), diff_match_patch::DiffOp::new(Operation::Equal, vec!["x", "a", "x"]), diff_match_patch::DiffOp::new(Operation::Delete, vec!["b", "b", "y"]), diff_match_patch::DiffOp::new(Operation::Insert, vec!["z", "z"])],
+               dmp.diff_tokens(&tokens1, &tokens2));
+
+    // Tokens too common to anchor on, fall back to bisect.
+    let tokens1 = vec![1; 100].into_iter().chain(vec![2; 100]).collect::<Vec<_>>();
+    let tokens2 = vec![2; 100].into_iter().chain(vec![1; 100]).collect::<Vec<_>>();
+    let histogram = dmp.diff_tokens(&tokens1, &tokens2);
+    dmp.diff_algorithm = diff_match_patch::DiffAlgorithm::Myers;
+    assert_eq!(dmp.diff_tokens(&tokens1, &tokens2), histogram);
+
+    // Char mode.
+    dmp.diff_algorithm = diff_match_patch::DiffAlgorithm::Histogram;
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "a".to_string()), diff_match_patch::Diff::new(Operation::Equal, "b".to_string()), diff_match_patch::Diff::new(Operation::Insert, "a".to_string())],
+               dmp.diff_main("ab", "ba", false));
+
+    // Line mode.
+    let text1 = "[server]\nport = 80\nhost = a\n\n[client]\nport = 81\nhost = b\n\n".repeat(4);
+    let text2 = text1.replacen("port = 81\n", "port = 8081\n", 1).replace("host = a\n\n", "host = c\n\n");
+    let diffs = dmp.diff_main(&text1, &text2, true);
+    assert_eq!(text1, dmp.diff_text1(&diffs));
+    assert_eq!(text2, dmp.diff_text2(&diffs));
+}
+
+#[test]
+pub fn test_diff_unified() {
+    let mut dmp = diff_match_patch::Dmp::new();
+    let text1 = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+    let text2 = "a\nb\nC\nd\ne\nf\ng\nh\ni\nj\nK\nl\nm";
+    // Null case.
+    assert_eq!("", dmp.diff_unified_text(text1, text1, "old", "new"));
+
+    // Same output as diff -u.
+    let unified = "--- old\n+++ new\n@@ -1,6 +1,6 @@\n a\n b\n-c\n+C\n d\n e\n f\n@@ -8,5 +8,6 @@\n h\n i\n j\n-k\n+K\n l\n+m\n\\ No newline at end of file\n";
+    assert_eq!(unified, dmp.diff_unified_text(text1, text2, "old", "new"));
+
+    // Char diffs are widened to whole lines.
+    let diffs = dmp.diff_main(text1, text2, false);
+    assert_eq!(unified, dmp.diff_unified(&diffs, "old", "new"));
+
+    // Context lines.
+    dmp.unified_context = 1;
+    assert_eq!("--- old\n+++ new\n@@ -2,3 +2,3 @@\n b\n-c\n+C\n d\n@@ -10,3 +10,4 @@\n j\n-k\n+K\n l\n+m\n\\ No newline at end of file\n",
+               dmp.diff_unified_text(text1, text2, "old", "new"));
+    dmp.unified_context = 10;
+    assert_eq!(1, dmp.diff_unified_hunks(&diffs).len());
+
+    // Empty ranges.
+    dmp.unified_context = 0;
+    assert_eq!("--- a\n+++ b\n@@ -0,0 +1 @@\n+y\n", dmp.diff_unified_text("x\n", "y\nx\n", "a", "b"));
+    assert_eq!(vec![diff_match_patch::UnifiedHunk { diffs: vec![diff_match_patch::Diff::new(Operation::Delete, "x\n".to_string())], start1: 0, start2: 0, length1: 1, length2: 0 }],
+               dmp.diff_unified_hunks(&dmp.diff_main("x\n", "", false)));
+}
+
+#[test]
+pub fn test_patch_invert() {
+    let dmp = diff_match_patch::Dmp::new();
+    // Null case.
+    assert!(dmp.patch_invert(&[]).is_empty());
+
+    let text1 = "The quick brown fox jumps over the lazy dog.";
+    let text2 = "That quick brown fox jumped over a lazy dog.";
+    let patches = dmp.patch_make1(text1, text2);
+    let inverted = dmp.patch_invert(&patches);
+    assert_eq!("@@ -1,12 +1,11 @@\n Th\n-at\n+e\n  quick b\n@@ -21,17 +21,18 @@\n jump\n-ed\n+s\n  over \n-a\n+the\n  laz\n", dmp.patch_to_text(&inverted));
+    assert_eq!((text1.to_string(), vec![true, true]), dmp.patch_apply(&inverted, text2));
+    // Every patch is found right where it says.
+    assert!(dmp.patch_apply_report(&inverted, text2).1.iter().all(|report| report.exact && report.delta == 0));
+
+    // Round trips.
+    assert_eq!(dmp.patch_to_text(&patches), dmp.patch_to_text(&dmp.patch_invert(&inverted)));
+    assert_eq!(dmp.patch_to_text(&inverted), dmp.patch_to_text(&dmp.patch_from_text(dmp.patch_to_text(&inverted)).unwrap()));
+
+    // Lengths and offsets trade places.
+    let patches = dmp.patch_make1("abc", "");
+    assert_eq!((0, 0, 0, 3), (patches[0].start1, patches[0].start2, patches[0].length2, patches[0].length1));
+    let inverted = dmp.patch_invert(&patches);
+    assert_eq!((3, 0), (inverted[0].length2, inverted[0].length1));
+    assert_eq!(("abc".to_string(), vec![true]), dmp.patch_apply(&inverted, ""));
+}
+
+#[test]
+pub fn test_patch_compose() {
+    let dmp = diff_match_patch::Dmp::new();
+    let text1 = "The quick brown fox jumps over the lazy dog.";
+    let text2 = "That quick brown fox jumped over a lazy dog.";
+    let text3 = "That quick red fox jumped over a lazy dog!";
+    let patches1 = dmp.patch_make1(text1, text2);
+    let patches2 = dmp.patch_make1(text2, text3);
+
+    // Null cases.
+    assert!(dmp.patch_compose(&[], &[]).is_empty());
+    assert_eq!(dmp.patch_to_text(&patches1), dmp.patch_to_text(&dmp.patch_compose(&patches1, &[])));
+    assert_eq!(dmp.patch_to_text(&patches2), dmp.patch_to_text(&dmp.patch_compose(&[], &patches2)));
+
+    // Edits in different places.
+    let patches = dmp.patch_compose(&patches1, &patches2);
+    assert_eq!((text3.to_string(), vec![true; patches.len()]), dmp.patch_apply(&patches, text1));
+    assert!(dmp.patch_apply_report(&patches, text1).1.iter().all(|report| report.exact && report.delta == 0));
+
+    // Edits of edits.
+    let text3 = "Those quick brown foxes jumped over a lazy dog.";
+    let patches = dmp.patch_compose(&patches1, &dmp.patch_make1(text2, text3));
+    assert_eq!("@@ -1,38 +1,41 @@\n Th\n+os\n e quick brown fox\n+es\n  jump\n-s\n+ed\n  over \n-the\n+a\n  laz\n", dmp.patch_to_text(&patches));
+    assert_eq!((text3.to_string(), vec![true, true]), dmp.patch_apply(&patches, text1));
+
+    // A patch followed by its inverse changes nothing.
+    assert!(dmp.patch_compose(&patches1, &dmp.patch_invert(&patches1)).is_empty());
+
+    // Long histories.
+    let versions = ["", "abc", "abcdef", "xyzabcdef", "xyzabXdef", "xyz", "hello xyz world"];
+    let mut squashed: Vec<diff_match_patch::Patch> = vec![];
+    for pair in versions.windows(2) {
+        squashed = dmp.patch_compose(&squashed, &dmp.patch_make1(pair[0], pair[1]));
+        assert_eq!(pair[1].to_string(), dmp.patch_apply(&squashed, "").0);
+    }
+}
+
+#[test]
+pub fn test_diff_transform() {
+    let dmp = diff_match_patch::Dmp::new();
+    // Null case.
+    assert_eq!((vec![], vec![]), dmp.diff_transform(&[], &[]));
+
+    // Edits in different places.
+    let base = "The quick brown fox jumps over the lazy dog.";
+    let text_a = "The quick red fox jumps over the lazy dog.";
+    let text_b = "The quick brown fox jumped over a lazy dog.";
+    let (a_prime, b_prime) = dmp.diff_transform(&dmp.diff_main(base, text_a, false), &dmp.diff_main(base, text_b, false));
+    assert_eq!(text_b, dmp.diff_text1(&a_prime));
+    assert_eq!(text_a, dmp.diff_text1(&b_prime));
+    assert_eq!("The quick red fox jumped over a lazy dog.", dmp.diff_text2(&a_prime));
+    assert_eq!("The quick red fox jumped over a lazy dog.", dmp.diff_text2(&b_prime));
+
+    // Insertions at the same position, a goes first.
+    let a = vec![diff_match_patch::Diff::new(Operation::Equal, "a".to_string()), diff_match_patch::Diff::new(Operation::Insert, "X".to_string()), diff_match_patch::Diff::new(Operation::Equal, "b".to_string())];
+    let b = vec![diff_match_patch::Diff::new(Operation::Equal, "a".to_string()), diff_match_patch::Diff::new(Operation::Insert, "Y".to_string()), diff_match_patch::Diff::new(Operation::Equal, "b".to_string())];
+    let (a_prime, b_prime) = dmp.diff_transform(&a, &b);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "a".to_string()), diff_match_patch::Diff::new(Operation::Insert, "X".to_string()), diff_match_patch::Diff::new(Operation::Equal, "Yb".to_string())], a_prime);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "aX".to_string()), diff_match_patch::Diff::new(Operation::Insert, "Y".to_string()), diff_match_patch::Diff::new(Operation::Equal, "b".to_string())], b_prime);
+    let (a_prime, b_prime) = dmp.diff_transform(&b, &a);
+    assert_eq!("aYXb", dmp.diff_text2(&a_prime));
+    assert_eq!("aYXb", dmp.diff_text2(&b_prime));
+
+    // Overlapping deletions.
+    let (a_prime, b_prime) = dmp.diff_transform(&dmp.diff_main("abcdef", "af", false), &dmp.diff_main("abcdef", "abZf", false));
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "a".to_string()), diff_match_patch::Diff::new(Operation::Delete, "b".to_string()), diff_match_patch::Diff::new(Operation::Equal, "Zf".to_string())], a_prime);
+    assert_eq!("aZf", dmp.diff_text2(&b_prime));
+
+    // Positions in the other side's text map through the transformed diff.
+    assert_eq!(2, dmp.diff_xindex(&a_prime, 3));
+}
+
+#[test]
+pub fn test_sync() {
+    let dmp = diff_match_patch::Dmp::new();
+    // Messages go through a local channel as text.
+    let (sender, receiver) = std::sync::mpsc::channel::<String>();
+    let send = |message: diff_match_patch::SyncMessage| sender.send(dmp.sync_message_to_text(&message)).unwrap();
+    let receive = || dmp.sync_message_from_text(&receiver.recv().unwrap()).unwrap();
+    let mut client_text = "The quick brown fox jumps over the lazy dog.".to_string();
+    let mut server_text = client_text.clone();
+    let mut client = diff_match_patch::SyncState::new(&client_text);
+    let mut server = diff_match_patch::SyncState::new(&server_text);
+
+    // Concurrent edits converge after one round trip.
+    client_text = "The quick red fox jumps over the lazy dog.".to_string();
+    server_text = "The quick brown fox jumped over a lazy dog.".to_string();
+    send(dmp.sync_make_message(&mut client, &client_text));
+    dmp.sync_receive_message(&mut server, &receive(), &mut server_text).unwrap();
+    send(dmp.sync_make_message(&mut server, &server_text));
+    dmp.sync_receive_message(&mut client, &receive(), &mut client_text).unwrap();
+    assert_eq!("The quick red fox jumped over a lazy dog.", client_text);
+    assert_eq!(client_text, server_text);
+    assert_eq!(client.shadow, server.shadow);
+    assert!(client.edits.is_empty());
+
+    // Lost answer: the server goes back to its backup.
+    client_text = "A quick red fox jumped over a lazy dog.".to_string();
+    send(dmp.sync_make_message(&mut client, &client_text));
+    dmp.sync_receive_message(&mut server, &receive(), &mut server_text).unwrap();
+    server_text = server_text.replace("dog", "cat");
+    dmp.sync_make_message(&mut server, &server_text);
+    client_text = client_text.replace("jumped", "jumps");
+    send(dmp.sync_make_message(&mut client, &client_text));
+    dmp.sync_receive_message(&mut server, &receive(), &mut server_text).unwrap();
+    send(dmp.sync_make_message(&mut server, &server_text));
+    dmp.sync_receive_message(&mut client, &receive(), &mut client_text).unwrap();
+    assert_eq!("A quick red fox jumps over a lazy cat.", client_text);
+    assert_eq!(client_text, server_text);
+
+    // Lost message: the client sends its stack again.
+    client_text = "A quick red fox leaps over a lazy cat.".to_string();
+    dmp.sync_make_message(&mut client, &client_text);
+    client_text = "A quick red fox leaps over a lazy cat!".to_string();
+    let message = dmp.sync_make_message(&mut client, &client_text);
+    assert_eq!(2, message.edits.len());
+    send(message);
+    dmp.sync_receive_message(&mut server, &receive(), &mut server_text).unwrap();
+    assert_eq!(client_text, server_text);
+
+    // Duplicate edits are skipped.
+    let message = dmp.sync_make_message(&mut client, &client_text);
+    dmp.sync_receive_message(&mut server, &message, &mut server_text).unwrap();
+    assert_eq!(client_text, server_text);
+
+    // Drifting shadows are caught, and a raw message resets the peer.
+    let mut message = dmp.sync_make_message(&mut server, &server_text);
+    message.checksum ^= 1;
+    assert_eq!(Err(diff_match_patch::DmpError::ChecksumMismatch { expected: message.checksum, actual: message.checksum ^ 1 }), dmp.sync_receive_message(&mut client, &message, &mut client_text));
+    client_text = "Garbled\ttext\n".to_string();
+    send(dmp.sync_make_raw_message(&mut server, &server_text));
+    dmp.sync_receive_message(&mut client, &receive(), &mut client_text).unwrap();
+    assert_eq!(server_text, client_text);
+    client_text = "A quick red fox leaps over a lazy cat?".to_string();
+    send(dmp.sync_make_message(&mut client, &client_text));
+    dmp.sync_receive_message(&mut server, &receive(), &mut server_text).unwrap();
+    assert_eq!(client_text, server_text);
+
+    // Text format.
+    let message = diff_match_patch::SyncMessage { ack: 3, edits: vec![diff_match_patch::SyncEdit::Delta { version: 7, delta: "=3\t+x".to_string() }, diff_match_patch::SyncEdit::Raw { version: 8, text: "a\nb%".to_string() }], checksum: 0xbeef };
+    assert_eq!("v:3:0000beef\nd:7:=3\t+x\nr:8:a%0Ab%25\n", dmp.sync_message_to_text(&message));
+    assert_eq!(Ok(message.clone()), dmp.sync_message_from_text(&dmp.sync_message_to_text(&message)));
+    let message = diff_match_patch::SyncMessage { ack: 0, edits: vec![diff_match_patch::SyncEdit::Raw { version: 1, text: "++a".to_string() }], checksum: 0 };
+    assert_eq!("v:0:00000000\nr:1:++a\n", dmp.sync_message_to_text(&message));
+    assert_eq!(Ok(message.clone()), dmp.sync_message_from_text(&dmp.sync_message_to_text(&message)));
+    assert_eq!(Err(diff_match_patch::DmpError::BadHeader { line: 1, column: 1 }), dmp.sync_message_from_text("d:1:=3\n"));
+    assert_eq!(Err(diff_match_patch::DmpError::BadLinePrefix { line: 2, column: 1, prefix: 'x' }), dmp.sync_message_from_text("v:0:0\nx:1:=3\n"));
+    assert_eq!(Err(diff_match_patch::DmpError::BadNumber { line: 2, column: 3 }), dmp.sync_message_from_text("v:0:0\nd:a:=3\n"));
+    assert_eq!(Err(diff_match_patch::DmpError::BadEscape { line: 2, column: 5 }), dmp.sync_message_from_text("v:0:0\nr:1:%x\n"));
+
+    // Messages out of order.
+    assert_eq!(Err(diff_match_patch::DmpError::VersionMismatch { expected: server.local_version, actual: 99 }), dmp.sync_receive_message(&mut server, &diff_match_patch::SyncMessage { ack: 99, edits: vec![], checksum: 0 }, &mut server_text));
+}
+
+#[test]
+pub fn test_diff_map_position() {
+    let dmp = diff_match_patch::Dmp::new();
+    let left = diff_match_patch::Bias::Left;
+    let right = diff_match_patch::Bias::Right;
+    let mapped = |position, deleted| diff_match_patch::MappedPosition { position, deleted };
+    // "The cat" -> "The big hat"
+    let diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "The ".to_string()), diff_match_patch::Diff::new(Operation::Insert, "big ".to_string()), diff_match_patch::Diff::new(Operation::Delete, "c".to_string()), diff_match_patch::Diff::new(Operation::Insert, "h".to_string()), diff_match_patch::Diff::new(Operation::Equal, "at".to_string())];
+
+    // Bias at insertions.
+    assert_eq!(mapped(4, false), dmp.diff_map_position(&diffs, 4, left));
+    assert_eq!(mapped(9, false), dmp.diff_map_position(&diffs, 5, left));
+    assert_eq!(mapped(2, false), dmp.diff_map_position(&diffs, 2, right));
+    assert_eq!(mapped(11, false), dmp.diff_map_position(&diffs, 7, right));
+    // Past the end.
+    assert_eq!(mapped(13, false), dmp.diff_map_position(&diffs, 9, left));
+
+    // Positions inside deleted text.
+    let diffs = dmp.diff_main("abcdef", "abf", false);
+    assert_eq!(mapped(2, false), dmp.diff_map_position(&diffs, 2, right));
+    assert_eq!(mapped(2, true), dmp.diff_map_position(&diffs, 3, left));
+    assert_eq!(mapped(2, true), dmp.diff_map_position(&diffs, 4, right));
+    assert_eq!(mapped(2, false), dmp.diff_map_position(&diffs, 5, left));
+
+    // Replaced text: bias picks an end.
+    let diffs = dmp.diff_main("a123b", "aXYb", false);
+    assert_eq!(vec![mapped(1, false), mapped(1, true), mapped(3, false), mapped(4, false)], dmp.diff_map_positions(&diffs, &[1, 2, 4, 5], left));
+    assert_eq!(vec![mapped(3, true), mapped(1, false)], dmp.diff_map_positions(&diffs, &[3, 1], right));
+
+    // Batches in any order agree with diff_xindex.
+    let text1 = "The quick brown fox jumps over the lazy dog.";
+    let text2 = "That quick brown fox jumped over a lazy dog.";
+    let diffs = dmp.diff_main(text1, text2, false);
+    let locs: Vec<usize> = (0..=text1.len()).rev().collect();
+    let positions = dmp.diff_map_positions(&diffs, &locs, left);
+    for (loc, position) in locs.iter().zip(&positions) {
+        if !position.deleted {
+            assert_eq!(dmp.diff_xindex(&diffs, *loc as i32) as usize, position.position);
+        }
+    }
+
+    // Reverse mapping.
+    let diffs = dmp.diff_main("abcdef", "abf", false);
+    assert_eq!(vec![mapped(0, false), mapped(2, false), mapped(6, false)], dmp.diff_unmap_positions(&diffs, &[0, 2, 3], left));
+    assert_eq!(vec![mapped(5, false)], dmp.diff_unmap_positions(&diffs, &[2], right));
+    let diffs = dmp.diff_main("abf", "abcdef", false);
+    assert_eq!(vec![mapped(2, true), mapped(2, false)], dmp.diff_unmap_positions(&diffs, &[4, 5], left));
+
+    // Ranges.
+    let diffs = dmp.diff_main("one two three", "one 2 three four", false);
+    let ranges = dmp.diff_map_ranges(&diffs, &[0..3, 4..7, 5..6, 8..13, 13..13]);
+    assert_eq!(vec![
+        diff_match_patch::MappedRange { range: 0..3, deleted: false },
+        diff_match_patch::MappedRange { range: 4..5, deleted: false },
+        diff_match_patch::MappedRange { range: 5..5, deleted: true },
+        diff_match_patch::MappedRange { range: 6..11, deleted: false },
+        diff_match_patch::MappedRange { range: 16..16, deleted: false }
+    ], ranges);
+    assert_eq!(diff_match_patch::MappedRange { range: 2..4, deleted: false }, dmp.diff_map_range(&diffs, 2..5));
+    assert_eq!(vec![diff_match_patch::MappedRange { range: 13..13, deleted: true }, diff_match_patch::MappedRange { range: 4..7, deleted: false }], dmp.diff_unmap_ranges(&diffs, &[11..16, 4..5]));
+
+    // Patch sets, with their rolling context.
+    let diffs = dmp.diff_main(text1, text2, false);
+    let patches = dmp.patch_make2(&diffs);
+    assert_eq!(dmp.diff_map_positions(&diffs, &locs, left), dmp.patch_map_positions(&patches, &locs, left));
+    assert_eq!(dmp.diff_map_ranges(&diffs, &[0..4, 35..39]), dmp.patch_map_ranges(&patches, &[0..4, 35..39]));
+}
+
+#[test]
+pub fn test_diff_line_map() {
+    let dmp = diff_match_patch::Dmp::new();
+    // Null case.
+    let line_map = dmp.diff_line_map(&[]);
+    assert_eq!((0, 0, None), (line_map.old_lines(), line_map.new_lines(), line_map.new_line(0)));
+
+    // Output of diff_chars_tolines.
+    let text1: Vec<char> = "alpha\nbeta\ngamma\ndelta\n".chars().collect();
+    let text2: Vec<char> = "alpha\nGAMMA\ngamma\nepsilon\ndelta\n".chars().collect();
+    let (chars1, chars2, line_array) = dmp.diff_lines_tochars(&text1, &text2);
+    let mut diffs = dmp.diff_main(&chars1, &chars2, false);
+    dmp.diff_chars_tolines(&mut diffs, &line_array);
+    let line_map = dmp.diff_line_map(&diffs);
+    assert_eq!((4, 5), (line_map.old_lines(), line_map.new_lines()));
+    assert_eq!(vec![Some(0), None, Some(2), Some(4), None], (0..5).map(|line| line_map.new_line(line)).collect::<Vec<_>>());
+    assert_eq!(vec![Some(0), None, Some(2), None, Some(3), None], (0..6).map(|line| line_map.old_line(line)).collect::<Vec<_>>());
+
+    // Output of diff_linemode, lines edited in place still map.
+    let diffs = dmp.diff_linemode(&text1, &text2);
+    let line_map = dmp.diff_line_map(&diffs);
+    assert_eq!(vec![Some(0), Some(1), Some(2), Some(4)], (0..4).map(|line| line_map.new_line(line)).collect::<Vec<_>>());
+    assert_eq!(None, line_map.old_line(3));
+
+    // Last lines without a line break.
+    let line_map = dmp.diff_line_map(&dmp.diff_main("a\nb", "a\nc", false));
+    assert_eq!((Some(1), Some(1)), (line_map.new_line(1), line_map.old_line(1)));
+    let line_map = dmp.diff_line_map(&dmp.diff_main("a\nb", "a\n", false));
+    assert_eq!((2, 1, None), (line_map.old_lines(), line_map.new_lines(), line_map.new_line(1)));
+    let line_map = dmp.diff_line_map(&dmp.diff_main("a\n", "a\nb", false));
+    assert_eq!((1, 2, None), (line_map.old_lines(), line_map.new_lines(), line_map.old_line(1)));
+}
+
+#[test]
+pub fn test_diff_moves() {
+    let mut dmp = diff_match_patch::Dmp::new();
+    // Null case.
+    assert!(dmp.diff_moves(&[], true).is_empty());
+
+    // A paragraph moved as it is.
+    let para1 = "The quick brown fox jumps over the lazy dog.\n";
+    let para2 = "Pack my box with five dozen liquor jugs.\n";
+    let para3 = "How vexingly quick daft zebras jump!\n";
+    let mut diffs = dmp.diff_main(&[para1, para2, para3].concat(), &[para2, para3, para1].concat(), false);
+    dmp.diff_cleanup_semantic(&mut diffs);
+    let moves = dmp.diff_moves(&diffs, true);
+    assert_eq!(1, moves.len());
+    assert_eq!((Operation::Delete, para1), (diffs[moves[0].from].operation, diffs[moves[0].from].text.as_str()));
+    assert_eq!((Operation::Insert, para1), (diffs[moves[0].to].operation, diffs[moves[0].to].text.as_str()));
+    assert_eq!(1.0, moves[0].similarity);
+    assert_eq!(Some(vec![diff_match_patch::Diff::new(Operation::Equal, para1.to_string())]), moves[0].diffs);
+
+    // Edited on the way.
+    let edited = "The quick brown fox jumped over a lazy dog.\n";
+    let mut diffs = dmp.diff_main(&[para1, para2, para3].concat(), &[para2, para3, edited].concat(), false);
+    dmp.diff_cleanup_semantic(&mut diffs);
+    let moves = dmp.diff_moves(&diffs, true);
+    assert_eq!(1, moves.len());
+    assert_eq!(edited, diffs[moves[0].to].text);
+    assert!(moves[0].similarity > 0.8 && moves[0].similarity < 1.0);
+    assert_eq!(edited, dmp.diff_text2(moves[0].diffs.as_ref().unwrap()));
+    assert_eq!(None, dmp.diff_moves(&diffs, false)[0].diffs);
+
+    // Too different, or too short.
+    dmp.move_threshold = 0.99;
+    assert!(dmp.diff_moves(&diffs, false).is_empty());
+    dmp.move_threshold = 0.8;
+    dmp.move_min_length = 100;
+    assert!(dmp.diff_moves(&diffs, false).is_empty());
+
+    // A replacement is not a move.
+    dmp.move_min_length = 1;
+    let diffs = vec![diff_match_patch::Diff::new(Operation::Delete, "abc".to_string()), diff_match_patch::Diff::new(Operation::Insert, "abc".to_string())];
+    assert!(dmp.diff_moves(&diffs, false).is_empty());
+}
+
+#[test]
+pub fn test_diff_ignore() {
+    let mut dmp = diff_match_patch::Dmp::new();
+    let d = |operation, text: &str| diff_match_patch::Diff::new(operation, text.to_string());
+
+    // diff -w: equalities keep the chars of text1.
+    dmp.whitespace_mode = diff_match_patch::WhitespaceMode::IgnoreAll;
+    assert_eq!(vec![d(Operation::Equal, "a b  c\n")], dmp.diff_main("a b  c\n", "ab c \n", false));
+    assert_eq!(vec![d(Operation::Equal, "if (x)  {"), d(Operation::Delete, "y "), d(Operation::Insert, "z"), d(Operation::Equal, "}")], dmp.diff_main("if (x)  {y }", "if(x){ z}", false));
+
+    // diff -b: only the amount of whitespace is ignored.
+    dmp.whitespace_mode = diff_match_patch::WhitespaceMode::IgnoreAmount;
+    assert_eq!(vec![d(Operation::Equal, "a \t b  \nc")], dmp.diff_main("a \t b  \nc", "a b\nc", false));
+    assert_eq!(vec![d(Operation::Equal, "a"), d(Operation::Insert, " "), d(Operation::Equal, "b")], dmp.diff_main("ab", "a b", false));
+
+    // --ignore-blank-lines.
+    dmp.whitespace_mode = diff_match_patch::WhitespaceMode::Exact;
+    dmp.ignore_blank_lines = true;
+    let diffs = dmp.diff_main("one\n\ntwo\nthree\n", "one\ntwo\n  \nthree\n\n", false);
+    assert_eq!(vec![d(Operation::Equal, "one\n\ntwo\nthree\n")], diffs);
+    assert_eq!(vec![d(Operation::Equal, "one\n"), d(Operation::Delete, "two"), d(Operation::Insert, "2"), d(Operation::Equal, "\n\n")], dmp.diff_main("one\ntwo\n\n", "one\n\n2\n", false));
+
+    // -i.
+    dmp.ignore_blank_lines = false;
+    dmp.ignore_case = true;
+    assert_eq!(vec![d(Operation::Equal, "Hello World"), d(Operation::Insert, "!")], dmp.diff_main("Hello World", "hello WORLD!", false));
+
+    // Line mode, with replaced lines rediffed char by char.
+    dmp.ignore_case = false;
+    dmp.whitespace_mode = diff_match_patch::WhitespaceMode::IgnoreAll;
+    let text1: Vec<char> = "fn main() {\n    let x = 1;\n    println!(\"{}\", x);\n}\n".chars().collect();
+    let text2: Vec<char> = "fn main() {\n\tlet x = 2;\n\tprintln!(\"{}\", x);\n}\n".chars().collect();
+    let diffs = dmp.diff_linemode(&text1, &text2);
+    assert_eq!(vec![d(Operation::Equal, "fn main() {\n    let x = "), d(Operation::Delete, "1"), d(Operation::Insert, "2"), d(Operation::Equal, ";\n    println!(\"{}\", x);\n}\n")], diffs);
+    assert_eq!(text1.iter().collect::<String>(), dmp.diff_text1(&diffs));
+    let text1 = text1.iter().collect::<String>().repeat(3);
+    let text2 = text2.iter().collect::<String>().repeat(3);
+    let diffs = dmp.diff_main(&text1, &text2, true);
+    assert_eq!(text1, dmp.diff_text1(&diffs));
+    assert_eq!(3, diffs.iter().filter(|diff| diff.operation == Operation::Insert).count());
+
+    // Patches and merges ignore nothing, they must rebuild text2.
+    dmp.ignore_case = true;
+    let patches = dmp.patch_make1("hello world", "HELLO  world!");
+    assert_eq!("HELLO  world!", dmp.patch_apply(&patches, "hello world").0);
+    let merged = dmp.merge3("abc\n", "ABC\n", "abc\nx\n");
+    assert_eq!(("ABC\nx\n", true), (merged.text.as_str(), merged.conflicts.is_empty()));
+}
+
+#[test]
+pub fn test_diff_granularity() {
+    let mut dmp = diff_match_patch::Dmp::new();
+    let d = |operation, text: &str| diff_match_patch::Diff::new(operation, text.to_string());
+
+    // Whole words.
+    dmp.diff_granularity = diff_match_patch::DiffGranularity::Word;
+    assert!(dmp.diff_main("", "", false).is_empty());
+    assert_eq!(vec![d(Operation::Equal, "The "), d(Operation::Delete, "cat"), d(Operation::Insert, "cart"), d(Operation::Equal, " sat.")], dmp.diff_main("The cat sat.", "The cart sat.", false));
+    assert_eq!(vec![d(Operation::Equal, "a "), d(Operation::Insert, "b "), d(Operation::Equal, "ünï")], dmp.diff_main("a ünï", "a b ünï", false));
+
+    // Custom word boundaries.
+    dmp.word_boundary = regex::Regex::new(r"[\s\p{P}]").unwrap();
+    assert_eq!(vec![d(Operation::Equal, "The "), d(Operation::Delete, "cat"), d(Operation::Insert, "dog"), d(Operation::Equal, ".")], dmp.diff_main("The cat.", "The dog.", false));
+    assert_eq!(("\u{1}\u{2}\u{3}\u{4}".to_string(), "\u{1}\u{2}\u{5}\u{4}".to_string()), {
+        let (chars1, chars2, _) = dmp.diff_words_tochars("The cat.", "The dog.");
+        (chars1, chars2)
+    });
+
+    // Whole lines.
+    dmp.diff_granularity = diff_match_patch::DiffGranularity::Line;
+    assert_eq!(vec![d(Operation::Equal, "alpha\n"), d(Operation::Delete, "beta\n"), d(Operation::Insert, "bet\n"), d(Operation::Equal, "gamma")], dmp.diff_main("alpha\nbeta\ngamma", "alpha\nbet\ngamma", false));
+
+    // With ignore modes.
+    dmp.ignore_case = true;
+    assert_eq!(vec![d(Operation::Equal, "alpha\nbeta\n"), d(Operation::Delete, "gamma"), d(Operation::Insert, "GAMMA\n")], dmp.diff_main("alpha\nbeta\ngamma", "ALPHA\nBeta\nGAMMA\n", false));
+    dmp.diff_granularity = diff_match_patch::DiffGranularity::Word;
+    dmp.word_boundary = regex::Regex::new(r"\s").unwrap();
+    assert_eq!(vec![d(Operation::Equal, "The "), d(Operation::Delete, "cat"), d(Operation::Insert, "dog"), d(Operation::Equal, " Sat")], dmp.diff_main("The cat Sat", "the dog sat", false));
+}
+
+#[test]
+pub fn test_tokenizer() {
+    use diff_match_patch::Tokenizer;
+    let mut dmp = diff_match_patch::Dmp::new();
+    let d = |operation, text: &str| diff_match_patch::Diff::new(operation, text.to_string());
+    let chars = |text: &str| text.chars().collect::<Vec<char>>();
+
+    // Built-in tokenizers.
+    assert_eq!(vec![0..1, 1..2], diff_match_patch::CharTokenizer.tokenize(&chars("ab")));
+    assert_eq!(vec![0..6, 6..11, 11..12], diff_match_patch::LineTokenizer.tokenize(&chars("alpha\nbeta\n\n")));
+    assert_eq!(vec![0..6, 6..10], diff_match_patch::LineTokenizer.tokenize(&chars("alpha\nbeta")));
+    assert!(diff_match_patch::LineTokenizer.tokenize(&[]).is_empty());
+    assert_eq!(vec![0..3, 3..4, 4..5, 5..8], diff_match_patch::WordTokenizer::default().tokenize(&chars("fóo \tbar")));
+
+    // A tokenizer of our own, used directly.
+    struct Cells;
+    impl diff_match_patch::Tokenizer for Cells {
+        fn tokenize(&self, text: &[char]) -> Vec<std::ops::Range<usize>> {
+            // a cell with the comma or line break after it.
+            let mut spans = vec![];
+            let mut start = 0;
+            for (i, ch) in text.iter().enumerate() {
+                if *ch == ',' || *ch == '\n' || i + 1 == text.len() {
+                    spans.push(start..i + 1);
+                    start = i + 1;
+                }
+            }
+            spans
+        }
+    }
+    assert_eq!(vec![d(Operation::Equal, "id,name,"), d(Operation::Delete, "12\n"), d(Operation::Insert, "13\n"), d(Operation::Equal, "1,ann,30\n")], dmp.diff_tokenized("id,name,12\n1,ann,30\n", "id,name,13\n1,ann,30\n", &Cells));
+
+    // Registered by name.
+    dmp.register_tokenizer("csv", Cells);
+    dmp.diff_granularity = diff_match_patch::DiffGranularity::Custom("csv");
+    assert_eq!(vec![d(Operation::Equal, "1,"), d(Operation::Delete, "ann,"), d(Operation::Insert, "anne,"), d(Operation::Equal, "30")], dmp.diff_main("1,ann,30", "1,anne,30", false));
+
+    // An unknown name diffs char by char.
+    dmp.diff_granularity = diff_match_patch::DiffGranularity::Custom("tsv");
+    assert_eq!(vec![d(Operation::Equal, "1,ann"), d(Operation::Insert, "e"), d(Operation::Equal, ",30")], dmp.diff_main("1,ann,30", "1,anne,30", false));
+    assert_eq!(1, dmp.diff_review("1,ann,30\n", "1,anne,30\n").len());
+    dmp.diff_granularity = diff_match_patch::DiffGranularity::Custom("csv");
+
+    // Sentences, with ignore_case on top.
+    struct Sentences;
+    impl diff_match_patch::Tokenizer for Sentences {
+        fn tokenize(&self, text: &[char]) -> Vec<std::ops::Range<usize>> {
+            // a sentence up to its full stop, spaces go with the next one.
+            let mut spans = vec![];
+            let mut start = 0;
+            for (i, ch) in text.iter().enumerate() {
+                if *ch == '.' {
+                    spans.push(start..i + 1);
+                    start = i + 1;
+                }
+            }
+            spans.push(start..text.len());
+            spans
+        }
+    }
+    dmp.register_tokenizer("sentence", Sentences);
+    dmp.diff_granularity = diff_match_patch::DiffGranularity::Custom("sentence");
+    dmp.ignore_case = true;
+    assert_eq!(vec![d(Operation::Equal, "One. Two."), d(Operation::Delete, " Three."), d(Operation::Insert, " Four."), d(Operation::Equal, " five")], dmp.diff_main("One. Two. Three. five", "one. two. Four. Five", false));
+}
+
+#[test]
+pub fn test_diff_graphemes() {
+    use diff_match_patch::Tokenizer;
+    let mut dmp = diff_match_patch::Dmp::new();
+    let d = |operation, text: &str| diff_match_patch::Diff::new(operation, text.to_string());
+    let chars = |text: &str| text.chars().collect::<Vec<char>>();
+
+    // Clusters of several chars.
+    assert_eq!(vec![0..2, 2..3, 3..8, 8..10], diff_match_patch::GraphemeTokenizer.tokenize(&chars("e\u{301}x\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{1F1EB}\u{1F1F7}")));
+
+    // Off, diffs split clusters.
+    assert_eq!(vec![d(Operation::Equal, "e"), d(Operation::Delete, "\u{301}"), d(Operation::Insert, "\u{300}")], dmp.diff_main("e\u{301}", "e\u{300}", false));
+
+    // Combining marks.
+    dmp.grapheme_clusters = true;
+    assert_eq!(vec![d(Operation::Delete, "e\u{301}"), d(Operation::Insert, "e\u{300}")], dmp.diff_main("e\u{301}", "e\u{300}", false));
+    assert_eq!(vec![d(Operation::Equal, "caf"), d(Operation::Delete, "e\u{301}"), d(Operation::Insert, "e\u{300}"), d(Operation::Equal, "s")], dmp.diff_main("cafe\u{301}s", "cafe\u{300}s", false));
+
+    // Emoji modifiers, ZWJ sequences and flags.
+    assert_eq!(vec![d(Operation::Equal, "a"), d(Operation::Delete, "\u{1F44D}"), d(Operation::Insert, "\u{1F44D}\u{1F3FD}"), d(Operation::Equal, " b")], dmp.diff_main("a\u{1F44D} b", "a\u{1F44D}\u{1F3FD} b", false));
+    assert_eq!(vec![d(Operation::Delete, "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}"), d(Operation::Insert, "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F466}")], dmp.diff_main("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}", "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F466}", false));
+    assert_eq!(vec![d(Operation::Equal, "\u{1F1EB}\u{1F1F7}"), d(Operation::Delete, "\u{1F1E9}\u{1F1EA}"), d(Operation::Insert, "\u{1F1EA}\u{1F1F8}")], dmp.diff_main("\u{1F1EB}\u{1F1F7}\u{1F1E9}\u{1F1EA}", "\u{1F1EB}\u{1F1F7}\u{1F1EA}\u{1F1F8}", false));
+
+    // A cleanup splitting a cluster, and putting it back together.
+    let mut diffs = vec![d(Operation::Delete, "e\u{301}"), d(Operation::Insert, "e\u{300}")];
+    dmp.diff_cleanup_merge(&mut diffs);
+    assert_eq!(vec![d(Operation::Equal, "e"), d(Operation::Delete, "\u{301}"), d(Operation::Insert, "\u{300}")], diffs);
+    dmp.diff_cleanup_graphemes(&mut diffs);
+    assert_eq!(vec![d(Operation::Delete, "e\u{301}"), d(Operation::Insert, "e\u{300}")], diffs);
+
+    // Lossless shifting skips positions inside a cluster.
+    let mut diffs = vec![d(Operation::Equal, "a"), d(Operation::Insert, " \u{301}b"), d(Operation::Equal, " \u{301}c")];
+    dmp.diff_cleanup_semantic_lossless(&mut diffs);
+    assert_eq!(vec![d(Operation::Equal, "a"), d(Operation::Insert, " \u{301}b"), d(Operation::Equal, " \u{301}c")], diffs);
+    dmp.grapheme_clusters = false;
+    dmp.diff_cleanup_semantic_lossless(&mut diffs);
+    assert_eq!(vec![d(Operation::Equal, "a "), d(Operation::Insert, "\u{301}b "), d(Operation::Equal, "\u{301}c")], diffs);
+
+    // Patch context takes whole clusters.
+    let text1 = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467} hello";
+    let text2 = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467} jello";
+    assert_eq!(d(Operation::Equal, "\u{1F469}\u{200D}\u{1F467} "), dmp.patch_make1(text1, text2)[0].diffs[0]);
+    dmp.grapheme_clusters = true;
+    let patches = dmp.patch_make1(text1, text2);
+    assert_eq!(vec![d(Operation::Equal, "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467} "), d(Operation::Delete, "h"), d(Operation::Insert, "j"), d(Operation::Equal, "ello")], patches[0].diffs);
+    assert_eq!((0, 0, 11, 11), (patches[0].start1, patches[0].start2, patches[0].length1, patches[0].length2));
+    assert_eq!(text2, dmp.patch_apply(&patches, text1).0);
+
+    // Runs of flags are paired from their start, however far back it is.
+    let flags = "\u{1F1EB}\u{1F1F7}".repeat(20);
+    let patches = dmp.patch_make1(&format!("{flags}\u{1F1EB}hello"), &format!("{flags}\u{1F1EB}jello"));
+    assert_eq!(d(Operation::Equal, "\u{1F1EB}\u{1F1F7}\u{1F1EB}\u{1F1F7}\u{1F1EB}"), patches[0].diffs[0]);
+}
+
+#[test]
+pub fn test_diff_review() {
+    use diff_match_patch::ReviewLine;
+    let mut dmp = diff_match_patch::Dmp::new();
+    let d = |operation, text: &str| diff_match_patch::Diff::new(operation, text.to_string());
+    let context = |line1, line2, text: &str| ReviewLine::Context { line1, line2, text: text.to_string() };
+
+    // Equal texts have no hunks.
+    assert!(dmp.diff_review("a\nb\n", "a\nb\n").is_empty());
+
+    // A changed line is paired with the inserted line most like it.
+    let text1 = "fn main() {\n    let x = 1;\n    println!(\"{}\", x);\n}\n";
+    let text2 = "fn main() {\n    let x = 2;\n    let y = 3;\n    println!(\"{}\", x);\n}\n";
+    let hunks = dmp.diff_review(text1, text2);
+    assert_eq!(1, hunks.len());
+    assert_eq!((0, 0, 4, 5), (hunks[0].start1, hunks[0].start2, hunks[0].length1, hunks[0].length2));
+    assert_eq!(vec![
+        context(0, 0, "fn main() {\n"),
+        ReviewLine::Modified { line1: 1, line2: 1, similarity: 1.0 - 1.0 / 15.0, diffs: vec![d(Operation::Equal, "    let x = "), d(Operation::Delete, "1"), d(Operation::Insert, "2"), d(Operation::Equal, ";\n")] },
+        ReviewLine::Inserted { line2: 2, text: "    let y = 3;\n".to_string() },
+        context(2, 3, "    println!(\"{}\", x);\n"),
+        context(3, 4, "}\n")
+    ], hunks[0].lines);
+    assert_eq!((Some(1), Some(1)), (hunks[0].lines[1].line1(), hunks[0].lines[1].line2()));
+    assert_eq!((None, Some(2)), (hunks[0].lines[2].line1(), hunks[0].lines[2].line2()));
+
+    // Lines too unlike stay deleted and inserted, and far apart changes get hunks of their own.
+    dmp.unified_context = 0;
+    let hunks = dmp.diff_review("a\nb\nc\nd\ne\n", "a\nB\nc\nd\nzzzzz\n");
+    assert_eq!(2, hunks.len());
+    assert_eq!(vec![ReviewLine::Modified { line1: 1, line2: 1, similarity: 0.5, diffs: vec![d(Operation::Delete, "b"), d(Operation::Insert, "B"), d(Operation::Equal, "\n")] }], hunks[0].lines);
+    assert_eq!((4, 4, 1, 1), (hunks[1].start1, hunks[1].start2, hunks[1].length1, hunks[1].length2));
+    assert_eq!(vec![ReviewLine::Deleted { line1: 4, text: "e\n".to_string() }, ReviewLine::Inserted { line2: 4, text: "zzzzz\n".to_string() }], hunks[1].lines);
+    dmp.review_threshold = 0.9;
+    assert_eq!(vec![ReviewLine::Deleted { line1: 1, text: "b\n".to_string() }, ReviewLine::Inserted { line2: 1, text: "B\n".to_string() }], dmp.diff_review("a\nb\nc\n", "a\nB\nc\n")[0].lines);
+
+    // Pairs keep their order, refined word by word.
+    dmp.review_threshold = 0.5;
+    dmp.diff_granularity = diff_match_patch::DiffGranularity::Word;
+    let lines = dmp.diff_review("one two\nthree four\n", "zero\none two!\nthree five\n").remove(0).lines;
+    assert_eq!(vec![
+        ReviewLine::Inserted { line2: 0, text: "zero\n".to_string() },
+        ReviewLine::Modified { line1: 0, line2: 1, similarity: 1.0 - 4.0 / 9.0, diffs: vec![d(Operation::Equal, "one "), d(Operation::Delete, "two"), d(Operation::Insert, "two!"), d(Operation::Equal, "\n")] },
+        ReviewLine::Modified { line1: 1, line2: 2, similarity: 1.0 - 4.0 / 11.0, diffs: vec![d(Operation::Equal, "three "), d(Operation::Delete, "four"), d(Operation::Insert, "five"), d(Operation::Equal, "\n")] }
+    ], lines);
+
+    // Pairs found in a diff of our own.
+    dmp.diff_granularity = diff_match_patch::DiffGranularity::Char;
+    let diffs = vec![d(Operation::Delete, "old\n"), d(Operation::Insert, "olde\n")];
+    assert_eq!(vec![ReviewLine::Modified { line1: 0, line2: 0, similarity: 0.8, diffs: vec![d(Operation::Equal, "old"), d(Operation::Insert, "e"), d(Operation::Equal, "\n")] }], dmp.diff_review_hunks(&diffs)[0].lines);
+}
+
+#[test]
+pub fn test_diff_side_by_side() {
+    use diff_match_patch::{RowFormatter, RowKind, SideBySideRow, SideLine};
+    let dmp = diff_match_patch::Dmp::new();
+    let line = |number, text: &str| Some(SideLine { number, text: text.to_string() });
+    let row = |left, right, kind| SideBySideRow { left, right, kind };
+
+    // Equal texts.
+    assert!(dmp.diff_side_by_side(&[]).is_empty());
+    let text1: Vec<char> = "a\nb\nc\nd\ne\n".chars().collect();
+    let text2: Vec<char> = "a\nB\nc\nx\ny\ne\nf".chars().collect();
+    assert_eq!(vec![row(line(0, "a"), line(0, "a"), RowKind::Equal)], dmp.diff_side_by_side(&dmp.diff_linemode(&text1[..2], &text1[..2])));
+
+    // Changes are aligned line by line, the longer side runs on alone.
+    let rows = dmp.diff_side_by_side(&dmp.diff_linemode(&text1, &text2));
+    assert_eq!(vec![
+        row(line(0, "a"), line(0, "a"), RowKind::Equal),
+        row(line(1, "b"), line(1, "B"), RowKind::Change),
+        row(line(2, "c"), line(2, "c"), RowKind::Equal),
+        row(line(3, "d"), line(3, "x"), RowKind::Change),
+        row(None, line(4, "y"), RowKind::Insert),
+        row(line(4, "e"), line(5, "e"), RowKind::Equal),
+        row(None, line(6, "f"), RowKind::Insert)
+    ], rows);
+    assert_eq!(vec![row(line(0, "gone"), None, RowKind::Delete)], dmp.diff_side_by_side(&[diff_match_patch::Diff::new(Operation::Delete, "gone\r\n".to_string())]));
+
+    // Terminal columns, like diff -y.
+    let mut terminal = diff_match_patch::TerminalRowFormatter::new(6);
+    assert_eq!(concat!(
+        "   1 a           1 a\n",
+        "   2 b      |    2 B\n",
+        "   3 c           3 c\n",
+        "   4 d      |    4 x\n",
+        "            >    5 y\n",
+        "   5 e           6 e\n",
+        "            >    7 f\n"
+    ), terminal.format(&rows));
+    terminal.number_width = 0;
+    terminal.tab_width = 4;
+    assert_eq!("a   bc | x   y\n", terminal.format(&[row(line(0, "a\tbcdef"), line(0, "x\ty"), RowKind::Change)]));
+
+    // HTML table.
+    assert_eq!(concat!(
+        "<table class=\"diff\">\n",
+        "<tr class=\"change\"><td class=\"num\">2</td><td class=\"left\">&lt;b&gt;</td><td class=\"num\">2</td><td class=\"right\">B &amp; b</td></tr>\n",
+        "<tr class=\"insert\"><td class=\"num\"></td><td class=\"left\"></td><td class=\"num\">3</td><td class=\"right\">y</td></tr>\n",
+        "</table>\n"
+    ), diff_match_patch::HtmlRowFormatter::new().format(&[row(line(1, "<b>"), line(1, "B & b"), RowKind::Change), row(None, line(2, "y"), RowKind::Insert)]));
+}
+
+#[test]
+pub fn test_patch_apply_shared() {
+    // One Dmp, borrowed by several threads at once.
+    let dmp = diff_match_patch::Dmp::new();
+    let patches = dmp.patch_make1("The quick brown fox jumps over the lazy dog.", "That quick brown fox jumped over a lazy dog.");
+    let text = dmp.patch_to_text(&patches);
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                assert_eq!(("That quick brown fox jumped over a lazy dog.".to_string(), vec![true, true]), dmp.patch_apply(&patches, "The quick brown fox jumps over the lazy dog."));
+                assert_eq!(text, dmp.patch_to_text(&dmp.patch_from_text(text.clone()).unwrap()));
+            });
+        }
+    });
+}
+
+#[test]
+pub fn test_patch_apply_report() {
+    let dmp = diff_match_patch::Dmp::new();
+    // Null case.
+    assert_eq!(("Hello world.".chars().collect(), vec![]), dmp.patch_apply_report(&[], "Hello world."));
+
+    // Exact match.
+    let patches = dmp.patch_make1("The quick brown fox jumps over the lazy dog.", "That quick brown fox jumped over a lazy dog.");
+    assert_eq!(("That quick brown fox jumped over a lazy dog.".chars().collect(), vec![
+        diff_match_patch::ApplyReport { applied: true, expected_loc: 0, actual_loc: Some(0), delta: 0, exact: true, levenshtein_ratio: 0.0, range: Some(0..12) },
+        diff_match_patch::ApplyReport { applied: true, expected_loc: 21, actual_loc: Some(21), delta: 0, exact: true, levenshtein_ratio: 0.0, range: Some(21..38) }
+    ]), dmp.patch_apply_report(&patches, "The quick brown fox jumps over the lazy dog."));
+
+    // Partial match, the second patch inherits the drift of the first.
+    let (text, reports) = dmp.patch_apply_report(&patches, "Well, The quick red rabbit jumps over the tired tiger.");
+    assert_eq!("Well, That quick red rabbit jumped over a tired tiger.", text);
+    assert_eq!((Some(4), 6, false), (reports[0].actual_loc, reports[0].delta, reports[0].exact));
+    assert!(reports[0].levenshtein_ratio > 0.0);
+    assert_eq!((27, Some(28)), (reports[1].expected_loc, reports[1].actual_loc));
+    assert_eq!(Some(28..45), reports[1].range);
+
+    // Failed match.
+    let (_, reports) = dmp.patch_apply_report(&patches, "I am the very model of a modern major general.");
+    assert_eq!(diff_match_patch::ApplyReport { applied: false, expected_loc: 20, actual_loc: None, delta: 0, exact: false, levenshtein_ratio: 0.0, range: None }, reports[1]);
+
+    // One report per patch, even if it is split up.
+    let patches = dmp.patch_make1("x1234567890123456789012345678901234567890123456789012345678901234567890y", "xabcy");
+    assert_eq!(1, patches.len());
+    let (text, reports) = dmp.patch_apply_report(&patches, "x123456789012345678901234567890-----++++++++++-----123456789012345678901234567890y");
+    assert_eq!("xabcy", text);
+    assert_eq!(1, reports.len());
+    assert_eq!((true, false, Some(0..5)), (reports[0].applied, reports[0].exact, reports[0].range.clone()));
+
+    // Big delete, too far off patch_delete_threshold.
+    let (_, reports) = dmp.patch_apply_report(&patches, "x12345678901234567890---------------++++++++++---------------12345678901234567890y");
+    assert!(!reports[0].applied);
+    assert!(reports[0].levenshtein_ratio > dmp.patch_delete_threshold);
+}
+
+#[test]
+pub fn test_patch_from_unified() {
+    let dmp = diff_match_patch::Dmp::new();
+    let git = "diff --git a/a.txt b/a.txt\nindex 4cb29ea..a623a0b 100644\n--- a/a.txt\n+++ b/a.txt\n@@ -1,3 +1,3 @@\n one\n-two\n-three\n+2\n+three\n\\ No newline at end of file\n\
+diff --git a/fresh.txt b/fresh.txt\nnew file mode 100644\nindex 0000000..ce01362\n--- /dev/null\n+++ b/fresh.txt\n@@ -0,0 +1 @@\n+hello\n\
+diff --git a/gone.txt b/gone.txt\ndeleted file mode 100644\nindex b023018..0000000\n--- a/gone.txt\n+++ /dev/null\n@@ -1 +0,0 @@\n-bye\n\
+diff --git a/old.txt b/new.txt\nsimilarity index 100%\nrename from old.txt\nrename to new.txt\n";
+    let files = dmp.patch_from_unified(git).unwrap();
+    assert_eq!(4, files.len());
+    assert_eq!((Some("a.txt".to_string()), Some("a.txt".to_string()), false), (files[0].old_path.clone(), files[0].new_path.clone(), files[0].renamed));
+    assert_eq!(vec![diff_match_patch::UnifiedHunk {
+        diffs: vec![diff_match_patch::Diff::new(Operation::Equal, "one\n".to_string()), diff_match_patch::Diff::new(Operation::Delete, "two\n".to_string()), diff_match_patch::Diff::new(Operation::Delete, "three\n".to_string()), diff_match_patch::Diff::new(Operation::Insert, "2\n".to_string()), diff_match_patch::Diff::new(Operation::Insert, "three".to_string())],
+        start1: 0, start2: 0, length1: 3, length2: 3
+    }], files[0].hunks);
+    assert_eq!((None, Some("fresh.txt".to_string())), (files[1].old_path.clone(), files[1].new_path.clone()));
+    assert_eq!((0, 0, 0, 1), (files[1].hunks[0].start1, files[1].hunks[0].length1, files[1].hunks[0].start2, files[1].hunks[0].length2));
+    assert_eq!((Some("gone.txt".to_string()), None), (files[2].old_path.clone(), files[2].new_path.clone()));
+    assert_eq!(diff_match_patch::FilePatch { old_path: Some("old.txt".to_string()), new_path: Some("new.txt".to_string()), renamed: true, hunks: vec![] }, files[3]);
+
+    // Hunks print back the way they were read.
+    assert_eq!("@@ -1,3 +1,3 @@\n one\n-two\n-three\n+2\n+three\n\\ No newline at end of file\n", files[0].hunks[0].to_string());
+
+    // Several files of a plain diff -u, timestamps are not part of the path.
+    let gnu = "Only in a: junk\n--- a/x.txt\t2020-01-01 00:00:00 +0000\n+++ b/x.txt\t2020-01-02 00:00:00 +0000\n@@ -1 +1 @@\n-x\n+X\n--- y.txt\n+++ y.txt\n@@ -1,2 +1 @@\n y\n-\n";
+    let files = dmp.patch_from_unified(gnu).unwrap();
+    assert_eq!(2, files.len());
+    assert_eq!(Some("a/x.txt".to_string()), files[0].old_path);
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "y\n".to_string()), diff_match_patch::Diff::new(Operation::Delete, "\n".to_string())], files[1].hunks[0].diffs);
+
+    // Errors.
+    assert_eq!(Err(diff_match_patch::DmpError::BadHeader { line: 3, column: 4 }), dmp.patch_from_unified("--- a\n+++ b\n@@ -x +1 @@\n"));
+    assert_eq!(Err(diff_match_patch::DmpError::BadLinePrefix { line: 4, column: 1, prefix: '*' }), dmp.patch_from_unified("--- a\n+++ b\n@@ -1 +1 @@\n*a\n"));
+    assert_eq!(Err(diff_match_patch::DmpError::LengthMismatch { line: 3, column: 1, expected: 2, actual: 1 }), dmp.patch_from_unified("--- a\n+++ b\n@@ -1,2 +1,2 @@\n a\n"));
+    assert_eq!(Err(diff_match_patch::DmpError::LengthMismatch { line: 5, column: 1, expected: 1, actual: 2 }), dmp.patch_from_unified("--- a\n+++ b\n@@ -1 +1,2 @@\n-a\n-b\n+a\n"));
+}
+
+#[test]
+pub fn test_patch_apply_unified() {
+    let dmp = diff_match_patch::Dmp::new();
+    let diff = "--- a.txt\n+++ a.txt\n@@ -1,3 +1,3 @@\n one\n-two\n-three\n+2\n+three\n\\ No newline at end of file\n";
+    let files = dmp.patch_from_unified(diff).unwrap();
+    assert_eq!(("one\n2\nthree".to_string(), vec![true]), dmp.patch_apply_unified(&files[0], "one\ntwo\nthree\n"));
+
+    // Line numbers drifted.
+    assert_eq!(("zero\nextra\none\n2\nthree".to_string(), vec![true]), dmp.patch_apply_unified(&files[0], "zero\nextra\none\ntwo\nthree\n"));
+
+    // Nothing to match.
+    assert_eq!(("something else\n".to_string(), vec![false]), dmp.patch_apply_unified(&files[0], "something else\n"));
+
+    // New and deleted files.
+    let files = dmp.patch_from_unified("--- /dev/null\n+++ b\n@@ -0,0 +1 @@\n+hello\n--- a\n+++ /dev/null\n@@ -1 +0,0 @@\n-bye\n").unwrap();
+    assert_eq!(("hello\n".to_string(), vec![true]), dmp.patch_apply_unified(&files[0], ""));
+    assert_eq!(("".to_string(), vec![true]), dmp.patch_apply_unified(&files[1], "bye\n"));
+
+    // Round trip through diff_unified_text, with a failed hunk in the middle.
+    let words = ["alfa", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel", "india", "juliett", "kilo", "lima", "mike", "november", "oscar", "papa", "quebec", "romeo", "sierra", "tango", "uniform", "victor", "whiskey", "xray", "yankee", "zulu"];
+    let text1 = words.iter().map(|word| format!("{word}\n")).collect::<String>();
+    let text2 = text1.replace("echo\n", "ECHO\n").replace("oscar\n", "OSCAR\n").replace("xray\n", "");
+    let files = dmp.patch_from_unified(&dmp.diff_unified_text(&text1, &text2, "a", "b")).unwrap();
+    assert_eq!(3, files[0].hunks.len());
+    assert_eq!((text2.clone(), vec![true, true, true]), dmp.patch_apply_unified(&files[0], &text1));
+    let source = text1.replace("lima\nmike\nnovember\noscar\npapa\nquebec\nromeo\n", "rewritten since\n");
+    let (patched, results) = dmp.patch_apply_unified(&files[0], &source);
+    assert_eq!(vec![true, false, true], results);
+    assert_eq!(source.replace("echo\n", "ECHO\n").replace("xray\n", ""), patched);
+}
+
+#[test]
+pub fn test_merge3() {
+    let mut dmp = diff_match_patch::Dmp::new();
+    let base = "one\ntwo\nthree\n";
+    // Null case.
+    assert_eq!(diff_match_patch::MergeResult { text: base.to_string(), conflicts: vec![] }, dmp.merge3(base, base, base));
+
+    // Edits to different places.
+    assert_eq!(diff_match_patch::MergeResult { text: "ONE\ntwo\nTHREE\n".to_string(), conflicts: vec![] }, dmp.merge3(base, "ONE\ntwo\nthree\n", "one\ntwo\nTHREE\n"));
+
+    // Same edit on both sides.
+    assert_eq!(diff_match_patch::MergeResult { text: "one\n2\nthree\n".to_string(), conflicts: vec![] }, dmp.merge3(base, "one\n2\nthree\n", "one\n2\nthree\n"));
+
+    // Different words of the same line.
+    assert_eq!(diff_match_patch::MergeResult { text: "the slow brown cat\njumps\n".to_string(), conflicts: vec![] },
+               dmp.merge3("the quick brown fox\njumps\n", "the slow brown fox\njumps\n", "the quick brown cat\njumps\n"));
+
+    // Conflict.
+    let conflict = diff_match_patch::MergeConflict { base: "two\n".to_string(), ours: "2\n".to_string(), theirs: "deux\n".to_string(), range: 4..47 };
+    assert_eq!(diff_match_patch::MergeResult { text: "one\n<<<<<<< ours\n2\n=======\ndeux\n>>>>>>> theirs\nthree\n".to_string(), conflicts: vec![conflict.clone()] },
+               dmp.merge3(base, "one\n2\nthree\n", "one\ndeux\nthree\n"));
+
+    // Conflicts cover whole lines, clean edits of the same line included.
+    let merged = dmp.merge3("the quick brown fox\njumps\n", "a quick red fox\njumps\n", "the quick green fox\njumps\n");
+    assert_eq!("<<<<<<< ours\na quick red fox\n=======\nthe quick green fox\n>>>>>>> theirs\njumps\n", merged.text);
+    assert_eq!("the quick brown fox\n", merged.conflicts[0].base);
+
+    // Resolutions.
+    dmp.merge_resolution = diff_match_patch::MergeResolution::Ours;
+    assert_eq!(diff_match_patch::MergeResult { text: "one\n2\nthree\n".to_string(), conflicts: vec![diff_match_patch::MergeConflict { range: 4..6, ..conflict.clone() }] },
+               dmp.merge3(base, "one\n2\nthree\n", "one\ndeux\nthree\n"));
+    dmp.merge_resolution = diff_match_patch::MergeResolution::Theirs;
+    assert_eq!("one\ndeux\nthree\n", dmp.merge3(base, "one\n2\nthree\n", "one\ndeux\nthree\n").text);
+    dmp.merge_resolution = diff_match_patch::MergeResolution::Union;
+    assert_eq!(diff_match_patch::MergeResult { text: "one\n2\ndeux\nthree".to_string(), conflicts: vec![diff_match_patch::MergeConflict { range: 4..11, ..conflict }] },
+               dmp.merge3(base, "one\n2\nthree", "one\ndeux\nthree\n"));
+
+    // Sides without a final line break still end up on lines of their own.
+    dmp.merge_resolution = diff_match_patch::MergeResolution::Markers;
+    assert_eq!("<<<<<<< ours\na\n=======\nb\n>>>>>>> theirs\n", dmp.merge3("", "a", "b").text);
+}
+
+#[test]
+pub fn test_diff_linemode_many_lines() {
+    // More unique lines than there are chars below the surrogate range.
+    let dmp = diff_match_patch::Dmp::new();
+    let mut text1 = String::new();
+    for i in 0..60000 {
+        text1 += format!("{i}\n").as_str();
+    }
+    let text2 = text1.replace("\n30000\n", "\nthirty thousand\n").replace("\n59999\n", "\n");
+    let diffs = dmp.diff_linemode(&text1.chars().collect::<Vec<_>>(), &text2.chars().collect::<Vec<_>>());
+    assert_eq!(vec![text1, text2], diff_rebuildtexts(diffs));
 }
 
 #[test]
@@ -650,8 +1655,8 @@ pub fn test_diff_bisect_timeout() {
     let b = "map".to_string();
 
     let expected = vec![
-        diff_match_patch::Diff::new(-1, "cat".to_string()),
-        diff_match_patch::Diff::new(1, "map".to_string())
+        diff_match_patch::Diff::new(Operation::Delete, "cat".to_string()),
+        diff_match_patch::Diff::new(Operation::Insert, "map".to_string())
     ];
 
     let result = dmp.diff_bisect(&a.chars().collect::<Vec<_>>(), &b.chars().collect::<Vec<_>>());
@@ -664,18 +1669,18 @@ pub fn test_diff_main() {
     let new_dmp = diff_match_patch::Dmp::new();
     let temp: Vec<diff_match_patch::Diff> = Vec::new();
     assert_eq!(temp, new_dmp.diff_main("", "", true));
-    assert_eq!(vec![diff_match_patch::Diff::new(0, "abc".to_string())], new_dmp.diff_main("abc", "abc", true));
-    assert_eq!(vec![diff_match_patch::Diff::new(0, "ab".to_string()), diff_match_patch::Diff::new(1, "123".to_string()), diff_match_patch::Diff::new(0, "c".to_string())], new_dmp.diff_main("abc", "ab123c", true));
-    assert_eq!(vec![diff_match_patch::Diff::new(0, "a".to_string()), diff_match_patch::Diff::new(-1, "123".to_string()), diff_match_patch::Diff::new(0, "bc".to_string())], new_dmp.diff_main("a123bc", "abc", true));
-    assert_eq!(vec![diff_match_patch::Diff::new(0, "a".to_string()), diff_match_patch::Diff::new(1, "123".to_string()), diff_match_patch::Diff::new(0, "b".to_string()), diff_match_patch::Diff::new(1, "456".to_string()), diff_match_patch::Diff::new(0, "c".to_string())], new_dmp.diff_main("abc", "a123b456c", true));
-    assert_eq!(vec![diff_match_patch::Diff::new(0, "a".to_string()), diff_match_patch::Diff::new(-1, "123".to_string()), diff_match_patch::Diff::new(0, "b".to_string()), diff_match_patch::Diff::new(-1, "456".to_string()), diff_match_patch::Diff::new(0, "c".to_string())], new_dmp.diff_main("a123b456c", "abc", true));
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "a".to_string()), diff_match_patch::Diff::new(1, "b".to_string())], new_dmp.diff_main("a", "b", true));
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "Apple".to_string()), diff_match_patch::Diff::new(1, "Banana".to_string()), diff_match_patch::Diff::new(0, "s are a".to_string()), diff_match_patch::Diff::new(1, "lso".to_string()), diff_match_patch::Diff::new(0, " fruit.".to_string())], new_dmp.diff_main("Apples are a fruit.", "Bananas are also fruit.", true));
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "a".to_string()), diff_match_patch::Diff::new(1, "\u{0680}".to_string()), diff_match_patch::Diff::new(0, "x".to_string()), diff_match_patch::Diff::new(-1, "\t".to_string()), diff_match_patch::Diff::new(1, "\n".to_string())], new_dmp.diff_main("ax\t", "\u{0680}x\n", false));
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "1".to_string()), diff_match_patch::Diff::new(0, "a".to_string()), diff_match_patch::Diff::new(-1, "y".to_string()), diff_match_patch::Diff::new(0, "b".to_string()), diff_match_patch::Diff::new(-1, "2".to_string()), diff_match_patch::Diff::new(1, "xab".to_string())], new_dmp.diff_main("1ayb2", "abxab", false));
-    assert_eq!(vec![diff_match_patch::Diff::new(1, "xaxcx".to_string()), diff_match_patch::Diff::new(0, "abc".to_string()), diff_match_patch::Diff::new(-1, "y".to_string())], new_dmp.diff_main("abcy", "xaxcxabc", false));
-    assert_eq!(vec![diff_match_patch::Diff::new(-1, "ABCD".to_string()), diff_match_patch::Diff::new(0, "a".to_string()), diff_match_patch::Diff::new(-1, "=".to_string()), diff_match_patch::Diff::new(1, "-".to_string()), diff_match_patch::Diff::new(0, "bcd".to_string()), diff_match_patch::Diff::new(-1, "=".to_string()), diff_match_patch::Diff::new(1, "-".to_string()), diff_match_patch::Diff::new(0, "efghijklmnopqrs".to_string()), diff_match_patch::Diff::new(-1, "EFGHIJKLMNOefg".to_string())], new_dmp.diff_main("ABCDa=bcd=efghijklmnopqrsEFGHIJKLMNOefg", "a-bcd-efghijklmnopqrs", false));
-    assert_eq!(vec![diff_match_patch::Diff::new(1, " ".to_string()), diff_match_patch::Diff::new(0, "a".to_string()), diff_match_patch::Diff::new(1, "nd".to_string()), diff_match_patch::Diff::new(0, " [[Pennsylvania]]".to_string()), diff_match_patch::Diff::new(-1, " and [[New".to_string())], new_dmp.diff_main("a [[Pennsylvania]] and [[New", " and [[Pennsylvania]]", false));
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "abc".to_string())], new_dmp.diff_main("abc", "abc", true));
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "ab".to_string()), diff_match_patch::Diff::new(Operation::Insert, "123".to_string()), diff_match_patch::Diff::new(Operation::Equal, "c".to_string())], new_dmp.diff_main("abc", "ab123c", true));
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "a".to_string()), diff_match_patch::Diff::new(Operation::Delete, "123".to_string()), diff_match_patch::Diff::new(Operation::Equal, "bc".to_string())], new_dmp.diff_main("a123bc", "abc", true));
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "a".to_string()), diff_match_patch::Diff::new(Operation::Insert, "123".to_string()), diff_match_patch::Diff::new(Operation::Equal, "b".to_string()), diff_match_patch::Diff::new(Operation::Insert, "456".to_string()), diff_match_patch::Diff::new(Operation::Equal, "c".to_string())], new_dmp.diff_main("abc", "a123b456c", true));
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Equal, "a".to_string()), diff_match_patch::Diff::new(Operation::Delete, "123".to_string()), diff_match_patch::Diff::new(Operation::Equal, "b".to_string()), diff_match_patch::Diff::new(Operation::Delete, "456".to_string()), diff_match_patch::Diff::new(Operation::Equal, "c".to_string())], new_dmp.diff_main("a123b456c", "abc", true));
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "a".to_string()), diff_match_patch::Diff::new(Operation::Insert, "b".to_string())], new_dmp.diff_main("a", "b", true));
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "Apple".to_string()), diff_match_patch::Diff::new(Operation::Insert, "Banana".to_string()), diff_match_patch::Diff::new(Operation::Equal, "s are a".to_string()), diff_match_patch::Diff::new(Operation::Insert, "lso".to_string()), diff_match_patch::Diff::new(Operation::Equal, " fruit.".to_string())], new_dmp.diff_main("Apples are a fruit.", "Bananas are also fruit.", true));
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "a".to_string()), diff_match_patch::Diff::new(Operation::Insert, "\u{0680}".to_string()), diff_match_patch::Diff::new(Operation::Equal, "x".to_string()), diff_match_patch::Diff::new(Operation::Delete, "\t".to_string()), diff_match_patch::Diff::new(Operation::Insert, "\n".to_string())], new_dmp.diff_main("ax\t", "\u{0680}x\n", false));
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "1".to_string()), diff_match_patch::Diff::new(Operation::Equal, "a".to_string()), diff_match_patch::Diff::new(Operation::Delete, "y".to_string()), diff_match_patch::Diff::new(Operation::Equal, "b".to_string()), diff_match_patch::Diff::new(Operation::Delete, "2".to_string()), diff_match_patch::Diff::new(Operation::Insert, "xab".to_string())], new_dmp.diff_main("1ayb2", "abxab", false));
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Insert, "xaxcx".to_string()), diff_match_patch::Diff::new(Operation::Equal, "abc".to_string()), diff_match_patch::Diff::new(Operation::Delete, "y".to_string())], new_dmp.diff_main("abcy", "xaxcxabc", false));
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Delete, "ABCD".to_string()), diff_match_patch::Diff::new(Operation::Equal, "a".to_string()), diff_match_patch::Diff::new(Operation::Delete, "=".to_string()), diff_match_patch::Diff::new(Operation::Insert, "-".to_string()), diff_match_patch::Diff::new(Operation::Equal, "bcd".to_string()), diff_match_patch::Diff::new(Operation::Delete, "=".to_string()), diff_match_patch::Diff::new(Operation::Insert, "-".to_string()), diff_match_patch::Diff::new(Operation::Equal, "efghijklmnopqrs".to_string()), diff_match_patch::Diff::new(Operation::Delete, "EFGHIJKLMNOefg".to_string())], new_dmp.diff_main("ABCDa=bcd=efghijklmnopqrsEFGHIJKLMNOefg", "a-bcd-efghijklmnopqrs", false));
+    assert_eq!(vec![diff_match_patch::Diff::new(Operation::Insert, " ".to_string()), diff_match_patch::Diff::new(Operation::Equal, "a".to_string()), diff_match_patch::Diff::new(Operation::Insert, "nd".to_string()), diff_match_patch::Diff::new(Operation::Equal, " [[Pennsylvania]]".to_string()), diff_match_patch::Diff::new(Operation::Delete, " and [[New".to_string())], new_dmp.diff_main("a [[Pennsylvania]] and [[New", " and [[Pennsylvania]]", false));
         
     // Test the linemode speedup.
     // Must be long to pass the 100 char cutoff.
@@ -696,7 +1701,7 @@ pub fn test_diff_main() {
 
 #[test]
 pub fn test_match_apphabet() {
-    let mut dmp = diff_match_patch::Dmp::new();
+    let dmp = diff_match_patch::Dmp::new();
     let mut s: HashMap<char,i32> = HashMap::new();
     s.insert('a', 4);
     s.insert('b', 2);
@@ -779,7 +1784,6 @@ pub fn test_match_main() {
 
     dmp.match_threshold = 0.7;
     assert_eq!(4, dmp.match_main("I am the very model of a modern major general.", " that berry ", 5));
-    dmp.match_threshold = 0.5;
 }
 
 
@@ -790,37 +1794,44 @@ pub fn test_patch_obj() {
     patch.start2 = 21;
     patch.length1 = 18;
     patch.length2 = 17;
-    patch.diffs = vec![diff_match_patch::Diff::new(0, "jump".to_string()), diff_match_patch::Diff::new(-1, "s".to_string()), diff_match_patch::Diff::new(1, "ed".to_string()), diff_match_patch::Diff::new(0, " over ".to_string()), diff_match_patch::Diff::new(-1, "the".to_string()), diff_match_patch::Diff::new(1, "a".to_string()), diff_match_patch::Diff::new(0, "\nlaz".to_string())];
+    patch.diffs = vec![diff_match_patch::Diff::new(Operation::Equal, "jump".to_string()), diff_match_patch::Diff::new(Operation::Delete, "s".to_string()), diff_match_patch::Diff::new(Operation::Insert, "ed".to_string()), diff_match_patch::Diff::new(Operation::Equal, " over ".to_string()), diff_match_patch::Diff::new(Operation::Delete, "the".to_string()), diff_match_patch::Diff::new(Operation::Insert, "a".to_string()), diff_match_patch::Diff::new(Operation::Equal, "\nlaz".to_string())];
     assert_eq!("@@ -21,18 +22,17 @@\n jump\n-s\n+ed\n  over \n-the\n+a\n %0Alaz\n".to_string(), patch.to_string());
 }
 
 
 #[test]
 pub fn test_patch_from_text() {
-    let mut dmp = diff_match_patch::Dmp::new();
+    let dmp = diff_match_patch::Dmp::new();
     let diffs: Vec<diff_match_patch::Patch> = vec![];
-    assert_eq!(diffs, dmp.patch_from_text("".to_string()));
+    assert_eq!(diffs, dmp.patch_from_text("".to_string()).unwrap());
     
     let strp = "@@ -21,18 +22,17 @@\n jump\n-s\n+ed\n  over \n-the\n+a\n %0Alaz\n".to_string();
-    assert_eq!(strp, dmp.patch_from_text(strp.clone())[0].to_string());
+    assert_eq!(strp, dmp.patch_from_text(strp.clone()).unwrap()[0].to_string());
+
+    assert_eq!("@@ -1,1 +1,1 @@\n-a\n+b\n".to_string(), dmp.patch_from_text("@@ -1 +1 @@\n-a\n+b\n".to_string()).unwrap()[0].to_string());
 
-    assert_eq!("@@ -1,1 +1,1 @@\n-a\n+b\n".to_string(), dmp.patch_from_text("@@ -1 +1 @@\n-a\n+b\n".to_string())[0].to_string());
+    assert_eq!("@@ -1,3 +0,0 @@\n-abc\n".to_string(), dmp.patch_from_text("@@ -1,3 +0,0 @@\n-abc\n".to_string()).unwrap()[0].to_string());
 
-    assert_eq!("@@ -1,3 +0,0 @@\n-abc\n".to_string(), dmp.patch_from_text("@@ -1,3 +0,0 @@\n-abc\n".to_string())[0].to_string());
+    assert_eq!("@@ -0,0 +1,3 @@\n+abc\n".to_string(), dmp.patch_from_text("@@ -0,0 +1,3 @@\n+abc\n".to_string()).unwrap()[0].to_string());
 
-    assert_eq!("@@ -0,0 +1,3 @@\n+abc\n".to_string(), dmp.patch_from_text("@@ -0,0 +1,3 @@\n+abc\n".to_string())[0].to_string());
+    // Generates errors.
+    assert_eq!(Err(diff_match_patch::DmpError::BadHeader { line: 1, column: 1 }), dmp.patch_from_text("Bad\nPatch\n".to_string()));
+    assert_eq!(Err(diff_match_patch::DmpError::BadHeader { line: 3, column: 4 }), dmp.patch_from_text("@@ -1 +1 @@\n-a\n@@ -x +1 @@\n".to_string()));
+    assert_eq!(Err(diff_match_patch::DmpError::BadLinePrefix { line: 3, column: 1, prefix: '*' }), dmp.patch_from_text("@@ -1 +1 @@\n-a\n*b\n".to_string()));
+    assert_eq!(Err(diff_match_patch::DmpError::BadEscape { line: 2, column: 3 }), dmp.patch_from_text("@@ -1 +1 @@\n+a%zz\n".to_string()));
+    assert_eq!(Err(diff_match_patch::DmpError::InvalidUtf8 { line: 2, column: 2 }), dmp.patch_from_text("@@ -1 +1 @@\n+%FF\n".to_string()));
 }
 
 #[test]
 pub fn test_patch_to_text() {
-    let mut dmp = diff_match_patch::Dmp::new();
+    let dmp = diff_match_patch::Dmp::new();
     let  mut strp = "@@ -21,18 +22,17 @@\n jump\n-s\n+ed\n  over \n-the\n+a\n  laz\n".to_string();
-    let mut p = dmp.patch_from_text(strp.clone());
-    assert_eq!(strp, dmp.patch_to_text(&mut p));
+    let mut p = dmp.patch_from_text(strp.clone()).unwrap();
+    assert_eq!(strp, dmp.patch_to_text(&p));
 
     strp = "@@ -1,9 +1,9 @@\n-f\n+F\n oo+fooba\n@@ -7,8 +7,8 @@\n obar\n-,\n+.\n tes\n".to_string();
-    p = dmp.patch_from_text(strp.clone());
-    assert_eq!(strp, dmp.patch_to_text(&mut p));
+    p = dmp.patch_from_text(strp.clone()).unwrap();
+    assert_eq!(strp, dmp.patch_to_text(&p));
 }
 
 
@@ -829,22 +1840,22 @@ pub fn test_patch_add_context()
 {
     let mut dmp = diff_match_patch::Dmp::new();
     dmp.patch_margin = 4;
-    let mut p = dmp.patch_from_text("@@ -21,4 +21,10 @@\n-jump\n+somersault\n".to_string())[0].clone();
+    let mut p = dmp.patch_from_text("@@ -21,4 +21,10 @@\n-jump\n+somersault\n".to_string()).unwrap()[0].clone();
     dmp.patch_add_context(&mut p, &mut ("The quick brown fox jumps over the lazy dog.".chars().collect::<Vec<_>>()));
     assert_eq!(p.to_string(), "@@ -17,12 +17,18 @@\n fox \n-jump\n+somersault\n s ov\n".to_string());
 
     // Same, but not enough trailing context.
-    p = dmp.patch_from_text("@@ -21,4 +21,10 @@\n-jump\n+somersault\n".to_string())[0].clone();
+    p = dmp.patch_from_text("@@ -21,4 +21,10 @@\n-jump\n+somersault\n".to_string()).unwrap()[0].clone();
     dmp.patch_add_context(&mut p, &mut ("The quick brown fox jumps.".chars().collect::<Vec<_>>()));
     assert_eq!(p.to_string(), "@@ -17,10 +17,16 @@\n fox \n-jump\n+somersault\n s.\n".to_string());
 
     // Same, but not enough leading context.
-    let mut p = dmp.patch_from_text("@@ -3 +3,2 @@\n-e\n+at\n".to_string())[0].clone();
+    let mut p = dmp.patch_from_text("@@ -3 +3,2 @@\n-e\n+at\n".to_string()).unwrap()[0].clone();
     dmp.patch_add_context(&mut p, &mut ("The quick brown fox jumps.".chars().collect::<Vec<_>>()));
     assert_eq!(p.to_string(), "@@ -1,7 +1,8 @@\n Th\n-e\n+at\n  qui\n".to_string());
 
     // # Same, but with ambiguity.
-    p = dmp.patch_from_text("@@ -3 +3,2 @@\n-e\n+at\n".to_string())[0].clone();
+    p = dmp.patch_from_text("@@ -3 +3,2 @@\n-e\n+at\n".to_string()).unwrap()[0].clone();
     dmp.patch_add_context(&mut p, &mut ("The quick brown fox jumps.  The quick brown fox crashes.".chars().collect::<Vec<_>>()));
     assert_eq!(p.to_string(), "@@ -1,27 +1,28 @@\n Th\n-e\n+at\n  quick brown fox jumps. \n".to_string());
 }
@@ -852,10 +1863,10 @@ pub fn test_patch_add_context()
 
 #[test]
 pub fn test_patch_make() {
-    let mut dmp = diff_match_patch::Dmp::new();
+    let dmp = diff_match_patch::Dmp::new();
     // Null case.
     let mut patches = dmp.patch_make1("", "");
-    assert_eq!("".to_string(), dmp.patch_to_text(&mut patches));
+    assert_eq!("".to_string(), dmp.patch_to_text(&patches));
 
     let text1 = "The quick brown fox jumps over the lazy dog.";
     let text2 = "That quick brown fox jumped over a lazy dog.";
@@ -863,33 +1874,33 @@ pub fn test_patch_make() {
     let mut expected_patch = "@@ -1,8 +1,7 @@\n Th\n-at\n+e\n  qui\n@@ -21,17 +21,18 @@\n jump\n-ed\n+s\n  over \n-a\n+the\n  laz\n".to_string();
     // The second patch must be "-21,17 +21,18", not "-22,17 +21,18" due to rolling context.
     patches = dmp.patch_make1(text2, text1);
-    assert_eq!(expected_patch, dmp.patch_to_text(&mut patches));
+    assert_eq!(expected_patch, dmp.patch_to_text(&patches));
 
     // Text1+Text2 inputs.
     expected_patch = "@@ -1,11 +1,12 @@\n Th\n-e\n+at\n  quick b\n@@ -22,18 +22,17 @@\n jump\n-s\n+ed\n  over \n-the\n+a\n  laz\n".to_string();
     patches = dmp.patch_make1(text1, text2);
-    assert_eq!(expected_patch, dmp.patch_to_text(&mut patches));
+    assert_eq!(expected_patch, dmp.patch_to_text(&patches));
 
     // Diff input.
     let mut diffs = dmp.diff_main(text1, text2, false);
-    patches = dmp.patch_make2(&mut diffs);
-    assert_eq!(expected_patch, dmp.patch_to_text(&mut patches));
+    patches = dmp.patch_make2(&diffs);
+    assert_eq!(expected_patch, dmp.patch_to_text(&patches));
 
     // Text1+Diff inputs.
-    patches = dmp.patch_make4(text1, &mut diffs);
-    assert_eq!(expected_patch, dmp.patch_to_text(&mut patches));
+    patches = dmp.patch_make4(text1, &diffs);
+    assert_eq!(expected_patch, dmp.patch_to_text(&patches));
 
     // Text1+Text2+Diff inputs (deprecated).
-    patches = dmp.patch_make3(text1, text2, &mut diffs);
-    assert_eq!(expected_patch, dmp.patch_to_text(&mut patches));
+    patches = dmp.patch_make3(text1, text2, &diffs);
+    assert_eq!(expected_patch, dmp.patch_to_text(&patches));
 
     // Character encoding.
     patches = dmp.patch_make1("`1234567890-=[]\\;',./", "~!@#$%^&*()_+{}|:\"<>?");
-    assert_eq!("@@ -1,21 +1,21 @@\n-%601234567890-=%5B%5D%5C;',./\n+~!@#$%25%5E&*()_+%7B%7D%7C:%22%3C%3E?\n".to_string(), dmp.patch_to_text(&mut patches));
+    assert_eq!("@@ -1,21 +1,21 @@\n-%601234567890-=%5B%5D%5C;',./\n+~!@#$%25%5E&*()_+%7B%7D%7C:%22%3C%3E?\n".to_string(), dmp.patch_to_text(&patches));
 
     // Character decoding.
-    diffs = vec![diff_match_patch::Diff::new(-1, "`1234567890-=[]\\;',./".to_string()), diff_match_patch::Diff::new(1, "~!@#$%^&*()_+{}|:\"<>?".to_string())];
-    assert_eq!(diffs, dmp.patch_from_text("@@ -1,21 +1,21 @@\n-%601234567890-=%5B%5D%5C;',./\n+~!@#$%25%5E&*()_+%7B%7D%7C:%22%3C%3E?\n".to_string())[0].diffs);
+    diffs = vec![diff_match_patch::Diff::new(Operation::Delete, "`1234567890-=[]\\;',./".to_string()), diff_match_patch::Diff::new(Operation::Insert, "~!@#$%^&*()_+{}|:\"<>?".to_string())];
+    assert_eq!(diffs, dmp.patch_from_text("@@ -1,21 +1,21 @@\n-%601234567890-=%5B%5D%5C;',./\n+~!@#$%25%5E&*()_+%7B%7D%7C:%22%3C%3E?\n".to_string()).unwrap()[0].diffs);
 
     // Long string with repeats.
     let mut text1 = "".to_string();
@@ -899,7 +1910,7 @@ pub fn test_patch_make() {
     let text2 = text1.clone() + "123";
     expected_patch = "@@ -573,28 +573,31 @@\n cdefabcdefabcdefabcdefabcdef\n+123\n".to_string();
     patches = dmp.patch_make1(text1.as_str(), text2.as_str());
-    assert_eq!(expected_patch, dmp.patch_to_text(&mut patches));
+    assert_eq!(expected_patch, dmp.patch_to_text(&patches));
 
 }
 
@@ -910,43 +1921,43 @@ pub fn test_patch_splitmax() {
     dmp.match_maxbits = 32;
     let mut patches = dmp.patch_make1("abcdefghijklmnopqrstuvwxyz01234567890", "XabXcdXefXghXijXklXmnXopXqrXstXuvXwxXyzX01X23X45X67X89X0");
     dmp.patch_splitmax(&mut patches);
-    assert_eq!("@@ -1,32 +1,46 @@\n+X\n ab\n+X\n cd\n+X\n ef\n+X\n gh\n+X\n ij\n+X\n kl\n+X\n mn\n+X\n op\n+X\n qr\n+X\n st\n+X\n uv\n+X\n wx\n+X\n yz\n+X\n 012345\n@@ -25,13 +39,18 @@\n zX01\n+X\n 23\n+X\n 45\n+X\n 67\n+X\n 89\n+X\n 0\n".to_string(), dmp.patch_to_text(&mut patches));
+    assert_eq!("@@ -1,32 +1,46 @@\n+X\n ab\n+X\n cd\n+X\n ef\n+X\n gh\n+X\n ij\n+X\n kl\n+X\n mn\n+X\n op\n+X\n qr\n+X\n st\n+X\n uv\n+X\n wx\n+X\n yz\n+X\n 012345\n@@ -25,13 +39,18 @@\n zX01\n+X\n 23\n+X\n 45\n+X\n 67\n+X\n 89\n+X\n 0\n".to_string(), dmp.patch_to_text(&patches));
 
     patches = dmp.patch_make1("abcdef1234567890123456789012345678901234567890123456789012345678901234567890uvwxyz", "abcdefuvwxyz");
-    let old_totext = dmp.patch_to_text(&mut patches);
+    let old_totext = dmp.patch_to_text(&patches);
     dmp.patch_splitmax(&mut patches);
-    assert_eq!(old_totext, dmp.patch_to_text(&mut patches));
+    assert_eq!(old_totext, dmp.patch_to_text(&patches));
 
     patches = dmp.patch_make1("1234567890123456789012345678901234567890123456789012345678901234567890", "abc");
     dmp.patch_splitmax(&mut patches);
-    assert_eq!("@@ -1,32 +1,4 @@\n-1234567890123456789012345678\n 9012\n@@ -29,32 +1,4 @@\n-9012345678901234567890123456\n 7890\n@@ -57,14 +1,3 @@\n-78901234567890\n+abc\n", dmp.patch_to_text(&mut patches));
+    assert_eq!("@@ -1,32 +1,4 @@\n-1234567890123456789012345678\n 9012\n@@ -29,32 +1,4 @@\n-9012345678901234567890123456\n 7890\n@@ -57,14 +1,3 @@\n-78901234567890\n+abc\n", dmp.patch_to_text(&patches));
 
     patches = dmp.patch_make1("abcdefghij , h : 0 , t : 1 abcdefghij , h : 0 , t : 1 abcdefghij , h : 0 , t : 1", "abcdefghij , h : 1 , t : 1 abcdefghij , h : 1 , t : 1 abcdefghij , h : 0 , t : 1");
     dmp.patch_splitmax(&mut patches);
-    assert_eq!("@@ -2,32 +2,32 @@\n bcdefghij , h : \n-0\n+1\n  , t : 1 abcdef\n@@ -29,32 +29,32 @@\n bcdefghij , h : \n-0\n+1\n  , t : 1 abcdef\n".to_string(), dmp.patch_to_text(&mut patches));
+    assert_eq!("@@ -2,32 +2,32 @@\n bcdefghij , h : \n-0\n+1\n  , t : 1 abcdef\n@@ -29,32 +29,32 @@\n bcdefghij , h : \n-0\n+1\n  , t : 1 abcdef\n".to_string(), dmp.patch_to_text(&patches));
 }
 
 
 #[test]
 pub fn test_patch_add_padding() {
     // Both edges full.
-    let mut dmp = diff_match_patch::Dmp::new();
+    let dmp = diff_match_patch::Dmp::new();
     let mut patches = dmp.patch_make1("", "test");
-    assert_eq!("@@ -0,0 +1,4 @@\n+test\n".to_string(), dmp.patch_to_text(&mut patches));
+    assert_eq!("@@ -0,0 +1,4 @@\n+test\n".to_string(), dmp.patch_to_text(&patches));
     dmp.patch_add_padding(&mut patches);
-    assert_eq!("@@ -1,8 +1,12 @@\n %01%02%03%04\n+test\n %01%02%03%04\n".to_string(), dmp.patch_to_text(&mut patches));
+    assert_eq!("@@ -1,8 +1,12 @@\n %01%02%03%04\n+test\n %01%02%03%04\n".to_string(), dmp.patch_to_text(&patches));
 
     // Both edges partial.
     patches = dmp.patch_make1("XY", "XtestY");
-    assert_eq!("@@ -1,2 +1,6 @@\n X\n+test\n Y\n".to_string(), dmp.patch_to_text(&mut patches));
+    assert_eq!("@@ -1,2 +1,6 @@\n X\n+test\n Y\n".to_string(), dmp.patch_to_text(&patches));
     dmp.patch_add_padding(&mut patches);
-    assert_eq!("@@ -2,8 +2,12 @@\n %02%03%04X\n+test\n Y%01%02%03\n".to_string(), dmp.patch_to_text(&mut patches));
+    assert_eq!("@@ -2,8 +2,12 @@\n %02%03%04X\n+test\n Y%01%02%03\n".to_string(), dmp.patch_to_text(&patches));
 
     // Both edges none.
     patches = dmp.patch_make1("XXXXYYYY", "XXXXtestYYYY");
-    assert_eq!("@@ -1,8 +1,12 @@\n XXXX\n+test\n YYYY\n".to_string(), dmp.patch_to_text(&mut patches));
+    assert_eq!("@@ -1,8 +1,12 @@\n XXXX\n+test\n YYYY\n".to_string(), dmp.patch_to_text(&patches));
     dmp.patch_add_padding(&mut patches);
-    assert_eq!("@@ -5,8 +5,12 @@\n XXXX\n+test\n YYYY\n".to_string(), dmp.patch_to_text(&mut patches));
+    assert_eq!("@@ -5,8 +5,12 @@\n XXXX\n+test\n YYYY\n".to_string(), dmp.patch_to_text(&patches));
 }
 
 
@@ -958,36 +1969,36 @@ pub fn test_patch_apply() {
     dmp.patch_delete_threshold = 0.5;
     // Null case.
     let mut patches = dmp.patch_make1("", "");
-    let mut results = dmp.patch_apply(&mut patches, "Hello world.");
+    let mut results = dmp.patch_apply(&patches, "Hello world.");
     assert_eq!(("Hello world.".chars().collect(), vec![]), results);
 
     // Exact match.
     patches = dmp.patch_make1("The quick brown fox jumps over the lazy dog.", "That quick brown fox jumped over a lazy dog.");
-    results = dmp.patch_apply(&mut patches, "The quick brown fox jumps over the lazy dog.");
+    results = dmp.patch_apply(&patches, "The quick brown fox jumps over the lazy dog.");
     assert_eq!(("That quick brown fox jumped over a lazy dog.".chars().collect(), vec![true, true]), results);
 
     // Partial match.
-    results = dmp.patch_apply(&mut patches, "The quick red rabbit jumps over the tired tiger.");
+    results = dmp.patch_apply(&patches, "The quick red rabbit jumps over the tired tiger.");
     assert_eq!(("That quick red rabbit jumped over a tired tiger.".chars().collect(), vec![true, true]), results);
 
     // Failed match.
-    results = dmp.patch_apply(&mut patches, "I am the very model of a modern major general.");
+    results = dmp.patch_apply(&patches, "I am the very model of a modern major general.");
     assert_eq!(("I am the very model of a modern major general.".chars().collect(), vec![false, false]), results);
 
     // Big delete, small change.
     patches = dmp.patch_make1("x1234567890123456789012345678901234567890123456789012345678901234567890y", "xabcy");
-    results = dmp.patch_apply(&mut patches, "x123456789012345678901234567890-----++++++++++-----123456789012345678901234567890y");
+    results = dmp.patch_apply(&patches, "x123456789012345678901234567890-----++++++++++-----123456789012345678901234567890y");
     assert_eq!(("xabcy".chars().collect(), vec![true, true]), results);
 
     // Big delete, big change 1.
     patches = dmp.patch_make1("x1234567890123456789012345678901234567890123456789012345678901234567890y", "xabcy");
-    results = dmp.patch_apply(&mut patches, "x12345678901234567890---------------++++++++++---------------12345678901234567890y");
+    results = dmp.patch_apply(&patches, "x12345678901234567890---------------++++++++++---------------12345678901234567890y");
     assert_eq!(("xabc12345678901234567890---------------++++++++++---------------12345678901234567890y".chars().collect(), vec![false, true]), results);
 
     // Big delete, big change 2.
     dmp.patch_delete_threshold = 0.6;
     patches = dmp.patch_make1("x1234567890123456789012345678901234567890123456789012345678901234567890y", "xabcy");
-    results = dmp.patch_apply(&mut patches, "x12345678901234567890---------------++++++++++---------------12345678901234567890y");
+    results = dmp.patch_apply(&patches, "x12345678901234567890---------------++++++++++---------------12345678901234567890y");
     assert_eq!(("xabcy".chars().collect(), vec![true, true]), results);
     dmp.patch_delete_threshold = 0.5;
 
@@ -995,40 +2006,87 @@ pub fn test_patch_apply() {
     dmp.match_threshold = 0.0;
     dmp.match_distance = 0;
     patches = dmp.patch_make1("abcdefghijklmnopqrstuvwxyz--------------------1234567890", "abcXXXXXXXXXXdefghijklmnopqrstuvwxyz--------------------1234567YYYYYYYYYY890");
-    results = dmp.patch_apply(&mut patches, "ABCDEFGHIJKLMNOPQRSTUVWXYZ--------------------1234567890");
+    results = dmp.patch_apply(&patches, "ABCDEFGHIJKLMNOPQRSTUVWXYZ--------------------1234567890");
     assert_eq!(("ABCDEFGHIJKLMNOPQRSTUVWXYZ--------------------1234567YYYYYYYYYY890".chars().collect(), vec![false, true]), results);
     dmp.match_threshold = 0.5;
     dmp.match_distance = 1000;
 
     // No side effects.
     patches = dmp.patch_make1("", "test");
-    let mut patchstr = dmp.patch_to_text(&mut patches);
-    results = dmp.patch_apply(&mut patches, "");
-    assert_eq!(patchstr, dmp.patch_to_text(&mut patches));
+    let mut patchstr = dmp.patch_to_text(&patches);
+    results = dmp.patch_apply(&patches, "");
+    assert_eq!(patchstr, dmp.patch_to_text(&patches));
 
     // No side effects with major delete.
     patches = dmp.patch_make1("The quick brown fox jumps over the lazy dog.", "Woof");
-    patchstr = dmp.patch_to_text(&mut patches);
-    dmp.patch_apply(&mut patches, "The quick brown fox jumps over the lazy dog.");
-    assert_eq!(patchstr, dmp.patch_to_text(&mut patches));
+    patchstr = dmp.patch_to_text(&patches);
+    dmp.patch_apply(&patches, "The quick brown fox jumps over the lazy dog.");
+    assert_eq!(patchstr, dmp.patch_to_text(&patches));
 
     // Edge exact match.
     patches = dmp.patch_make1("", "test");
-    dmp.patch_apply(&mut patches, "");
+    dmp.patch_apply(&patches, "");
     assert_eq!(("test".chars().collect(), vec![true]), results);
 
     // Near edge exact match.
     patches = dmp.patch_make1("XY", "XtestY");
-    results = dmp.patch_apply(&mut patches, "XY");
+    results = dmp.patch_apply(&patches, "XY");
     assert_eq!(("XtestY".chars().collect(), vec![true]), results);
 
     // Edge partial match.
     patches = dmp.patch_make1("y", "y123");
-    results = dmp.patch_apply(&mut patches, "x");
+    results = dmp.patch_apply(&patches, "x");
     assert_eq!(("x123".chars().collect(), vec![true]), results);
 
     // Applying "delete" patch on an empty text.
     patches = dmp.patch_make1("test", "");
-    results = dmp.patch_apply(&mut patches, "");
+    results = dmp.patch_apply(&patches, "");
     assert_eq!(("".chars().collect(), vec![true]), results);
 }
+
+#[test]
+pub fn test_diff_bytes() {
+    let dmp = diff_match_patch::Dmp::new();
+    // Not valid UTF-8.
+    let bytes1: &[u8] = b"\xff\x00abc\xfe";
+    let bytes2: &[u8] = b"\xff\x01abc";
+    let diffs = dmp.diff_bytes(bytes1, bytes2);
+    assert_eq!(vec![diff_match_patch::DiffOp::new(Operation::Equal, vec![0xff]), diff_match_patch::DiffOp::new(Operation::Delete, vec![0x00]), diff_match_patch::DiffOp::new(Operation::Insert, vec![0x01]), diff_match_patch::DiffOp::new(Operation::Equal, b"abc".to_vec()), diff_match_patch::DiffOp::new(Operation::Delete, vec![0xfe])], diffs);
+
+    let delta = dmp.diff_bytes_todelta(&diffs);
+    assert_eq!("=1\t-1\t+%01\t=3\t-1", delta);
+    assert_eq!(Ok(diffs), dmp.diff_bytes_from_delta(bytes1, &delta));
+
+    // Inserted text keeps the safe characters readable.
+    let diffs = vec![diff_match_patch::DiffOp::new(Operation::Insert, b"a b%\xc3".to_vec())];
+    assert_eq!("+a b%25%C3", dmp.diff_bytes_todelta(&diffs));
+    assert_eq!(Ok(diffs), dmp.diff_bytes_from_delta(b"", "+a b%25%C3"));
+
+    // Generates errors.
+    assert_eq!(Err(diff_match_patch::DmpError::LengthMismatch { line: 1, column: 3, expected: 6, actual: 5 }), dmp.diff_bytes_from_delta(bytes1, "=5"));
+    assert_eq!(Err(diff_match_patch::DmpError::LengthMismatch { line: 1, column: 4, expected: 6, actual: usize::MAX }), dmp.diff_bytes_from_delta(bytes1, &format!("=1\t={}", u64::MAX)));
+    assert_eq!(Err(diff_match_patch::DmpError::BadEscape { line: 1, column: 2 }), dmp.diff_bytes_from_delta(b"", "+%G1"));
+}
+
+#[test]
+pub fn test_patch_bytes() {
+    let dmp = diff_match_patch::Dmp::new();
+    let bytes1: Vec<u8> = (0..=255).collect();
+    let mut bytes2 = bytes1.clone();
+    bytes2[100] = 0;
+    bytes2.splice(200..203, vec![0xff, 0xfe]);
+    let patches = dmp.patch_make_bytes(&bytes1, &bytes2);
+    assert_eq!((bytes2.clone(), vec![true, true]), dmp.patch_apply_bytes(&patches, &bytes1));
+
+    // Same patches from a byte diff.
+    let diffs = dmp.diff_bytes(&bytes1, &bytes2);
+    let diff_patches = dmp.patch_make_bytes_diffs(&bytes1, &diffs);
+    assert_eq!(bytes2, dmp.patch_apply_bytes(&diff_patches, &bytes1).0);
+
+    // Fuzzy application to drifted bytes.
+    let mut drifted = vec![0x80; 10];
+    drifted.extend(&bytes1);
+    let mut expected = vec![0x80; 10];
+    expected.extend(&bytes2);
+    assert_eq!((expected, vec![true, true]), dmp.patch_apply_bytes(&patches, &drifted));
+}